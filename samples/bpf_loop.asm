@@ -0,0 +1,19 @@
+; Runs a callback on a counter in the stack 16 times, without a loop invariant.
+    mov r1 0
+    stxdw [r10 - 8] r1
+    mov r1 16
+    ldfunc r2 step
+    mov r3 r10
+    sub r3 8
+    mov r4 0
+    call 181            ; loop
+    ldxdw r0 [r10 - 8]
+    exit
+
+;# function step
+;# requires is_buffer(r2, 8)
+    ldxdw r3 [r2]
+    add r3 1
+    stxdw [r2] r3
+    mov r0 0
+    exit
@@ -0,0 +1,12 @@
+;# requires is_buffer(r1, r2)
+;# requires r2 = 64
+    mov r3, 0 ; idx
+    mov r0, 0
+loop:
+    mov r4 r1
+    add r4 r3
+    ldxb r4 [r4]
+    add r0 r4      ; load byte and add to sum
+    add r3 1
+    jlt r3 64 loop
+    exit
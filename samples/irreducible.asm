@@ -0,0 +1,14 @@
+; Irreducible loop
+; The loop can be entered at both a and b, so both need an invariant.
+
+    mov r0 0
+    mov r1 0
+    jgt r2 5 b
+a:
+;# req r1 < 10
+    add r1 1
+b:
+;# req r1 <= 10
+    jlt r1 10 a
+    mov r0 r1
+    exit
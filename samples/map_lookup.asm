@@ -0,0 +1,14 @@
+;# map 1 value_size 16
+; Reads the second counter of the map value at key 0, or returns 0 if there is none.
+    mov r1 0
+    stxdw [r10 - 8] r1
+    ldmapfd r1 1
+    mov r2 r10
+    sub r2 8
+    call 1              ; map_lookup_elem
+    mov r6 r0
+    mov r0 0
+    jeq r6 0 miss       ; the value is null if the key isn't present
+    ldxdw r0 [r6 + 8]
+miss:
+    exit
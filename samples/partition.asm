@@ -33,5 +33,6 @@ caseC:
 continue:
     ja loop
 return:
+    mov r0 0
     exit
 ; done
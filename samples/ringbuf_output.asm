@@ -0,0 +1,17 @@
+; Sends the current time through a ring buffer.
+    ldmapfd r1 1
+    mov r2 8
+    mov r3 0
+    call 131            ; ringbuf_reserve
+    mov r6 r0
+    jeq r6 0 fail       ; nothing is reserved if the buffer is full
+    call 5              ; ktime_get_ns
+    stxdw [r6] r0
+    mov r1 r6
+    mov r2 0
+    call 132            ; ringbuf_submit
+    mov r0 0
+    exit
+fail:
+    mov r0 1
+    exit
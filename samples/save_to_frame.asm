@@ -1,8 +1,7 @@
 ;# requires is_buffer(r1, r2)
 ;# requires r2 = 8
-;# requires is_buffer(r10, r9)
-;# requires r9 = 512
 stxdw [r10 + -8] r1
 ldxdw r1 [r10 + -8] ; erases knowledge of r1
 ldxdw r3 [r1]
+mov r0 0
 exit
@@ -2,6 +2,10 @@
     mov r1 0
     mov r2 32
 loop:
+;# req r2 = 32
+;# req r1 < 32
+;# variant sub(r2, r1)
+;# bound 32
     add r0 r1
     add r1 1
     jlt r1 r2 loop
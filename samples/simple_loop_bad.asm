@@ -2,6 +2,8 @@
     mov r1 0
     mov r2 32
 loop:
+;# req r2 = 32
+;# variant sub(r2, r1)
     add r0 r1
     add r1 1
     jeq r1 r1 loop
@@ -0,0 +1,14 @@
+;# requires r1 <= 100
+;# ensures r0 <= 201
+    mov r6 1
+    call double         ; only the contract of the subprogram is used here
+    add r0 r6
+    exit
+
+;# function double
+;# requires r1 <= 100
+;# ensures r0 <= 200
+    mov r6 r1           ; r6 is restored for the caller
+    mov r0 r6
+    add r0 r1
+    exit
@@ -0,0 +1,9 @@
+;# map 1 value_size 4
+;# tail_requires is_buffer(r1, 24)
+; Dispatches to the program at index 0 of a program array, or drops the packet.
+; Verify with `--prog-type xdp`.
+    ldmapfd r2 1
+    mov r3 0
+    call 12             ; tail_call, which only returns if it fails
+    mov r0 1            ; XDP_DROP
+    exit
@@ -1,3 +1,4 @@
+    mov r0 0
     jgt r2 1000 end
     mov r3 0
 loop:
@@ -0,0 +1,13 @@
+; Drops IPv6 packets and passes everything else.
+; Verify with `--prog-type xdp`.
+    ldxw r2 [r1]        ; r2 = ctx->data
+    ldxw r3 [r1 + 4]    ; r3 = ctx->data_end
+    mov r0 2            ; XDP_PASS
+    mov r4 r2
+    add r4 14
+    jgt r4 r3 end       ; bounds check for the ethernet header
+    ldxh r5 [r2 + 12]   ; ethertype
+    jne r5 0xdd86 end
+    mov r0 1            ; XDP_DROP
+end:
+    exit
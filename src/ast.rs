@@ -6,6 +6,18 @@ pub enum WordSize {
     B8, B16, B32, B64,
 }
 
+impl WordSize {
+    /// Number of bytes covered by a word of this size.
+    pub fn bytes(&self) -> i64 {
+        match self {
+            WordSize::B8 => 1,
+            WordSize::B16 => 2,
+            WordSize::B32 => 4,
+            WordSize::B64 => 8,
+        }
+    }
+}
+
 #[rustfmt::skip]
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum Cc {
@@ -24,7 +36,7 @@ pub enum BinAlu {
     Mov, Add, Sub, Mul, Div, Mod, And, Or, Xor, Lsh, Rsh, Arsh,
 }
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
 pub struct Reg(u8);
 impl Reg {
     pub const R0: Self = Reg(0);
@@ -75,7 +87,11 @@ pub enum Stmt {
     Load(WordSize, Reg, MemRef),
     LoadImm(Reg, Imm),
     LoadMapFd(Reg, Imm),
+    /// Load a pointer to a subprogram, to be passed as a callback.
+    LoadFunc(Reg, Label),
     Call(Imm),
+    /// Call of a subprogram of the module.
+    CallLocal(Label),
 }
 
 #[derive(Debug, Clone, PartialEq, Eq)]
@@ -119,12 +135,19 @@ pub enum Formula {
     Quant(QType, Ident, Box<Formula>),
     Rel(Cc, Expr, Expr),
     IsBuffer(Ident, Expr),
+    /// The register holds an acquired reference.
+    /// It is checked by reference tracking rather than the verification conditions.
+    Held(Ident),
 }
 
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub enum Logic {
     Assert(Formula),
     Require(Formula),
+    /// A measure that decreases on every iteration of the loop starting at the block.
+    Variant(Expr),
+    /// Maximum number of iterations of the loop starting at the block.
+    Bound(Imm),
 }
 
 #[derive(Debug, Clone, PartialEq, Eq)]
@@ -135,8 +158,27 @@ pub enum Line {
     Cont(Cont),
 }
 
+/// Declaration of a map that the program refers to by its file descriptor.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MapDef {
+    pub fd: Imm,
+    pub value_size: i64,
+}
+
+/// A subprogram that is verified against its own contract.
+pub struct Function {
+    pub name: Label,
+    pub requires: Vec<Formula>,
+    pub ensures: Vec<Formula>,
+    pub lines: Vec<Line>,
+}
+
 pub struct Module {
+    pub maps: Vec<MapDef>,
     pub requires: Vec<Formula>,
     pub ensures: Vec<Formula>,
+    /// Precondition of the programs that the module tail calls.
+    pub tail_requires: Vec<Formula>,
     pub lines: Vec<Line>,
+    pub functions: Vec<Function>,
 }
@@ -0,0 +1,1072 @@
+//! Tracking of known bits and bounds of registers the way the kernel verifier does.
+//! Each register has a [Tnum] along with signed and unsigned, 32- and 64-bit minimums and maximums,
+//! which are refined along the branches of conditional jumps.
+//! Pointers are tracked by their offset, with their region given by the register types.
+//! Packet pointers also carry the number of bytes that comparisons against the packet end prove to exist.
+//! This predicts which accesses the kernel rejects without any annotations,
+//! and the verification conditions can assume the facts it finds,
+//! in which case they are only sound if the transfer functions here are.
+
+use std::{
+    collections::{BTreeSet, HashMap, HashSet},
+    fmt::{self, Display, Formatter},
+};
+
+use crate::{
+    cfg::*,
+    dataflow::{solve, Analysis, Direction, Lattice},
+    formula::FormulaBuilder,
+    interval::{constants, negated},
+    tnum::Tnum,
+    types::{RegType, RegTypes, Region, TypeInfo},
+    vc::STACK_SIZE,
+};
+
+/// The kernel rejects pointers whose offset might exceed this many bytes.
+const MAX_VAR_OFF: i64 = 1 << 29;
+
+/// What the kernel knows about a scalar, or about the offset of a pointer.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Scalar {
+    pub var_off: Tnum,
+    pub umin: u64,
+    pub umax: u64,
+    pub smin: i64,
+    pub smax: i64,
+    pub u32_min: u32,
+    pub u32_max: u32,
+    pub s32_min: i32,
+    pub s32_max: i32,
+}
+
+impl Scalar {
+    pub const UNKNOWN: Scalar = Scalar {
+        var_off: Tnum::UNKNOWN,
+        umin: 0,
+        umax: u64::MAX,
+        smin: i64::MIN,
+        smax: i64::MAX,
+        u32_min: 0,
+        u32_max: u32::MAX,
+        s32_min: i32::MIN,
+        s32_max: i32::MAX,
+    };
+
+    pub fn constant(value: u64) -> Self {
+        Scalar {
+            var_off: Tnum::constant(value),
+            umin: value,
+            umax: value,
+            smin: value as i64,
+            smax: value as i64,
+            u32_min: value as u32,
+            u32_max: value as u32,
+            s32_min: value as i32,
+            s32_max: value as i32,
+        }
+    }
+
+    /// Any value of the given range, zero-extended from 32 bits.
+    fn zext32(umin: u32, umax: u32) -> Self {
+        let mut s = Scalar::UNKNOWN;
+        s.var_off = Tnum::range(umin as u64, umax as u64);
+        s.umin = umin as u64;
+        s.umax = umax as u64;
+        s.sync()
+    }
+
+    /// Any value of a word of the given size, zero-extended.
+    fn word(size: WordSize) -> Self {
+        match size {
+            WordSize::B64 => Scalar::UNKNOWN,
+            size => Scalar::zext32(0, (u64::MAX >> (64 - 8 * size.bytes())) as u32),
+        }
+    }
+
+    pub fn as_const(&self) -> Option<u64> {
+        self.var_off.is_const().then_some(self.var_off.value)
+    }
+
+    fn is_empty(&self) -> bool {
+        self.umin > self.umax
+            || self.smin > self.smax
+            || self.u32_min > self.u32_max
+            || self.s32_min > self.s32_max
+    }
+
+    /// Tighten the bounds using each other and the known bits, as `reg_bounds_sync` does.
+    fn sync(mut self) -> Self {
+        self.update_bounds();
+        self.deduce_bounds();
+        self.bound_offset();
+        self.update_bounds();
+        self
+    }
+
+    fn update_bounds(&mut self) {
+        let Tnum { value, mask } = self.var_off;
+        let sub = self.var_off.subreg();
+        self.s32_min = self
+            .s32_min
+            .max((sub.value | (sub.mask & i32::MIN as u32 as u64)) as u32 as i32);
+        self.s32_max = self
+            .s32_max
+            .min((sub.value | (sub.mask & i32::MAX as u64)) as u32 as i32);
+        self.u32_min = self.u32_min.max(sub.value as u32);
+        self.u32_max = self.u32_max.min((sub.value | sub.mask) as u32);
+        self.smin = self.smin.max((value | (mask & i64::MIN as u64)) as i64);
+        self.smax = self.smax.min((value | (mask & i64::MAX as u64)) as i64);
+        self.umin = self.umin.max(value);
+        self.umax = self.umax.min(value | mask);
+    }
+
+    fn deduce_bounds(&mut self) {
+        // The signed and unsigned bounds agree if the sign is known.
+        if self.s32_min >= 0 || self.s32_max < 0 {
+            self.u32_min = self.u32_min.max(self.s32_min as u32);
+            self.u32_max = self.u32_max.min(self.s32_max as u32);
+            (self.s32_min, self.s32_max) = (self.u32_min as i32, self.u32_max as i32);
+        } else if (self.u32_max as i32) >= 0 {
+            self.s32_min = self.u32_min as i32;
+            self.u32_max = self.u32_max.min(self.s32_max as u32);
+            self.s32_max = self.u32_max as i32;
+        } else if (self.u32_min as i32) < 0 {
+            self.u32_min = self.u32_min.max(self.s32_min as u32);
+            self.s32_min = self.u32_min as i32;
+            self.s32_max = self.u32_max as i32;
+        }
+
+        if self.smin >= 0 || self.smax < 0 {
+            self.umin = self.umin.max(self.smin as u64);
+            self.umax = self.umax.min(self.smax as u64);
+            (self.smin, self.smax) = (self.umin as i64, self.umax as i64);
+        } else if (self.umax as i64) >= 0 {
+            self.smin = self.umin as i64;
+            self.umax = self.umax.min(self.smax as u64);
+            self.smax = self.umax as i64;
+        } else if (self.umin as i64) < 0 {
+            self.umin = self.umin.max(self.smin as u64);
+            self.smin = self.umin as i64;
+            self.smax = self.umax as i64;
+        }
+    }
+
+    fn bound_offset(&mut self) {
+        let var64 = self.var_off.intersect(Tnum::range(self.umin, self.umax));
+        let var32 = var64
+            .subreg()
+            .intersect(Tnum::range(self.u32_min as u64, self.u32_max as u64));
+        self.var_off = var64.clear_subreg().or(var32);
+    }
+
+    /// The lower 32 bits, zero-extended.
+    fn subreg(&self) -> Self {
+        let mut s = Scalar::zext32(self.u32_min, self.u32_max);
+        s.var_off = s.var_off.intersect(self.var_off.subreg());
+        s.sync()
+    }
+
+    fn join(&self, other: &Scalar) -> Scalar {
+        Scalar {
+            var_off: self.var_off.union(other.var_off),
+            umin: self.umin.min(other.umin),
+            umax: self.umax.max(other.umax),
+            smin: self.smin.min(other.smin),
+            smax: self.smax.max(other.smax),
+            u32_min: self.u32_min.min(other.u32_min),
+            u32_max: self.u32_max.max(other.u32_max),
+            s32_min: self.s32_min.min(other.s32_min),
+            s32_max: self.s32_max.max(other.s32_max),
+        }
+    }
+
+    /// Apply a 64-bit operation, as `adjust_scalar_min_max_vals` does.
+    fn binary(op: BinAlu, a: Scalar, b: Scalar) -> Scalar {
+        let mut r = Scalar::UNKNOWN;
+        match op {
+            BinAlu::Mov => return b,
+            BinAlu::Add => {
+                r.var_off = a.var_off + b.var_off;
+                if let (Some(smin), Some(smax)) =
+                    (a.smin.checked_add(b.smin), a.smax.checked_add(b.smax))
+                {
+                    (r.smin, r.smax) = (smin, smax);
+                }
+                if let (Some(umin), Some(umax)) =
+                    (a.umin.checked_add(b.umin), a.umax.checked_add(b.umax))
+                {
+                    (r.umin, r.umax) = (umin, umax);
+                }
+            }
+            BinAlu::Sub => {
+                r.var_off = a.var_off - b.var_off;
+                if let (Some(smin), Some(smax)) =
+                    (a.smin.checked_sub(b.smax), a.smax.checked_sub(b.smin))
+                {
+                    (r.smin, r.smax) = (smin, smax);
+                }
+                if a.umin >= b.umax {
+                    (r.umin, r.umax) = (a.umin - b.umax, a.umax - b.umin);
+                }
+            }
+            BinAlu::Mul => {
+                r.var_off = a.var_off * b.var_off;
+                let small = a.umax <= u32::MAX as u64 && b.umax <= u32::MAX as u64;
+                if a.smin >= 0 && b.smin >= 0 && small {
+                    (r.umin, r.umax) = (a.umin * b.umin, a.umax * b.umax);
+                }
+            }
+            BinAlu::And => {
+                r.var_off = a.var_off.and(b.var_off);
+                (r.umin, r.umax) = (r.var_off.value, a.umax.min(b.umax));
+                if a.smin >= 0 && b.smin >= 0 {
+                    (r.smin, r.smax) = (r.umin as i64, r.umax as i64);
+                }
+            }
+            BinAlu::Or => {
+                r.var_off = a.var_off.or(b.var_off);
+                (r.umin, r.umax) = (a.umin.max(b.umin), r.var_off.max());
+                if a.smin >= 0 && b.smin >= 0 {
+                    (r.smin, r.smax) = (r.umin as i64, r.umax as i64);
+                }
+            }
+            BinAlu::Xor => {
+                r.var_off = a.var_off.xor(b.var_off);
+                (r.umin, r.umax) = (r.var_off.min(), r.var_off.max());
+                if a.smin >= 0 && b.smin >= 0 {
+                    (r.smin, r.smax) = (r.umin as i64, r.umax as i64);
+                }
+            }
+            // Like `is_safe_to_compute_dst_reg_range`, only shifts by a constant amount within
+            // range are tracked, and any other shift leaves the result unknown.
+            BinAlu::Lsh | BinAlu::Rsh | BinAlu::Arsh if b.as_const().is_none_or(|n| n >= 64) => (),
+            BinAlu::Lsh => {
+                let n = b.umin as u32;
+                r.var_off = a.var_off.lshift(n);
+                if a.umax.leading_zeros() >= n {
+                    (r.umin, r.umax) = (a.umin << n, a.umax << n);
+                }
+            }
+            BinAlu::Rsh => {
+                let n = b.umin as u32;
+                r.var_off = a.var_off.rshift(n);
+                (r.umin, r.umax) = (a.umin >> n, a.umax >> n);
+            }
+            BinAlu::Arsh => {
+                let n = b.umin as u32;
+                r.var_off = a.var_off.arshift(n, 64);
+                (r.smin, r.smax) = (a.smin >> n, a.smax >> n);
+            }
+            // Division is left unknown by the kernel.
+            BinAlu::Div | BinAlu::Mod => (),
+        }
+        r.sync()
+    }
+
+    /// Apply a 32-bit operation to the lower halves, zero-extending the result.
+    fn binary32(op: BinAlu, a: Scalar, b: Scalar) -> Scalar {
+        let (a, b) = (a.subreg(), b.subreg());
+        let r = match op {
+            BinAlu::Lsh | BinAlu::Rsh | BinAlu::Arsh if b.as_const().is_none_or(|n| n >= 32) => {
+                Scalar::UNKNOWN
+            }
+            // The sign bit is bit 31, so only nonnegative values shift like 64-bit ones.
+            BinAlu::Arsh if a.s32_min < 0 => Scalar {
+                var_off: a.var_off.arshift(b.umin as u32, 32),
+                ..Scalar::UNKNOWN
+            },
+            op => Scalar::binary(op, a, b),
+        };
+        let mut s = match r.umax <= u32::MAX as u64 {
+            true => Scalar::zext32(r.umin as u32, r.umax as u32),
+            false => Scalar::zext32(0, u32::MAX),
+        };
+        s.var_off = s.var_off.intersect(r.var_off.subreg());
+        s.sync()
+    }
+
+    /// Restrict `self cc other` for a comparison that holds, as `reg_set_min_max` does.
+    /// Returns `None` if it can't hold.
+    fn assume(cc: Cc, mut a: Scalar, mut b: Scalar) -> Option<(Scalar, Scalar)> {
+        match cc {
+            Cc::Eq => {
+                let var_off = a.var_off.intersect(b.var_off);
+                let meet = Scalar {
+                    var_off,
+                    umin: a.umin.max(b.umin),
+                    umax: a.umax.min(b.umax),
+                    smin: a.smin.max(b.smin),
+                    smax: a.smax.min(b.smax),
+                    u32_min: a.u32_min.max(b.u32_min),
+                    u32_max: a.u32_max.min(b.u32_max),
+                    s32_min: a.s32_min.max(b.s32_min),
+                    s32_max: a.s32_max.min(b.s32_max),
+                };
+                // Known bits that disagree leave no values.
+                if !a.var_off.contains(&var_off) || !b.var_off.contains(&var_off) {
+                    return None;
+                }
+                (a, b) = (meet, meet);
+            }
+            Cc::Ne => match (a.as_const(), b.as_const()) {
+                (Some(x), Some(y)) if x == y => return None,
+                (_, Some(y)) => a = a.exclude(y),
+                (Some(x), _) => b = b.exclude(x),
+                _ => (),
+            },
+            Cc::Set => match b.as_const() {
+                Some(0) => return None,
+                Some(y) if y.is_power_of_two() => {
+                    a.var_off = a.var_off.or(Tnum::constant(y));
+                }
+                _ => (),
+            },
+            Cc::Gt | Cc::Ge | Cc::Lt | Cc::Le => {
+                let strict = matches!(cc, Cc::Gt | Cc::Lt) as u64;
+                // Bring it into the form `lo < hi` or `lo <= hi`.
+                let (lo, hi) = match cc {
+                    Cc::Lt | Cc::Le => (&mut a, &mut b),
+                    _ => (&mut b, &mut a),
+                };
+                lo.umax = lo.umax.min(hi.umax.checked_sub(strict)?);
+                hi.umin = hi.umin.max(lo.umin.checked_add(strict)?);
+            }
+            Cc::Sgt | Cc::Sge | Cc::Slt | Cc::Sle => {
+                let strict = matches!(cc, Cc::Sgt | Cc::Slt) as i64;
+                let (lo, hi) = match cc {
+                    Cc::Slt | Cc::Sle => (&mut a, &mut b),
+                    _ => (&mut b, &mut a),
+                };
+                lo.smax = lo.smax.min(hi.smax.checked_sub(strict)?);
+                hi.smin = hi.smin.max(lo.smin.checked_add(strict)?);
+            }
+        }
+        let (a, b) = (a.sync(), b.sync());
+        (!a.is_empty() && !b.is_empty()).then_some((a, b))
+    }
+
+    /// Remove a value at either end of the bounds.
+    fn exclude(mut self, value: u64) -> Self {
+        if self.umin == value && self.umin < self.umax {
+            self.umin += 1;
+        } else if self.umax == value && self.umin < self.umax {
+            self.umax -= 1;
+        }
+        if self.smin == value as i64 && self.smin < self.smax {
+            self.smin += 1;
+        } else if self.smax == value as i64 && self.smin < self.smax {
+            self.smax -= 1;
+        }
+        self
+    }
+
+    /// Restrict the bits that a failed `jset` leaves clear.
+    fn clear(mut self, bits: u64) -> Self {
+        self.var_off = self.var_off.and(Tnum::constant(!bits));
+        self.sync()
+    }
+}
+
+impl Display for Scalar {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        if let Some(c) = self.as_const() {
+            return f.write_fmt(format_args!("{}", c as i64));
+        }
+        f.write_fmt(format_args!(
+            "umin={} umax={} smin={} smax={} var_off={}",
+            self.umin, self.umax, self.smin, self.smax, self.var_off
+        ))
+    }
+}
+
+/// The statement that gave a packet pointer a variable offset, by block number and index.
+/// Pointers moved from it by constants share it, as they share an `id` in the kernel.
+/// No pointer from an earlier run of the statement can keep it, since the first run is reached
+/// by a path without it, and joins forget the IDs that differ.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PacketId {
+    block: usize,
+    index: usize,
+}
+
+/// A packet pointer, relative to the base that the pointers with the same ID share,
+/// which is the start of the packet for those without one.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Packet {
+    id: Option<PacketId>,
+    /// Constant offset from the base.
+    off: i64,
+    /// Number of bytes from the base that a comparison against the packet end has proven to exist.
+    range: i64,
+}
+
+impl Packet {
+    /// A pointer of a given type that no comparison has been made for yet.
+    fn fresh(t: RegType, id: PacketId) -> Option<Self> {
+        match t {
+            RegType::Ptr(Region::Packet, o) => Some(Packet {
+                id: o.is_none().then_some(id),
+                off: o.unwrap_or(0),
+                range: 0,
+            }),
+            _ => None,
+        }
+    }
+
+    fn join(a: Option<Packet>, b: Option<Packet>) -> Option<Packet> {
+        let (a, b) = (a?, b?);
+        (a.id == b.id && a.off == b.off).then_some(Packet {
+            range: a.range.min(b.range),
+            ..a
+        })
+    }
+}
+
+/// What is known about the registers at a program point.
+/// For pointers, it is their offset within their region.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Regs {
+    /// The registers, or `None` if the point is unreachable.
+    pub scalars: Option<[Scalar; 11]>,
+    /// Ranges of the packet pointers, as `find_good_pkt_pointers` proves them.
+    packets: [Option<Packet>; 11],
+}
+
+impl Lattice for Regs {
+    fn join(&mut self, other: &Self) -> bool {
+        let Some(b) = &other.scalars else {
+            return false;
+        };
+        let Some(a) = &mut self.scalars else {
+            *self = other.clone();
+            return true;
+        };
+        let prev = (*a, self.packets);
+        for (a, b) in a.iter_mut().zip(b.iter()) {
+            *a = a.join(b);
+        }
+        for (a, b) in self.packets.iter_mut().zip(other.packets.iter()) {
+            *a = Packet::join(*a, *b);
+        }
+        (*a, self.packets) != prev
+    }
+}
+
+impl Regs {
+    const UNREACHABLE: Regs = Regs {
+        scalars: None,
+        packets: [None; 11],
+    };
+
+    pub fn get(&self, reg: Reg) -> Option<Scalar> {
+        self.scalars.map(|regs| regs[reg.get() as usize])
+    }
+
+    fn set(&mut self, reg: Reg, s: Scalar) {
+        if let Some(regs) = &mut self.scalars {
+            regs[reg.get() as usize] = s;
+        }
+    }
+
+    fn packet(&self, reg: Reg) -> Option<Packet> {
+        self.packets[reg.get() as usize]
+    }
+
+    fn src(&self, src: &RegImm) -> Option<Scalar> {
+        match src {
+            RegImm::Reg(r) => self.get(*r),
+            RegImm::Imm(i) => Some(Scalar::constant(*i as u64)),
+        }
+    }
+
+    /// Apply a statement, given the types of the registers before and after it.
+    fn step(&mut self, stmt: &Stmt, types: &RegTypes, types_after: &RegTypes, id: PacketId) {
+        self.step_packets(stmt, types, types_after, id);
+        // Pointers start at their known offset, if any.
+        let fresh = |reg: Reg, scalar: Scalar| match types_after.get(reg) {
+            RegType::Ptr(_, Some(o)) => Scalar::constant(o as u64),
+            RegType::PtrOrNull(_, _) | RegType::MapFd(_) | RegType::Func => Scalar::constant(0),
+            t if t.may_be_ptr() => Scalar::UNKNOWN,
+            _ => scalar,
+        };
+        let (dst, value) = match stmt {
+            Stmt::Binary(size, op, dst, src) => {
+                let (Some(a), Some(b)) = (self.get(*dst), self.src(src)) else {
+                    return;
+                };
+                let arith = matches!(op, BinAlu::Add | BinAlu::Sub | BinAlu::Mov);
+                let value = match (size, types.get(*dst), types.src(src)) {
+                    (WordSize::B64, _, _) if *op == BinAlu::Mov => b,
+                    (WordSize::B64, RegType::Scalar, RegType::Scalar) => Scalar::binary(*op, a, b),
+                    // Adding a pointer to a scalar moves it by the scalar.
+                    (WordSize::B64, RegType::Scalar, t) if *op == BinAlu::Add && t.is_ptr() => {
+                        Scalar::binary(*op, a, b)
+                    }
+                    (WordSize::B64, t, RegType::Scalar) if arith && t.is_ptr() => {
+                        Scalar::binary(*op, a, b)
+                    }
+                    (WordSize::B64, _, _) => Scalar::UNKNOWN,
+                    (_, RegType::Scalar, RegType::Scalar) => Scalar::binary32(*op, a, b),
+                    (_, _, _) if *op == BinAlu::Mov && matches!(src, RegImm::Imm(_)) => {
+                        Scalar::binary32(*op, a, b)
+                    }
+                    (_, _, _) => Scalar::word(WordSize::B32),
+                };
+                // Arithmetic keeps track of offsets that the types don't know.
+                let value = match types_after.get(*dst) {
+                    RegType::Ptr(_, Some(o)) => Scalar::constant(o as u64),
+                    _ => value,
+                };
+                (*dst, value)
+            }
+            Stmt::Unary(WordSize::B64, UnAlu::Neg, dst) => {
+                let value = match self.get(*dst).and_then(|a| a.as_const()) {
+                    Some(a) => Scalar::constant(a.wrapping_neg()),
+                    None => Scalar::UNKNOWN,
+                };
+                (*dst, value)
+            }
+            Stmt::Unary(size, UnAlu::Neg, dst) => {
+                let value = match self.get(*dst).and_then(|a| a.as_const()) {
+                    Some(a) => Scalar::constant((a as u32).wrapping_neg() as u64),
+                    None => Scalar::word(*size),
+                };
+                (*dst, value)
+            }
+            Stmt::Unary(size, _, dst) => (*dst, Scalar::word(*size)),
+            Stmt::Load(size, dst, _) => (*dst, fresh(*dst, Scalar::word(*size))),
+            Stmt::LoadImm(dst, i) => (*dst, Scalar::constant(*i as u64)),
+            Stmt::LoadMapFd(dst, _) | Stmt::LoadFunc(dst, _) => (*dst, Scalar::constant(0)),
+            Stmt::Call(_) | Stmt::CallLocal(_) => {
+                for r in 1..=5 {
+                    self.set(Reg::new(r).unwrap(), Scalar::UNKNOWN);
+                }
+                (Reg::R0, fresh(Reg::R0, Scalar::UNKNOWN))
+            }
+            Stmt::Store(_, _, _) | Stmt::Assert(_) => return,
+        };
+        self.set(dst, value);
+    }
+
+    /// Follow packet pointers through a statement, before the offsets are updated.
+    /// Moving one by a constant keeps its ID, and moving it by anything else gives it a new one.
+    fn step_packets(
+        &mut self,
+        stmt: &Stmt,
+        types: &RegTypes,
+        types_after: &RegTypes,
+        id: PacketId,
+    ) {
+        let constant = |src: &RegImm| match types.src(src) {
+            RegType::Scalar => self.src(src).and_then(|s| s.as_const()),
+            _ => None,
+        };
+        let defined = match stmt {
+            Stmt::Binary(WordSize::B64, BinAlu::Mov, dst, RegImm::Reg(src)) => {
+                Some((*dst, self.packet(*src)))
+            }
+            Stmt::Binary(WordSize::B64, op @ (BinAlu::Add | BinAlu::Sub), dst, src) => {
+                let sign = if *op == BinAlu::Add { 1 } else { -1 };
+                let moved = match (self.packet(*dst), constant(src), src) {
+                    (Some(p), Some(c), _) => Some((p, sign * c as i64)),
+                    (None, _, RegImm::Reg(src)) if *op == BinAlu::Add => self
+                        .packet(*src)
+                        .zip(constant(&RegImm::Reg(*dst)))
+                        .map(|(p, c)| (p, c as i64)),
+                    _ => None,
+                };
+                let moved = moved.map(|(p, c)| Packet {
+                    off: p.off.wrapping_add(c),
+                    ..p
+                });
+                Some((*dst, moved.or(Packet::fresh(types_after.get(*dst), id))))
+            }
+            Stmt::Binary(_, _, dst, _) | Stmt::Load(_, dst, _) => {
+                Some((*dst, Packet::fresh(types_after.get(*dst), id)))
+            }
+            _ => None,
+        };
+        if let Some((dst, p)) = defined {
+            self.packets[dst.get() as usize] = p;
+        }
+        for (reg, p) in (0..11).filter_map(Reg::new).zip(self.packets.iter_mut()) {
+            if !matches!(types_after.get(reg), RegType::Ptr(Region::Packet, _)) {
+                *p = None;
+            }
+        }
+    }
+
+    /// Extend the range of the pointers that share the ID of a packet pointer
+    /// that is at most the packet end, as `find_good_pkt_pointers` does.
+    fn check_packet(&mut self, reg: Reg) {
+        let Some(p) = self.packet(reg).filter(|p| p.off >= 0) else {
+            return;
+        };
+        for q in self.packets.iter_mut().flatten() {
+            if q.id == p.id {
+                q.range = q.range.max(p.off);
+            }
+        }
+    }
+
+    /// Restrict the registers to the values for which a comparison holds.
+    fn assume(&mut self, cc: Cc, lhs: Reg, rhs: &RegImm) {
+        let (Some(a), Some(b)) = (self.get(lhs), self.src(rhs)) else {
+            return;
+        };
+        match Scalar::assume(cc, a, b) {
+            Some((a, b)) => {
+                self.set(lhs, a);
+                if let RegImm::Reg(rhs) = rhs {
+                    self.set(*rhs, b);
+                }
+            }
+            None => *self = Regs::UNREACHABLE,
+        }
+    }
+}
+
+/// Known bits and bounds of the registers, following the register types.
+/// Widening jumps to constants of the program, so that loops bounded by them stay bounded.
+pub struct BoundsAnalysis {
+    /// Types before each statement of the reachable blocks, followed by the types at their end.
+    types: HashMap<Label, Vec<RegTypes>>,
+    thresholds: BTreeSet<i128>,
+    /// Numbers of the blocks, which make up the IDs of packet pointers.
+    blocks: HashMap<Label, usize>,
+}
+
+impl BoundsAnalysis {
+    pub fn new(cfg: &Cfg, types: &TypeInfo) -> Self {
+        let types: HashMap<Label, Vec<RegTypes>> = types
+            .reachable()
+            .map(|l| (l.clone(), types.stmt_types(l, &cfg.blocks[l])))
+            .collect();
+        let mut labels: Vec<&Label> = types.keys().collect();
+        labels.sort();
+        let blocks = labels
+            .into_iter()
+            .enumerate()
+            .map(|(i, l)| (l.clone(), i))
+            .collect();
+        let thresholds = constants(cfg)
+            .into_iter()
+            .flat_map(|c| [c as i128, c as u64 as i128])
+            .flat_map(|c| [c - 1, c, c + 1])
+            .collect();
+        BoundsAnalysis {
+            types,
+            thresholds,
+            blocks,
+        }
+    }
+
+    fn id(&self, label: &Label, index: usize) -> PacketId {
+        PacketId {
+            block: self.blocks[label],
+            index,
+        }
+    }
+
+    /// The register types before a statement.
+    pub fn types(&self, label: &Label, index: usize) -> Option<&RegTypes> {
+        self.types.get(label).map(|t| &t[index])
+    }
+
+    /// The nearest threshold that is at least `value` or at most it, within `[min, max]`.
+    fn threshold(&self, value: i128, up: bool, min: i128, max: i128) -> i128 {
+        match up {
+            true => self
+                .thresholds
+                .range(value..=max)
+                .next()
+                .copied()
+                .unwrap_or(max),
+            false => self
+                .thresholds
+                .range(min..=value)
+                .next_back()
+                .copied()
+                .unwrap_or(min),
+        }
+    }
+}
+
+impl Analysis for BoundsAnalysis {
+    type Fact = Regs;
+    const DIRECTION: Direction = Direction::Forward;
+
+    fn bottom(&self, _cfg: &Cfg) -> Self::Fact {
+        Regs::UNREACHABLE
+    }
+
+    fn boundary(&self, cfg: &Cfg) -> Self::Fact {
+        let mut regs = Regs {
+            scalars: Some([Scalar::UNKNOWN; 11]),
+            ..Regs::UNREACHABLE
+        };
+        if let Some(types) = self.types(&cfg.start, 0) {
+            for reg in (0..11).filter_map(Reg::new) {
+                if let t @ RegType::Ptr(_, Some(o)) = types.get(reg) {
+                    regs.set(reg, Scalar::constant(o as u64));
+                    regs.packets[reg.get() as usize] = Packet::fresh(t, self.id(&cfg.start, 0));
+                }
+            }
+        }
+        regs
+    }
+
+    fn transfer(&self, label: &Label, index: usize, stmt: &Stmt, fact: &mut Self::Fact) {
+        if let Some(types) = self.types.get(label) {
+            fact.step(
+                stmt,
+                &types[index],
+                &types[index + 1],
+                self.id(label, index),
+            );
+        }
+    }
+
+    fn edge(&self, label: &Label, next: &Continuation, target: &Label, fact: &mut Self::Fact) {
+        let Continuation::Jcc(cc, lhs, rhs, target_t, target_f) = next else {
+            return;
+        };
+        if target_t == target_f {
+            return;
+        }
+        let types = self.types.get(label).and_then(|t| t.last());
+        // A packet pointer that is at most the packet end proves the bytes before it exist.
+        if let (Some(t), RegImm::Reg(rhs), Cc::Gt | Cc::Ge | Cc::Lt | Cc::Le) = (types, rhs, cc) {
+            let taken = target == target_t;
+            match (t.get(*lhs), t.get(*rhs)) {
+                (RegType::Ptr(Region::Packet, _), RegType::Ptr(Region::PacketEnd, _))
+                    if matches!(cc, Cc::Lt | Cc::Le) == taken =>
+                {
+                    fact.check_packet(*lhs)
+                }
+                (RegType::Ptr(Region::PacketEnd, _), RegType::Ptr(Region::Packet, _))
+                    if matches!(cc, Cc::Gt | Cc::Ge) == taken =>
+                {
+                    fact.check_packet(*rhs)
+                }
+                _ => (),
+            }
+        }
+        // Comparisons of pointers say nothing about their offsets.
+        if types.is_none_or(|t| t.get(*lhs) != RegType::Scalar || t.src(rhs) != RegType::Scalar) {
+            return;
+        }
+        match (target == target_t, negated(*cc)) {
+            (true, _) => fact.assume(*cc, *lhs, rhs),
+            (false, Some(cc)) => fact.assume(cc, *lhs, rhs),
+            // A failed `jset` with a constant leaves its bits clear.
+            (false, None) => {
+                if let (Some(a), RegImm::Imm(i)) = (fact.get(*lhs), rhs) {
+                    fact.set(*lhs, a.clear(*i as u64));
+                }
+            }
+        }
+    }
+
+    fn widen(&self, fact: &mut Self::Fact, other: &Self::Fact) -> bool {
+        let (Some(a), Some(b)) = (&mut fact.scalars, &other.scalars) else {
+            return fact.join(other);
+        };
+        let prev = (*a, fact.packets);
+        // Ranges only shrink, and IDs are only forgotten, so packet pointers need no widening.
+        for (a, b) in fact.packets.iter_mut().zip(other.packets.iter()) {
+            *a = Packet::join(*a, *b);
+        }
+        for (a, b) in a.iter_mut().zip(b.iter()) {
+            let joined = a.join(b);
+            let (u64_max, i64_min, i64_max) =
+                (u64::MAX as i128, i64::MIN as i128, i64::MAX as i128);
+            if joined.umin < a.umin {
+                a.umin = self.threshold(joined.umin as i128, false, 0, u64_max) as u64;
+            }
+            if joined.umax > a.umax {
+                a.umax = self.threshold(joined.umax as i128, true, 0, u64_max) as u64;
+            }
+            if joined.smin < a.smin {
+                a.smin = self.threshold(joined.smin as i128, false, i64_min, i64_max) as i64;
+            }
+            if joined.smax > a.smax {
+                a.smax = self.threshold(joined.smax as i128, true, i64_min, i64_max) as i64;
+            }
+            // The 32-bit bounds and known bits have finite height.
+            a.var_off = joined.var_off;
+            (a.u32_min, a.u32_max) = (joined.u32_min, joined.u32_max);
+            (a.s32_min, a.s32_max) = (joined.s32_min, joined.s32_max);
+        }
+        (*a, fact.packets) != prev
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum BoundsErrKind {
+    /// An access through a stack pointer with a variable offset that might leave the frame.
+    Stack { reg: Reg, min: i64, max: i64 },
+    /// An access through a stack pointer with a constant offset above the frame.
+    StackFrame { off: i64, size: i64 },
+    /// An access through a map value pointer whose offset might be negative.
+    NegativeMin(Reg),
+    /// An access through a map value pointer whose offset isn't bounded.
+    Unbounded(Reg),
+    /// An access that might reach past the end of a map value.
+    MapValue {
+        value_size: i64,
+        off: i64,
+        size: i64,
+    },
+    /// An access through a packet pointer beyond the bytes that are checked against the packet end.
+    Packet {
+        reg: Reg,
+        off: i64,
+        size: i64,
+        range: i64,
+    },
+    /// A shift by a constant that is at least the width of the operation.
+    Shift(Imm),
+    /// A division or modulo by a constant zero.
+    DivByZero,
+}
+
+/// An operation that the kernel verifier rejects given the bounds it tracks.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BoundsErr {
+    pub label: Label,
+    pub site: Site,
+    pub kind: BoundsErrKind,
+}
+
+impl Display for BoundsErr {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        let BoundsErr { label, site, kind } = self;
+        match kind {
+            BoundsErrKind::Stack { reg, min, max } => f.write_fmt(format_args!(
+                "Variable stack access through r{} at offsets {min} to {max} leaves the frame",
+                reg.get()
+            ))?,
+            BoundsErrKind::StackFrame { off, size } => f.write_fmt(format_args!(
+                "Access of {size} bytes at offset {off} of the stack leaves the frame"
+            ))?,
+            BoundsErrKind::NegativeMin(reg) => f.write_fmt(format_args!(
+                "Minimum offset of r{} might be negative, so check it against 0 or use an unsigned one",
+                reg.get()
+            ))?,
+            BoundsErrKind::Unbounded(reg) => f.write_fmt(format_args!(
+                "Unbounded access through r{}, so check its offset before it",
+                reg.get()
+            ))?,
+            BoundsErrKind::MapValue {
+                value_size,
+                off,
+                size,
+            } => f.write_fmt(format_args!(
+                "Access of {size} bytes at offset {off} exceeds map value size {value_size}"
+            ))?,
+            BoundsErrKind::Packet {
+                reg,
+                off,
+                size,
+                range,
+            } => f.write_fmt(format_args!(
+                "Access of {size} bytes at offset {off} through r{} exceeds the {range} bytes checked against the packet end",
+                reg.get()
+            ))?,
+            BoundsErrKind::Shift(amount) => {
+                f.write_fmt(format_args!("Invalid shift by {amount}"))?
+            }
+            BoundsErrKind::DivByZero => f.write_str("Division by zero")?,
+        }
+        f.write_fmt(format_args!(" by {}", site.describe(label)))
+    }
+}
+
+/// Whether an access is known to stay within its region, or the error if the kernel rejects it.
+fn check_access(
+    cfg: &Cfg,
+    size: WordSize,
+    MemRef(reg, offset): &MemRef,
+    types: &RegTypes,
+    regs: &Regs,
+) -> Result<bool, BoundsErrKind> {
+    let Some(s) = regs.get(*reg) else {
+        return Ok(false);
+    };
+    let bytes = size.bytes();
+    match types.get(*reg) {
+        RegType::Ptr(Region::Stack, _) => {
+            let (min, max) = (
+                s.smin.saturating_add(*offset),
+                s.smax.saturating_add(*offset),
+            );
+            let above = max.saturating_add(bytes) > 0;
+            match (min >= -STACK_SIZE && !above, s.as_const()) {
+                (true, _) => Ok(true),
+                (false, Some(_)) if above => Err(BoundsErrKind::StackFrame {
+                    off: min,
+                    size: bytes,
+                }),
+                // Constant offsets below the frame are reported with the stack depth.
+                (false, Some(_)) => Ok(false),
+                (false, None) => Err(BoundsErrKind::Stack {
+                    reg: *reg,
+                    min,
+                    max,
+                }),
+            }
+        }
+        RegType::Ptr(Region::Packet, _) => {
+            let Some(p) = regs.packet(*reg) else {
+                return Err(BoundsErrKind::Packet {
+                    reg: *reg,
+                    off: *offset,
+                    size: bytes,
+                    range: 0,
+                });
+            };
+            // The offset of the pointer is the constant one from its base plus a variable one.
+            if s.smin < p.off {
+                return Err(BoundsErrKind::NegativeMin(*reg));
+            }
+            if s.umax >= MAX_VAR_OFF as u64 {
+                return Err(BoundsErrKind::Unbounded(*reg));
+            }
+            let off = p.off + offset;
+            if off < 0 || off + bytes > p.range {
+                return Err(BoundsErrKind::Packet {
+                    reg: *reg,
+                    off,
+                    size: bytes,
+                    range: p.range,
+                });
+            }
+            Ok(true)
+        }
+        RegType::Ptr(Region::MapValue(fd), _) => {
+            let Some(map) = cfg.map(fd) else {
+                return Ok(false);
+            };
+            if s.smin < 0 {
+                return Err(BoundsErrKind::NegativeMin(*reg));
+            }
+            if s.umax >= MAX_VAR_OFF as u64 {
+                return Err(BoundsErrKind::Unbounded(*reg));
+            }
+            let off = s.umax as i64 + offset;
+            if s.smin + offset < 0 || off + bytes > map.value_size {
+                return Err(BoundsErrKind::MapValue {
+                    value_size: map.value_size,
+                    off,
+                    size: bytes,
+                });
+            }
+            Ok(true)
+        }
+        _ => Ok(false),
+    }
+}
+
+/// Accesses and operations of a CFG that the kernel verifier rejects,
+/// along with the accesses that are known to stay within their region.
+fn check_all(cfg: &Cfg, types: &TypeInfo) -> (Vec<BoundsErr>, HashSet<(Label, usize)>) {
+    let analysis = BoundsAnalysis::new(cfg, types);
+    let results = solve(cfg, &analysis);
+    let mut errors = Vec::new();
+    let mut safe = HashSet::new();
+    let mut labels: Vec<&Label> = results.labels().collect();
+    labels.sort();
+    for label in labels {
+        let facts = results.stmt_facts(label);
+        for (index, stmt) in cfg.blocks[label].body.iter().enumerate() {
+            let Some(types) = analysis.types(label, index) else {
+                continue;
+            };
+            let checked = match stmt {
+                Stmt::Load(size, _, mem_ref) | Stmt::Store(size, mem_ref, _) => {
+                    check_access(cfg, *size, mem_ref, types, &facts[index])
+                }
+                Stmt::Binary(size, BinAlu::Lsh | BinAlu::Rsh | BinAlu::Arsh, _, RegImm::Imm(i))
+                    if *i < 0 || *i >= 8 * size.bytes() =>
+                {
+                    Err(BoundsErrKind::Shift(*i))
+                }
+                Stmt::Binary(_, BinAlu::Div | BinAlu::Mod, _, RegImm::Imm(0)) => {
+                    Err(BoundsErrKind::DivByZero)
+                }
+                _ => Ok(false),
+            };
+            match checked {
+                Ok(true) => {
+                    safe.insert((label.clone(), index));
+                }
+                Ok(false) => (),
+                Err(kind) => errors.push(BoundsErr {
+                    label: label.clone(),
+                    site: Site::Stmt(index, stmt.clone()),
+                    kind,
+                }),
+            }
+        }
+    }
+    (errors, safe)
+}
+
+/// Find the accesses and operations that the kernel verifier rejects given the bounds it tracks.
+pub fn bounds_errors(cfg: &Cfg, types: &TypeInfo) -> Vec<BoundsErr> {
+    check_all(cfg, types).0
+}
+
+/// Facts found by tracking bounds, for the verification conditions to use.
+/// They are assumed rather than proven, so bounds that are too tight make the conditions unsound.
+#[derive(Debug, Clone, Default)]
+pub struct Facts {
+    /// Bounds of the scalar registers at the start of each block.
+    pub assumptions: HashMap<Label, Formula>,
+    /// Accesses that are known to stay within their region, by block and index.
+    /// Divisions may be included too if their divisor is known not to be zero.
+    pub safe: HashSet<(Label, usize)>,
+}
+
+/// Find the bounds of the scalar registers and the accesses that are known to be safe.
+pub fn facts(cfg: &Cfg, types: &TypeInfo, f: &FormulaBuilder) -> Facts {
+    let analysis = BoundsAnalysis::new(cfg, types);
+    let results = solve(cfg, &analysis);
+    let mut assumptions = HashMap::new();
+    for label in results.labels() {
+        let (Some(regs), Some(types)) = (results.entry(label), analysis.types(label, 0)) else {
+            continue;
+        };
+        let max = i64::MAX as u64;
+        let mut bounds = Vec::new();
+        for reg in (0..10).filter_map(Reg::new) {
+            let Some(s) = regs.get(reg).filter(|_| types.get(reg) == RegType::Scalar) else {
+                continue;
+            };
+            let r = f.reg(reg).0;
+            match s.as_const() {
+                Some(c) if c <= max => bounds.push(f.eq(r, f.val(c as Imm))),
+                Some(_) => (),
+                None => {
+                    if s.umin > 0 && s.umin <= max {
+                        bounds.push(f.rel(Cc::Ge, r.clone(), f.val(s.umin as Imm)));
+                    }
+                    if s.umax < max {
+                        bounds.push(f.rel(Cc::Le, r, f.val(s.umax as Imm)));
+                    }
+                }
+            }
+        }
+        if let Some(bounds) = bounds.into_iter().reduce(|a, b| f.and(a, b)) {
+            assumptions.insert(label.clone(), bounds);
+        }
+    }
+    Facts {
+        assumptions,
+        safe: check_all(cfg, types).1,
+    }
+}
+
+#[cfg(test)]
+mod tests;
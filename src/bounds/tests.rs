@@ -0,0 +1,307 @@
+use super::*;
+use crate::prog::ProgType;
+
+/// Sets of values to build scalars from, near the ends of the signed and unsigned ranges.
+const SETS: [&[u64]; 10] = [
+    &[0],
+    &[1],
+    &[0, 1],
+    &[1, 3],
+    &[2, 7, 12],
+    &[u64::MAX],
+    &[-4i64 as u64, 2],
+    &[0x7fff_ffff, 0x8000_0000],
+    &[i64::MIN as u64, 5],
+    &[u32::MAX as u64 + 1, 63, 64],
+];
+
+const OPS: [BinAlu; 10] = [
+    BinAlu::Mov,
+    BinAlu::Add,
+    BinAlu::Sub,
+    BinAlu::Mul,
+    BinAlu::And,
+    BinAlu::Or,
+    BinAlu::Xor,
+    BinAlu::Lsh,
+    BinAlu::Rsh,
+    BinAlu::Arsh,
+];
+
+/// The smallest scalar containing the values.
+fn scalar(values: &[u64]) -> Scalar {
+    let constants = values.iter().map(|v| Scalar::constant(*v));
+    constants.reduce(|a, b| a.join(&b)).unwrap()
+}
+
+fn contains(s: &Scalar, v: u64) -> bool {
+    s.var_off.contains(&Tnum::constant(v))
+        && (s.umin..=s.umax).contains(&v)
+        && (s.smin..=s.smax).contains(&(v as i64))
+        && (s.u32_min..=s.u32_max).contains(&(v as u32))
+        && (s.s32_min..=s.s32_max).contains(&(v as i32))
+}
+
+fn eval(op: BinAlu, x: u64, y: u64) -> u64 {
+    match op {
+        BinAlu::Mov => y,
+        BinAlu::Add => x.wrapping_add(y),
+        BinAlu::Sub => x.wrapping_sub(y),
+        BinAlu::Mul => x.wrapping_mul(y),
+        BinAlu::And => x & y,
+        BinAlu::Or => x | y,
+        BinAlu::Xor => x ^ y,
+        BinAlu::Lsh => x.wrapping_shl(y as u32),
+        BinAlu::Rsh => x.wrapping_shr(y as u32),
+        BinAlu::Arsh => (x as i64).wrapping_shr(y as u32) as u64,
+        BinAlu::Div | BinAlu::Mod => unreachable!(),
+    }
+}
+
+fn eval32(op: BinAlu, x: u64, y: u64) -> u64 {
+    let (x, y) = (x as u32, y as u32);
+    let z = match op {
+        BinAlu::Mov => y,
+        BinAlu::Add => x.wrapping_add(y),
+        BinAlu::Sub => x.wrapping_sub(y),
+        BinAlu::Mul => x.wrapping_mul(y),
+        BinAlu::And => x & y,
+        BinAlu::Or => x | y,
+        BinAlu::Xor => x ^ y,
+        BinAlu::Lsh => x.wrapping_shl(y),
+        BinAlu::Rsh => x.wrapping_shr(y),
+        BinAlu::Arsh => (x as i32).wrapping_shr(y) as u32,
+        BinAlu::Div | BinAlu::Mod => unreachable!(),
+    };
+    z as u64
+}
+
+fn holds(cc: Cc, x: u64, y: u64) -> bool {
+    let (sx, sy) = (x as i64, y as i64);
+    match cc {
+        Cc::Eq => x == y,
+        Cc::Ne => x != y,
+        Cc::Set => x & y != 0,
+        Cc::Gt => x > y,
+        Cc::Ge => x >= y,
+        Cc::Lt => x < y,
+        Cc::Le => x <= y,
+        Cc::Sgt => sx > sy,
+        Cc::Sge => sx >= sy,
+        Cc::Slt => sx < sy,
+        Cc::Sle => sx <= sy,
+    }
+}
+
+#[test]
+fn binary() {
+    for op in OPS {
+        for xs in SETS {
+            for ys in SETS {
+                let (r, r32) = (
+                    Scalar::binary(op, scalar(xs), scalar(ys)),
+                    Scalar::binary32(op, scalar(xs), scalar(ys)),
+                );
+                for &x in xs {
+                    for &y in ys {
+                        let (z, z32) = (eval(op, x, y), eval32(op, x, y));
+                        assert!(contains(&r, z), "{x:#x} {op:?} {y:#x} = {z:#x} not in {r}");
+                        assert!(
+                            contains(&r32, z32),
+                            "{x:#x} {op:?}32 {y:#x} = {z32:#x} not in {r32}"
+                        );
+                    }
+                }
+            }
+        }
+    }
+}
+
+#[test]
+fn constant_folding() {
+    let c = Scalar::constant;
+    assert_eq!(Scalar::binary(BinAlu::Add, c(3), c(4)), c(7));
+    assert_eq!(Scalar::binary(BinAlu::Sub, c(3), c(4)), c(u64::MAX));
+    assert_eq!(Scalar::binary(BinAlu::Mul, c(6), c(7)), c(42));
+    assert_eq!(Scalar::binary(BinAlu::Lsh, c(1), c(4)), c(16));
+    assert_eq!(
+        Scalar::binary(BinAlu::Arsh, c(-8i64 as u64), c(1)),
+        c(-4i64 as u64)
+    );
+    assert_eq!(
+        Scalar::binary32(BinAlu::Sub, c(0), c(1)),
+        c(u32::MAX as u64)
+    );
+    assert_eq!(
+        Scalar::binary32(BinAlu::Arsh, c(0x8000_0000), c(31)),
+        c(u32::MAX as u64)
+    );
+}
+
+#[test]
+fn variable_shifts() {
+    // Shifting by either 0 or 1 can't be bounded by the extremes of the amount.
+    let amount = scalar(&[0, 1]);
+    for op in [BinAlu::Lsh, BinAlu::Rsh, BinAlu::Arsh] {
+        assert_eq!(
+            Scalar::binary(op, Scalar::constant(4), amount),
+            Scalar::UNKNOWN
+        );
+    }
+    assert_eq!(
+        Scalar::binary(BinAlu::Rsh, Scalar::constant(4), Scalar::constant(64)),
+        Scalar::UNKNOWN
+    );
+    let r = Scalar::binary32(BinAlu::Rsh, Scalar::constant(4), Scalar::constant(32));
+    assert_eq!((r.umin, r.umax), (0, u32::MAX as u64));
+}
+
+#[test]
+fn assume() {
+    use Cc::*;
+    for cc in [Eq, Ne, Set, Gt, Ge, Lt, Le, Sgt, Sge, Slt, Sle] {
+        for xs in SETS {
+            for ys in SETS {
+                let assumed = Scalar::assume(cc, scalar(xs), scalar(ys));
+                for &x in xs {
+                    for &y in ys {
+                        if !holds(cc, x, y) {
+                            continue;
+                        }
+                        let Some((a, b)) = assumed else {
+                            panic!("{x:#x} {cc:?} {y:#x} holds but was ruled out");
+                        };
+                        assert!(contains(&a, x), "{x:#x} {cc:?} {y:#x}: {x:#x} not in {a}");
+                        assert!(contains(&b, y), "{x:#x} {cc:?} {y:#x}: {y:#x} not in {b}");
+                    }
+                }
+            }
+        }
+    }
+}
+
+#[test]
+fn assume_refines() {
+    let any = Scalar::UNKNOWN;
+    let (a, _) = Scalar::assume(Cc::Lt, any, Scalar::constant(10)).unwrap();
+    assert_eq!((a.umin, a.umax), (0, 9));
+    assert_eq!(a.var_off, Tnum::range(0, 9));
+    let (a, _) = Scalar::assume(Cc::Sge, any, Scalar::constant(0)).unwrap();
+    assert_eq!((a.smin, a.umax), (0, i64::MAX as u64));
+    assert_eq!(
+        Scalar::assume(Cc::Gt, Scalar::constant(3), Scalar::constant(3)),
+        None
+    );
+    assert_eq!(
+        Scalar::assume(Cc::Eq, scalar(&[0, 1]), Scalar::constant(4)),
+        None
+    );
+}
+
+/// The kinds of errors the kernel is predicted to report for a program.
+fn errors(src: &str, prog: ProgType) -> Vec<BoundsErrKind> {
+    let cfg = Cfg::parse(src);
+    let init = HashSet::from([Reg::R1, Reg::R10]);
+    let types = TypeInfo::infer(&cfg, RegTypes::entry(&cfg, &init, prog), prog);
+    bounds_errors(&cfg, &types)
+        .into_iter()
+        .map(|e| e.kind)
+        .collect()
+}
+
+#[test]
+fn stack_frame() {
+    let above = "mov r1 0\nstxdw [r10 + 8] r1\nexit\n";
+    assert_eq!(
+        errors(above, ProgType::Function),
+        vec![BoundsErrKind::StackFrame { off: 8, size: 8 }]
+    );
+    let straddling = "mov r1 0\nstxdw [r10 - 4] r1\nexit\n";
+    assert_eq!(
+        errors(straddling, ProgType::Function),
+        vec![BoundsErrKind::StackFrame { off: -4, size: 8 }]
+    );
+    let within = "mov r1 0\nstxdw [r10 - 8] r1\nexit\n";
+    assert!(errors(within, ProgType::Function).is_empty());
+}
+
+#[test]
+fn unchecked_packet() {
+    let src = "ldxw r2 [r1]\nldxb r0 [r2]\nmov r0 2\nexit\n";
+    assert_eq!(
+        errors(src, ProgType::Xdp),
+        vec![BoundsErrKind::Packet {
+            reg: Reg::R2,
+            off: 0,
+            size: 1,
+            range: 0
+        }]
+    );
+}
+
+#[test]
+fn checked_packet() {
+    // Copies moved by constants share the range that a check of any of them proves.
+    let src = "\
+a:
+    ldxw r2 [r1]
+    ldxw r3 [r1 + 4]
+    mov r4 r2
+    add r4 14
+    jgt r4 r3 out
+b:
+    ldxh r0 [r2 + 12]
+    ldxb r0 [r4 - 1]
+    ldxb r0 [r2 + 14]
+out:
+    mov r0 2
+    exit
+";
+    assert_eq!(
+        errors(src, ProgType::Xdp),
+        vec![BoundsErrKind::Packet {
+            reg: Reg::R2,
+            off: 14,
+            size: 1,
+            range: 14
+        }]
+    );
+    // The end on the left proves it on the taken branch instead.
+    let flipped = src.replace("jgt r4 r3 out", "jge r3 r4 b\n    ja out");
+    assert_eq!(errors(&flipped, ProgType::Xdp).len(), 1);
+}
+
+#[test]
+fn variable_packet() {
+    // Moving a pointer by a variable amount needs a check of its own.
+    let src = "\
+a:
+    ldxw r2 [r1]
+    ldxw r3 [r1 + 4]
+    mov r4 r2
+    add r4 14
+    jgt r4 r3 out
+b:
+    ldxb r5 [r2]
+    and r5 7
+    add r2 r5
+    ldxb r0 [r2]
+    mov r4 r2
+    add r4 2
+    jgt r4 r3 out
+c:
+    ldxh r0 [r2]
+out:
+    mov r0 2
+    exit
+";
+    assert_eq!(
+        errors(src, ProgType::Xdp),
+        vec![BoundsErrKind::Packet {
+            reg: Reg::R2,
+            off: 0,
+            size: 1,
+            range: 0
+        }]
+    );
+}
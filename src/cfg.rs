@@ -8,13 +8,18 @@ use std::{
 };
 
 pub use crate::ast::{
-    BinAlu, Cc, Cont, Expr, Formula, Ident, Imm, Label, MemRef, Offset, Reg, RegImm, Stmt, UnAlu,
-    WordSize,
+    BinAlu, Cc, Cont, Expr, Formula, Ident, Imm, Label, MapDef, MemRef, Offset, Reg, RegImm, Stmt,
+    UnAlu, WordSize,
 };
 
+pub use graph::{DomTree, Loop, LoopForest};
+
+mod graph;
+
 use crate::{
     ast::{Line, Logic, Module},
     formula::FormulaBuilder,
+    helpers::helper_by_id,
 };
 
 #[derive(Debug, Clone, PartialEq, Eq)]
@@ -24,17 +29,96 @@ pub enum Continuation {
     Jcc(Cc, Reg, RegImm, Label, Label),
 }
 
+impl Continuation {
+    /// Labels of the blocks that control may be passed to.
+    pub fn targets(&self) -> Vec<&Label> {
+        match self {
+            Continuation::Exit => vec![],
+            Continuation::Jmp(target) => vec![target],
+            Continuation::Jcc(_, _, _, target_t, target_f) => vec![target_t, target_f],
+        }
+    }
+}
+
+/// A place within a block: either a statement of its body or its continuation.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Site {
+    Stmt(usize, Stmt),
+    Cont(Continuation),
+}
+
+impl Site {
+    /// Describe the site as part of the block with the given label.
+    pub fn describe(&self, label: &Label) -> String {
+        match self {
+            Site::Stmt(index, stmt) => format!("{stmt:?} ({label}:{index})"),
+            Site::Cont(cont) => format!("{cont:?} (end of {label})"),
+        }
+    }
+}
+
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct Block {
     pub require: Option<Formula>,
+    pub variant: Option<Expr>,
+    pub bound: Option<Imm>,
     pub body: Vec<Stmt>,
     pub next: Continuation,
 }
 
+impl Block {
+    /// The subprogram that the statement at `index` passes as a callback in `r2`.
+    /// It must be loaded within the same block, or creating the CFG fails.
+    pub fn callback(&self, index: usize) -> Option<&Label> {
+        for stmt in self.body[..index].iter().rev() {
+            match stmt {
+                Stmt::LoadFunc(Reg::R2, name) => return Some(name),
+                Stmt::Call(_) | Stmt::CallLocal(_) => return None,
+                stmt if writes_reg(stmt) == Some(Reg::R2) => return None,
+                _ => (),
+            }
+        }
+        None
+    }
+
+    /// The constant in `reg` before the statement at `index`,
+    /// if it is loaded within the same block.
+    pub fn constant(&self, reg: Reg, index: usize) -> Option<Imm> {
+        for stmt in self.body[..index].iter().rev() {
+            match stmt {
+                Stmt::Binary(WordSize::B64, BinAlu::Mov, dst, RegImm::Imm(i))
+                | Stmt::LoadImm(dst, i)
+                    if *dst == reg =>
+                {
+                    return Some(*i)
+                }
+                Stmt::Call(_) | Stmt::CallLocal(_) if reg.get() <= 5 => return None,
+                stmt if writes_reg(stmt) == Some(reg) => return None,
+                _ => (),
+            }
+        }
+        None
+    }
+}
+
+/// Contract of a subprogram, which its call sites rely on.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Contract {
+    pub requires: Formula,
+    pub ensures: Formula,
+}
+
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct Cfg {
+    /// Name of the subprogram, or `None` for the main program.
+    pub name: Option<Label>,
+    pub maps: Vec<MapDef>,
+    /// Contracts of all subprograms of the module.
+    pub contracts: HashMap<Label, Contract>,
     pub requires: Formula,
     pub ensures: Formula,
+    /// Precondition of the programs that tail calls transfer control to.
+    pub tail_requires: Formula,
     pub start: Label,
     pub blocks: HashMap<Label, Block>,
 }
@@ -46,6 +130,13 @@ pub enum ConvertErr {
     Unsupported(Stmt),
     MisplacedRequire,
     DuplicateLabel(String),
+    FramePointerWrite(Stmt),
+    UnknownFunction(Label),
+    MissingCallback(Stmt),
+    MisplacedVariant,
+    DuplicateVariant,
+    MisplacedBound,
+    DuplicateBound,
 }
 
 impl Display for ConvertErr {
@@ -67,6 +158,29 @@ impl Display for ConvertErr {
             ConvertErr::DuplicateLabel(label) => {
                 f.write_fmt(format_args!("Duplicate label \"{label}\""))
             }
+            ConvertErr::FramePointerWrite(instr) => {
+                f.write_fmt(format_args!("Frame pointer r10 is read-only: {instr:?}"))
+            }
+            ConvertErr::UnknownFunction(name) => {
+                f.write_fmt(format_args!("Called function \"{name}\" doesn't exist"))
+            }
+            ConvertErr::MissingCallback(instr) => {
+                let helper = match instr {
+                    Stmt::Call(id) => helper_by_id(*id).name,
+                    _ => "helper",
+                };
+                f.write_fmt(format_args!(
+                    "Callback of {helper} must be loaded into r2 in the same block: {instr:?}"
+                ))
+            }
+            ConvertErr::MisplacedVariant => {
+                f.write_str("Variants can only be placed at the start of blocks")
+            }
+            ConvertErr::DuplicateVariant => f.write_str("Blocks can only have a single variant"),
+            ConvertErr::MisplacedBound => {
+                f.write_str("Bounds can only be placed at the start of blocks")
+            }
+            ConvertErr::DuplicateBound => f.write_str("Blocks can only have a single bound"),
         }
     }
 }
@@ -77,6 +191,8 @@ struct State {
     label_counter: usize,
     label: String,
     require: Option<Formula>,
+    variant: Option<Expr>,
+    bound: Option<Imm>,
     body: Vec<Stmt>,
 }
 
@@ -88,6 +204,8 @@ impl State {
             label: "@0".to_owned(),
             label_counter: 0,
             require: None,
+            variant: None,
+            bound: None,
             body: Vec::new(),
         }
     }
@@ -98,14 +216,20 @@ impl State {
         }
         let mut label = "".to_owned();
         let mut require = None;
+        let mut variant = None;
+        let mut bound = None;
         let mut body = Vec::new();
         swap(&mut self.label, &mut label);
         swap(&mut self.require, &mut require);
+        swap(&mut self.variant, &mut variant);
+        swap(&mut self.bound, &mut bound);
         swap(&mut self.body, &mut body);
         self.blocks.insert(
             label,
             Block {
                 require,
+                variant,
+                bound,
                 body,
                 next,
             },
@@ -146,12 +270,98 @@ impl State {
 }
 
 impl Cfg {
-    pub fn create(ast: Module, f: &mut FormulaBuilder) -> Result<Cfg, ConvertErr> {
+    /// Find the declaration of the map with a file descriptor.
+    pub fn map(&self, fd: Imm) -> Option<&MapDef> {
+        self.maps.iter().find(|m| m.fd == fd)
+    }
+
+    /// Parse the CFG of the main program of a module, panicking on errors.
+    #[cfg(test)]
+    pub(crate) fn parse(src: &str) -> Cfg {
+        let (_, ast) = crate::parse::module(src).unwrap();
+        match Self::create(ast, &mut FormulaBuilder::new()) {
+            Ok(mut cfgs) => cfgs.remove(0),
+            Err(e) => panic!("{e}"),
+        }
+    }
+
+    /// Convert a module into the CFG of its main program, followed by those of its subprograms.
+    pub fn create(ast: Module, f: &mut FormulaBuilder) -> Result<Vec<Cfg>, ConvertErr> {
+        let mut contracts = HashMap::new();
+        for func in ast.functions.iter() {
+            let contract = Contract {
+                requires: f.and_all(func.requires.iter().cloned()),
+                ensures: f.and_all(func.ensures.iter().cloned()),
+            };
+            if contracts.insert(func.name.clone(), contract).is_some() {
+                return Err(ConvertErr::DuplicateLabel(func.name.clone()));
+            }
+        }
+
+        let mut cfgs = vec![Self::convert(
+            None,
+            ast.lines,
+            ast.requires,
+            ast.ensures,
+            f,
+        )?];
+        for func in ast.functions {
+            let cfg = Self::convert(Some(func.name), func.lines, func.requires, func.ensures, f)?;
+            cfgs.push(cfg);
+        }
+        // Callbacks must return what the helpers that they are passed to accept.
+        let mut callback_ensures = Vec::new();
+        for cfg in cfgs.iter() {
+            for block in cfg.blocks.values() {
+                for (index, stmt) in block.body.iter().enumerate() {
+                    match stmt {
+                        Stmt::CallLocal(name) | Stmt::LoadFunc(_, name)
+                            if !contracts.contains_key(name) =>
+                        {
+                            return Err(ConvertErr::UnknownFunction(name.clone()))
+                        }
+                        Stmt::Call(id) => {
+                            if let Some(callback) = helper_by_id(*id).callback {
+                                let name = block
+                                    .callback(index)
+                                    .ok_or(ConvertErr::MissingCallback(stmt.clone()))?;
+                                let r0 = f.reg(Reg::R0).0;
+                                let ret = f.rel(Cc::Le, r0, f.val(callback.max_ret));
+                                callback_ensures.push((name.clone(), ret));
+                            }
+                        }
+                        _ => (),
+                    }
+                }
+            }
+        }
+        for (name, ret) in callback_ensures {
+            if let Some(cfg) = cfgs.iter_mut().find(|c| c.name.as_ref() == Some(&name)) {
+                cfg.ensures = f.and(cfg.ensures.clone(), ret);
+            }
+        }
+
+        let tail_requires = f.and_all(ast.tail_requires);
+        for cfg in cfgs.iter_mut() {
+            cfg.maps = ast.maps.clone();
+            cfg.contracts = contracts.clone();
+            cfg.tail_requires = tail_requires.clone();
+        }
+        Ok(cfgs)
+    }
+
+    fn convert(
+        name: Option<Label>,
+        lines: Vec<Line>,
+        requires: Vec<Formula>,
+        ensures: Vec<Formula>,
+        f: &mut FormulaBuilder,
+    ) -> Result<Cfg, ConvertErr> {
         let mut state = State::new();
-        if ast.lines.last() != Some(&Line::Cont(Cont::Exit)) {
+        if lines.last() != Some(&Line::Cont(Cont::Exit)) {
             return Err(ConvertErr::NoExit);
         }
-        for line in ast.lines {
+        for line in lines {
             match line {
                 Line::Label(l) => {
                     if !state.body.is_empty() {
@@ -171,7 +381,29 @@ impl Cfg {
                         return Err(ConvertErr::MisplacedRequire);
                     }
                 }
-                Line::Stmt(i) => state.body.push(i),
+                Line::Logic(Logic::Variant(v)) => {
+                    if !state.body.is_empty() {
+                        return Err(ConvertErr::MisplacedVariant);
+                    }
+                    if state.variant.replace(v).is_some() {
+                        return Err(ConvertErr::DuplicateVariant);
+                    }
+                }
+                Line::Logic(Logic::Bound(n)) => {
+                    if !state.body.is_empty() {
+                        return Err(ConvertErr::MisplacedBound);
+                    }
+                    if state.bound.replace(n).is_some() {
+                        return Err(ConvertErr::DuplicateBound);
+                    }
+                }
+                Line::Stmt(i) => {
+                    // The stack model relies on r10 staying fixed.
+                    if writes_reg(&i) == Some(Reg::R10) {
+                        return Err(ConvertErr::FramePointerWrite(i));
+                    }
+                    state.body.push(i)
+                }
                 Line::Cont(c) => match c {
                     // End of blocks
                     Cont::Jmp(t) => {
@@ -196,11 +428,13 @@ impl Cfg {
         }
         state.resolve_aliases();
 
-        let requires = ast.requires.into_iter().fold(f.top(), |a, b| f.and(a, b));
-        let ensures = ast.ensures.into_iter().fold(f.top(), |a, b| f.and(a, b));
         Ok(Cfg {
-            requires,
-            ensures,
+            name,
+            maps: Vec::new(),
+            contracts: HashMap::new(),
+            requires: f.and_all(requires),
+            ensures: f.and_all(ensures),
+            tail_requires: f.top(),
             start: state
                 .label_aliases
                 .get("@0")
@@ -210,3 +444,25 @@ impl Cfg {
         })
     }
 }
+
+/// A label of a block in the CFGs that tests parse.
+#[cfg(test)]
+pub(crate) fn label(l: &str) -> Label {
+    l.to_owned()
+}
+
+/// The register that a statement writes to, apart from calls.
+fn writes_reg(stmt: &Stmt) -> Option<Reg> {
+    match stmt {
+        Stmt::Unary(_, _, dst)
+        | Stmt::Binary(_, _, dst, _)
+        | Stmt::Load(_, dst, _)
+        | Stmt::LoadImm(dst, _)
+        | Stmt::LoadMapFd(dst, _)
+        | Stmt::LoadFunc(dst, _) => Some(*dst),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests;
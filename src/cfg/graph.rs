@@ -0,0 +1,398 @@
+//! Graph algorithms on CFGs.
+//! Only blocks reachable from the start are considered.
+
+use std::collections::{HashMap, HashSet};
+
+use super::{Cfg, Continuation, Label};
+
+impl Cfg {
+    /// Labels of the blocks that control may pass to from a block.
+    pub fn successors(&self, label: &Label) -> Vec<&Label> {
+        self.blocks[label].next.targets()
+    }
+
+    /// Labels of the blocks that some path from the start reaches.
+    pub fn reachable(&self) -> HashSet<&Label> {
+        let mut reachable = HashSet::from([&self.start]);
+        let mut worklist = vec![&self.start];
+        while let Some(label) = worklist.pop() {
+            for target in self.successors(label) {
+                if reachable.insert(target) {
+                    worklist.push(target);
+                }
+            }
+        }
+        reachable
+    }
+
+    /// Predecessors of every reachable block.
+    pub fn predecessors(&self) -> HashMap<&Label, Vec<&Label>> {
+        let mut preds: HashMap<&Label, Vec<&Label>> = HashMap::new();
+        for label in self.reachable() {
+            preds.entry(label).or_default();
+            for target in self.successors(label) {
+                preds.entry(target).or_default().push(label);
+            }
+        }
+        preds
+    }
+
+    /// Labels of the reachable blocks in reverse postorder of a depth-first search from the start,
+    /// so that every block comes before its successors apart from those along back edges.
+    pub fn reverse_postorder(&self) -> Vec<&Label> {
+        let mut order = Vec::new();
+        let mut visited = HashSet::from([&self.start]);
+        let mut stack = vec![(&self.start, self.successors(&self.start).into_iter())];
+        while let Some((label, targets)) = stack.last_mut() {
+            let label = *label;
+            match targets.next() {
+                Some(target) if visited.insert(target) => {
+                    stack.push((target, self.successors(target).into_iter()));
+                }
+                Some(_) => (),
+                None => {
+                    order.push(label);
+                    stack.pop();
+                }
+            }
+        }
+        order.reverse();
+        order
+    }
+
+    /// The dominator tree, rooted at the start.
+    /// A block dominates another if every path from the start to the other passes through it.
+    pub fn dominators(&self) -> DomTree<'_> {
+        let labels: Vec<&Label> = self.reachable().into_iter().collect();
+        let index: HashMap<&Label, usize> =
+            labels.iter().enumerate().map(|(i, l)| (*l, i)).collect();
+        let mut succs = vec![Vec::new(); labels.len()];
+        for (i, label) in labels.iter().enumerate() {
+            succs[i] = self.successors(label).iter().map(|t| index[t]).collect();
+        }
+        let start = index[&self.start];
+        DomTree::new(labels, index, start, succs)
+    }
+
+    /// The post-dominator tree, rooted at a virtual node that every exit jumps to.
+    /// A block post-dominates another if every path from the other to an exit passes through it.
+    /// Blocks that can't reach an exit aren't part of the tree.
+    pub fn post_dominators(&self) -> DomTree<'_> {
+        let labels: Vec<&Label> = self.reachable().into_iter().collect();
+        let index: HashMap<&Label, usize> =
+            labels.iter().enumerate().map(|(i, l)| (*l, i)).collect();
+        let exit = labels.len();
+        let mut succs = vec![Vec::new(); labels.len() + 1];
+        for (i, label) in labels.iter().enumerate() {
+            for target in self.successors(label) {
+                succs[index[target]].push(i);
+            }
+            if self.blocks[*label].next == Continuation::Exit {
+                succs[exit].push(i);
+            }
+        }
+        DomTree::new(labels, index, exit, succs)
+    }
+
+    /// Strongly connected components of the reachable blocks,
+    /// ordered so that edges between components only go forwards.
+    pub fn sccs(&self) -> Vec<Vec<&Label>> {
+        self.sccs_within(&self.reachable())
+    }
+
+    /// Strongly connected components of the subgraph induced by some of the reachable blocks,
+    /// ordered so that edges between components only go forwards.
+    /// This uses Kosaraju's algorithm.
+    pub fn sccs_within<'a>(&'a self, nodes: &HashSet<&'a Label>) -> Vec<Vec<&'a Label>> {
+        let preds = self.predecessors();
+        let mut sorted: Vec<&Label> = nodes.iter().copied().collect();
+        sorted.sort();
+
+        // Order the nodes by when a depth-first search finishes with them.
+        let mut finished = Vec::new();
+        let mut visited = HashSet::new();
+        for root in sorted.iter() {
+            if !visited.insert(*root) {
+                continue;
+            }
+            let mut stack = vec![(*root, self.successors(root).into_iter())];
+            while let Some((label, targets)) = stack.last_mut() {
+                let label = *label;
+                match targets.next() {
+                    Some(target) if nodes.contains(target) && visited.insert(target) => {
+                        stack.push((target, self.successors(target).into_iter()));
+                    }
+                    Some(_) => (),
+                    None => {
+                        finished.push(label);
+                        stack.pop();
+                    }
+                }
+            }
+        }
+
+        // Nodes reaching a node backwards in reverse finishing order form its component.
+        let mut assigned = HashSet::new();
+        let mut result = Vec::new();
+        for root in finished.into_iter().rev() {
+            if !assigned.insert(root) {
+                continue;
+            }
+            let mut component = vec![root];
+            let mut worklist = vec![root];
+            while let Some(label) = worklist.pop() {
+                for pred in preds[label].iter() {
+                    if nodes.contains(pred) && assigned.insert(pred) {
+                        component.push(pred);
+                        worklist.push(pred);
+                    }
+                }
+            }
+            component.sort();
+            result.push(component);
+        }
+        result
+    }
+
+    /// The natural loops of the CFG, with one loop per header, nested by containment.
+    /// Back edges are edges to a block that dominates their source.
+    pub fn loop_forest(&self) -> LoopForest {
+        let doms = self.dominators();
+        let preds = self.predecessors();
+        let mut latches: HashMap<&Label, Vec<&Label>> = HashMap::new();
+        for label in self.reverse_postorder() {
+            for target in self.successors(label) {
+                if doms.dominates(target, label) {
+                    latches.entry(target).or_default().push(label);
+                }
+            }
+        }
+
+        let mut loops: Vec<Loop> = latches
+            .into_iter()
+            .map(|(header, latches)| {
+                // The body is what reaches a latch without passing through the header.
+                let mut body = HashSet::from([header]);
+                let mut worklist = latches.clone();
+                while let Some(label) = worklist.pop() {
+                    if body.insert(label) {
+                        worklist.extend(preds[label].iter().copied());
+                    }
+                }
+                let mut latches: Vec<Label> = latches.into_iter().cloned().collect();
+                latches.sort();
+                latches.dedup();
+                Loop {
+                    header: header.clone(),
+                    latches,
+                    body: body.into_iter().cloned().collect(),
+                }
+            })
+            .collect();
+        // Outer loops come before the loops that they contain.
+        loops.sort_by(|a, b| (b.body.len(), &a.header).cmp(&(a.body.len(), &b.header)));
+
+        let parents = (0..loops.len())
+            .map(|i| {
+                (0..i)
+                    .rev()
+                    .find(|j| loops[*j].body.contains(&loops[i].header))
+            })
+            .collect();
+        LoopForest { loops, parents }
+    }
+}
+
+/// A tree of blocks where every block's parent is its immediate dominator or post-dominator.
+pub struct DomTree<'a> {
+    labels: Vec<&'a Label>,
+    index: HashMap<&'a Label, usize>,
+    /// Parent of every node in the tree, which is itself for the root.
+    idom: Vec<Option<usize>>,
+    /// Depth of every node in the tree.
+    depth: Vec<usize>,
+}
+
+impl<'a> DomTree<'a> {
+    /// Compute the tree with the iterative algorithm of Cooper, Harvey and Kennedy.
+    /// Nodes are indices into `labels`, except possibly for a virtual root after them.
+    fn new(
+        labels: Vec<&'a Label>,
+        index: HashMap<&'a Label, usize>,
+        root: usize,
+        succs: Vec<Vec<usize>>,
+    ) -> Self {
+        let n = succs.len();
+        let mut preds = vec![Vec::new(); n];
+        for (i, targets) in succs.iter().enumerate() {
+            for t in targets {
+                preds[*t].push(i);
+            }
+        }
+
+        // Reverse postorder from the root.
+        let mut rpo = Vec::new();
+        let mut visited = vec![false; n];
+        visited[root] = true;
+        let mut stack = vec![(root, 0)];
+        while let Some((node, next)) = stack.last_mut() {
+            let node = *node;
+            match succs[node].get(*next) {
+                Some(t) => {
+                    *next += 1;
+                    if !visited[*t] {
+                        visited[*t] = true;
+                        stack.push((*t, 0));
+                    }
+                }
+                None => {
+                    rpo.push(node);
+                    stack.pop();
+                }
+            }
+        }
+        rpo.reverse();
+        let mut order = vec![usize::MAX; n];
+        for (i, node) in rpo.iter().enumerate() {
+            order[*node] = i;
+        }
+
+        let mut idom: Vec<Option<usize>> = vec![None; n];
+        idom[root] = Some(root);
+        let intersect = |idom: &[Option<usize>], mut a: usize, mut b: usize| {
+            while a != b {
+                while order[a] > order[b] {
+                    a = idom[a].unwrap();
+                }
+                while order[b] > order[a] {
+                    b = idom[b].unwrap();
+                }
+            }
+            a
+        };
+        let mut changed = true;
+        while changed {
+            changed = false;
+            for node in rpo.iter().skip(1) {
+                let new_idom =
+                    preds[*node]
+                        .iter()
+                        .filter(|p| idom[**p].is_some())
+                        .fold(None, |acc, p| match acc {
+                            None => Some(*p),
+                            Some(other) => Some(intersect(&idom, *p, other)),
+                        });
+                if new_idom.is_some() && idom[*node] != new_idom {
+                    idom[*node] = new_idom;
+                    changed = true;
+                }
+            }
+        }
+
+        let mut depth = vec![0; n];
+        for node in rpo.iter().skip(1) {
+            depth[*node] = depth[idom[*node].unwrap()] + 1;
+        }
+        Self {
+            labels,
+            index,
+            idom,
+            depth,
+        }
+    }
+
+    fn node(&self, label: &Label) -> Option<usize> {
+        self.index
+            .get(label)
+            .copied()
+            .filter(|i| self.idom[*i].is_some())
+    }
+
+    /// Whether a block is part of the tree.
+    pub fn contains(&self, label: &Label) -> bool {
+        self.node(label).is_some()
+    }
+
+    /// The parent of a block in the tree,
+    /// or `None` if it is the root, a child of the virtual root or not part of the tree.
+    pub fn idom(&self, label: &Label) -> Option<&'a Label> {
+        let parent = self.idom[self.node(label)?]?;
+        self.labels
+            .get(parent)
+            .copied()
+            .filter(|parent| *parent != label)
+    }
+
+    /// The children of a block in the tree.
+    pub fn children(&self, label: &Label) -> Vec<&'a Label> {
+        let Some(node) = self.node(label) else {
+            return vec![];
+        };
+        let mut children: Vec<&Label> = (0..self.labels.len())
+            .filter(|i| *i != node && self.idom[*i] == Some(node))
+            .map(|i| self.labels[i])
+            .collect();
+        children.sort();
+        children
+    }
+
+    /// Whether `a` dominates `b`, which holds for every block of the tree dominating itself.
+    pub fn dominates(&self, a: &Label, b: &Label) -> bool {
+        let (Some(a), Some(mut b)) = (self.node(a), self.node(b)) else {
+            return false;
+        };
+        while self.depth[b] > self.depth[a] {
+            b = self.idom[b].unwrap();
+        }
+        a == b
+    }
+}
+
+/// A natural loop: the blocks of the cycles through the back edges to a header.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Loop {
+    pub header: Label,
+    /// Sources of the back edges to the header.
+    pub latches: Vec<Label>,
+    pub body: HashSet<Label>,
+}
+
+/// The natural loops of a CFG, nested by containment.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LoopForest {
+    /// Every loop, with outer loops before the loops that they contain.
+    pub loops: Vec<Loop>,
+    /// Index of the innermost loop containing each loop, if there is one.
+    pub parents: Vec<Option<usize>>,
+}
+
+impl LoopForest {
+    /// Indices of the outermost loops.
+    pub fn roots(&self) -> Vec<usize> {
+        (0..self.loops.len())
+            .filter(|i| self.parents[*i].is_none())
+            .collect()
+    }
+
+    /// Indices of the loops directly nested in a loop.
+    pub fn children(&self, index: usize) -> Vec<usize> {
+        (0..self.loops.len())
+            .filter(|i| self.parents[*i] == Some(index))
+            .collect()
+    }
+
+    /// Index of the innermost loop containing a block, if there is one.
+    pub fn innermost(&self, label: &Label) -> Option<usize> {
+        (0..self.loops.len())
+            .rev()
+            .find(|i| self.loops[*i].body.contains(label))
+    }
+
+    /// Number of loops containing a block.
+    pub fn depth(&self, label: &Label) -> usize {
+        self.loops.iter().filter(|l| l.body.contains(label)).count()
+    }
+}
+
+#[cfg(test)]
+mod tests;
@@ -0,0 +1,157 @@
+use super::*;
+use crate::cfg::label;
+
+/// A diamond followed by a loop.
+const DIAMOND_LOOP: &str = "\
+a:
+    jeq r1 0 c
+b:
+    mov r0 1
+    ja d
+c:
+    mov r0 2
+d:
+    mov r2 0
+e:
+    add r2 1
+f:
+    jlt r2 10 e
+g:
+    exit
+";
+
+/// Loops nested in each other.
+const NESTED: &str = "\
+a:
+    mov r1 0
+outer:
+    mov r2 0
+inner:
+    add r2 1
+    jlt r2 10 inner
+latch:
+    add r1 1
+    jlt r1 10 outer
+end:
+    exit
+";
+
+/// A loop that can be entered at both `b` and `c`.
+const IRREDUCIBLE: &str = "\
+a:
+    mov r1 0
+    jgt r2 5 c
+b:
+    add r1 1
+c:
+    jlt r1 10 b
+d:
+    mov r0 r1
+    exit
+";
+
+#[test]
+fn dominators() {
+    let cfg = Cfg::parse(DIAMOND_LOOP);
+    let doms = cfg.dominators();
+    let idoms = [
+        ("a", None),
+        ("b", Some("a")),
+        ("c", Some("a")),
+        ("d", Some("a")),
+        ("e", Some("d")),
+        ("f", Some("e")),
+        ("g", Some("f")),
+    ];
+    for (l, idom) in idoms {
+        assert_eq!(
+            doms.idom(&label(l)),
+            idom.map(label).as_ref(),
+            "idom of {l}"
+        );
+    }
+    assert_eq!(doms.children(&label("a")), vec!["b", "c", "d"]);
+    assert!(doms.dominates(&label("a"), &label("g")));
+    assert!(doms.dominates(&label("e"), &label("e")));
+    assert!(!doms.dominates(&label("b"), &label("d")));
+    assert!(!doms.dominates(&label("f"), &label("e")));
+}
+
+#[test]
+fn post_dominators() {
+    let cfg = Cfg::parse(DIAMOND_LOOP);
+    let pdoms = cfg.post_dominators();
+    let ipdoms = [
+        ("a", Some("d")),
+        ("b", Some("d")),
+        ("c", Some("d")),
+        ("d", Some("e")),
+        ("e", Some("f")),
+        ("f", Some("g")),
+        ("g", None),
+    ];
+    for (l, ipdom) in ipdoms {
+        assert_eq!(
+            pdoms.idom(&label(l)),
+            ipdom.map(label).as_ref(),
+            "ipdom of {l}"
+        );
+    }
+    assert!(pdoms.dominates(&label("g"), &label("a")));
+    assert!(!pdoms.dominates(&label("b"), &label("a")));
+}
+
+#[test]
+fn post_dominators_without_exit() {
+    let cfg = Cfg::parse("a:\n    jeq r1 0 c\nb:\n    ja b\nc:\n    exit\n");
+    let pdoms = cfg.post_dominators();
+    assert!(pdoms.contains(&label("a")));
+    assert!(pdoms.contains(&label("c")));
+    assert!(!pdoms.contains(&label("b")));
+    assert_eq!(pdoms.idom(&label("a")), Some(&label("c")));
+}
+
+#[test]
+fn irreducible_sccs() {
+    let cfg = Cfg::parse(IRREDUCIBLE);
+    let sccs = cfg.sccs();
+    assert_eq!(sccs.len(), 3);
+    assert!(sccs.contains(&vec![&label("b"), &label("c")]));
+
+    // Edges between components only go forwards.
+    let position = |l: &Label| sccs.iter().position(|c| c.contains(&l)).unwrap();
+    for l in cfg.reachable() {
+        for t in cfg.successors(l) {
+            assert!(position(l) <= position(t), "edge {l} -> {t} goes backwards");
+        }
+    }
+
+    // Neither entry dominates the other, so there is no natural loop.
+    assert!(cfg.loop_forest().loops.is_empty());
+}
+
+#[test]
+fn loop_nesting() {
+    let cfg = Cfg::parse(NESTED);
+    let forest = cfg.loop_forest();
+    assert_eq!(forest.loops.len(), 2);
+    let (outer, inner) = (&forest.loops[0], &forest.loops[1]);
+    assert_eq!(outer.header, "outer");
+    assert_eq!(outer.latches, vec!["latch"]);
+    assert_eq!(
+        outer.body,
+        HashSet::from([label("outer"), label("inner"), label("latch")])
+    );
+    assert_eq!(inner.header, "inner");
+    assert_eq!(inner.latches, vec!["inner"]);
+    assert_eq!(inner.body, HashSet::from([label("inner")]));
+
+    assert_eq!(forest.parents, vec![None, Some(0)]);
+    assert_eq!(forest.roots(), vec![0]);
+    assert_eq!(forest.children(0), vec![1]);
+    assert_eq!(forest.innermost(&label("inner")), Some(1));
+    assert_eq!(forest.innermost(&label("latch")), Some(0));
+    assert_eq!(forest.innermost(&label("end")), None);
+    assert_eq!(forest.depth(&label("inner")), 2);
+    assert_eq!(forest.depth(&label("a")), 0);
+}
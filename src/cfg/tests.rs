@@ -0,0 +1,155 @@
+use super::*;
+
+fn create(src: &str) -> Result<Vec<Cfg>, ConvertErr> {
+    let (_, ast) = crate::parse::module(src).unwrap();
+    Cfg::create(ast, &mut FormulaBuilder::new())
+}
+
+#[test]
+fn functions() {
+    let src = "\
+mov r1 1
+call double
+exit
+
+;# function double
+;# requires r1 <= 100
+;# ensures r0 <= 200
+mov r0 r1
+add r0 r1
+exit
+";
+    let Ok(cfgs) = create(src) else {
+        panic!("conversion failed")
+    };
+    let names: Vec<_> = cfgs.iter().map(|c| c.name.clone()).collect();
+    assert_eq!(names, vec![None, Some("double".to_owned())]);
+    // Every CFG knows the contracts of all subprograms.
+    let f = FormulaBuilder::new();
+    let requires = f.and_all([f.rel(Cc::Le, f.reg(Reg::R1).0, f.val(100))]);
+    for cfg in cfgs.iter() {
+        assert_eq!(cfg.contracts["double"].requires, requires);
+    }
+    assert_eq!(cfgs[1].requires, requires);
+}
+
+#[test]
+fn unknown_functions() {
+    let call = "call double\nexit\n";
+    assert!(matches!(create(call), Err(ConvertErr::UnknownFunction(name)) if name == "double"));
+    let load = "ldfunc r2 step\nmov r0 0\nexit\n";
+    assert!(matches!(create(load), Err(ConvertErr::UnknownFunction(name)) if name == "step"));
+}
+
+#[test]
+fn duplicate_functions() {
+    let src = "\
+mov r0 0
+exit
+
+;# function f
+mov r0 0
+exit
+
+;# function f
+mov r0 1
+exit
+";
+    assert!(matches!(create(src), Err(ConvertErr::DuplicateLabel(name)) if name == "f"));
+}
+
+#[test]
+fn tail_requires() {
+    let src = "\
+;# tail_requires r1 = 5
+mov r0 0
+exit
+
+;# function f
+mov r0 0
+exit
+";
+    let Ok(cfgs) = create(src) else {
+        panic!("conversion failed")
+    };
+    let f = FormulaBuilder::new();
+    let requires = f.and_all([f.eq(f.reg(Reg::R1).0, f.val(5))]);
+    assert!(cfgs.iter().all(|c| c.tail_requires == requires));
+}
+
+const LOOP: &str = "\
+mov r1 16
+ldfunc r2 step
+mov r3 0
+mov r4 0
+call 181
+exit
+
+;# function step
+;# ensures r0 >= 0
+mov r0 0
+exit
+";
+
+#[test]
+fn callbacks() {
+    let Ok(cfgs) = create(LOOP) else {
+        panic!("conversion failed")
+    };
+    assert_eq!(cfgs[0].blocks["@0"].callback(4), Some(&"step".to_owned()));
+    // The callback must return what bpf_loop accepts, on top of its own contract.
+    let f = FormulaBuilder::new();
+    let r0 = f.reg(Reg::R0).0;
+    let ensures = f.and_all([f.rel(Cc::Ge, r0.clone(), f.val(0))]);
+    assert_eq!(cfgs[1].ensures, f.and(ensures, f.rel(Cc::Le, r0, f.val(1))));
+}
+
+#[test]
+fn missing_callbacks() {
+    // The callback must be loaded into r2 after the last write to it.
+    let overwritten = LOOP.replace("mov r3 0\n", "mov r2 0\n");
+    assert!(matches!(
+        create(&overwritten),
+        Err(ConvertErr::MissingCallback(Stmt::Call(181)))
+    ));
+    let elsewhere = LOOP.replace("mov r3 0\n", "ja next\nnext:\n");
+    assert!(matches!(
+        create(&elsewhere),
+        Err(ConvertErr::MissingCallback(Stmt::Call(181)))
+    ));
+}
+
+#[test]
+fn variants() {
+    let src = "loop:\n;# variant r1\nsub r1 1\njgt r1 0 loop\nexit\n";
+    let Ok(cfgs) = create(src) else {
+        panic!("conversion failed")
+    };
+    let f = FormulaBuilder::new();
+    assert_eq!(cfgs[0].blocks["loop"].variant, Some(f.reg(Reg::R1).0));
+
+    let misplaced = "loop:\nsub r1 1\n;# variant r1\njgt r1 0 loop\nexit\n";
+    assert!(matches!(
+        create(misplaced),
+        Err(ConvertErr::MisplacedVariant)
+    ));
+    let duplicate = "loop:\n;# variant r1\n;# variant r2\nsub r1 1\njgt r1 0 loop\nexit\n";
+    assert!(matches!(
+        create(duplicate),
+        Err(ConvertErr::DuplicateVariant)
+    ));
+}
+
+#[test]
+fn bounds() {
+    let src = "loop:\n;# variant r1\n;# bound 10\nsub r1 1\njgt r1 0 loop\nexit\n";
+    let Ok(cfgs) = create(src) else {
+        panic!("conversion failed")
+    };
+    assert_eq!(cfgs[0].blocks["loop"].bound, Some(10));
+
+    let misplaced = "loop:\nsub r1 1\n;# bound 10\njgt r1 0 loop\nexit\n";
+    assert!(matches!(create(misplaced), Err(ConvertErr::MisplacedBound)));
+    let duplicate = "loop:\n;# bound 10\n;# bound 20\nsub r1 1\njgt r1 0 loop\nexit\n";
+    assert!(matches!(create(duplicate), Err(ConvertErr::DuplicateBound)));
+}
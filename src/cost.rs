@@ -0,0 +1,318 @@
+//! Worst-case instruction counts.
+//! Every loop needs a `bound` annotation on its header giving the maximum number of iterations,
+//! along with a variant so that the VC checks the bound.
+//! Calls of subprograms count the instructions of the callee,
+//! but callbacks passed to helpers are not included.
+
+use std::{
+    collections::{HashMap, HashSet},
+    fmt::{self, Display, Formatter},
+};
+
+use crate::{
+    cfg::*,
+    dataflow::{solve, Analysis, Direction, Lattice},
+    loops::cut_points,
+};
+
+/// Number of instructions that the kernel verifier processes before giving up on a program.
+/// It walks every iteration of a loop, so runs that execute more instructions are rejected.
+pub const KERNEL_MAX_INSNS: u64 = 1_000_000;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CostErr {
+    /// A loop whose header has no bound on its iterations.
+    Unbounded(Label),
+    /// A loop with a bound but no variant, so that the VC can't check the bound.
+    Unchecked(Label),
+    /// A loop that can be entered other than through its header.
+    Irreducible(Label),
+    /// A function that calls itself, directly or indirectly.
+    Recursive(Label),
+    /// A call of a function whose cost is unknown.
+    Callee(Label),
+}
+
+impl Display for CostErr {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            CostErr::Unbounded(label) => f.write_fmt(format_args!(
+                "Loop at {label} has no bound on its iterations"
+            )),
+            CostErr::Unchecked(label) => f.write_fmt(format_args!(
+                "Bound of loop at {label} is unchecked, as the loop has no variant"
+            )),
+            CostErr::Irreducible(label) => f.write_fmt(format_args!(
+                "Loop at {label} can be entered other than through its header"
+            )),
+            CostErr::Recursive(label) => {
+                f.write_fmt(format_args!("Function {label} might be recursive"))
+            }
+            CostErr::Callee(label) => f.write_fmt(format_args!(
+                "Number of instructions of function {label} is unknown"
+            )),
+        }
+    }
+}
+
+/// A block on a path, or a loop that is run through repeatedly.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Step {
+    pub label: Label,
+    /// Number of times that a loop goes back to its header, or 1 for other blocks.
+    pub iterations: u64,
+    /// The most expensive path through a single iteration of a loop.
+    pub body: Vec<Step>,
+}
+
+impl Display for Step {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        if self.body.is_empty() {
+            return f.write_str(&self.label);
+        }
+        f.write_fmt(format_args!(
+            "{} x{} ({})",
+            self.label,
+            self.iterations,
+            Path(&self.body)
+        ))
+    }
+}
+
+struct Path<'a>(&'a [Step]);
+
+impl Display for Path<'_> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        for (i, step) in self.0.iter().enumerate() {
+            if i > 0 {
+                f.write_str(" -> ")?;
+            }
+            step.fmt(f)?;
+        }
+        Ok(())
+    }
+}
+
+/// Worst-case number of instructions of a run, along with the path that executes them.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Cost {
+    pub insns: u64,
+    pub path: Vec<Step>,
+}
+
+impl Display for Cost {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.write_fmt(format_args!(
+            "{} instructions via {}",
+            self.insns,
+            Path(&self.path)
+        ))
+    }
+}
+
+/// Worst-case costs from a program point, keyed by where their paths end:
+/// `None` for the exit of the run, or the header of the loop that they go back to.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+struct Remaining(HashMap<Option<Label>, Cost>);
+
+impl Lattice for Remaining {
+    fn join(&mut self, other: &Self) -> bool {
+        let mut changed = false;
+        for (end, cost) in other.0.iter() {
+            match self.0.get(end) {
+                Some(c) if c.insns >= cost.insns => (),
+                _ => {
+                    self.0.insert(end.clone(), cost.clone());
+                    changed = true;
+                }
+            }
+        }
+        changed
+    }
+}
+
+/// A backward analysis of the most expensive paths from each block.
+/// Back edges end the paths of an iteration,
+/// which are multiplied by the bound of the loop where the loop is entered.
+struct WorstCase<'a> {
+    callees: &'a HashMap<Label, u64>,
+    /// The region of each loop header and its bound.
+    loops: HashMap<Label, (HashSet<Label>, u64)>,
+}
+
+impl WorstCase<'_> {
+    /// Account for the iterations of the loop at `header` when entering it.
+    /// The loop goes back to its header at most `bound` times, before a last pass leaving it.
+    fn enter(&self, header: &Label, fact: &mut Remaining) {
+        let Some(iteration) = fact.0.remove(&Some(header.clone())) else {
+            return;
+        };
+        let iterations = self.loops[header].1;
+        let step = Step {
+            label: header.clone(),
+            iterations,
+            body: iteration.path,
+        };
+        let insns = iteration.insns.saturating_mul(iterations);
+        for cost in fact.0.values_mut() {
+            cost.insns = cost.insns.saturating_add(insns);
+            cost.path.insert(0, step.clone());
+        }
+    }
+}
+
+impl Analysis for WorstCase<'_> {
+    type Fact = Remaining;
+    const DIRECTION: Direction = Direction::Backward;
+
+    fn bottom(&self, _cfg: &Cfg) -> Remaining {
+        Remaining::default()
+    }
+
+    fn boundary(&self, _cfg: &Cfg) -> Remaining {
+        Remaining(HashMap::from([(None, Cost::default())]))
+    }
+
+    fn transfer(&self, _label: &Label, _index: usize, stmt: &Stmt, fact: &mut Remaining) {
+        // Calls of subprograms run the instructions of the callee as well.
+        let insns = match stmt {
+            Stmt::Assert(_) => 0,
+            Stmt::CallLocal(name) => self.callees[name].saturating_add(1),
+            _ => 1,
+        };
+        for cost in fact.0.values_mut() {
+            cost.insns = cost.insns.saturating_add(insns);
+        }
+    }
+
+    fn transfer_cont(&self, label: &Label, _next: &Continuation, fact: &mut Remaining) {
+        let step = Step {
+            label: label.clone(),
+            iterations: 1,
+            body: Vec::new(),
+        };
+        for cost in fact.0.values_mut() {
+            cost.insns = cost.insns.saturating_add(1);
+            cost.path.insert(0, step.clone());
+        }
+    }
+
+    fn edge(&self, label: &Label, _next: &Continuation, target: &Label, fact: &mut Remaining) {
+        match self.loops.get(target) {
+            Some((region, _)) if region.contains(label) => {
+                *fact = Remaining(HashMap::from([(Some(target.clone()), Cost::default())]));
+            }
+            Some(_) => self.enter(target, fact),
+            None => (),
+        }
+    }
+}
+
+/// Compute the worst-case number of instructions that a run of a CFG executes,
+/// given the costs of the functions that it calls.
+pub fn worst_case(cfg: &Cfg, callees: &HashMap<Label, u64>) -> Result<Cost, CostErr> {
+    for label in cfg.reachable() {
+        for stmt in cfg.blocks[label].body.iter() {
+            if let Stmt::CallLocal(name) = stmt {
+                if !callees.contains_key(name) {
+                    return Err(CostErr::Callee(name.clone()));
+                }
+            }
+        }
+    }
+
+    let mut loops = HashMap::new();
+    for cut in cut_points(cfg) {
+        if !cut.other_entries.is_empty() {
+            return Err(CostErr::Irreducible(cut.label));
+        }
+        let block = &cfg.blocks[&cut.label];
+        let bound = block.bound.ok_or(CostErr::Unbounded(cut.label.clone()))?;
+        if block.variant.is_none() {
+            return Err(CostErr::Unchecked(cut.label));
+        }
+        loops.insert(cut.label, (cut.region, bound as u64));
+    }
+
+    let analysis = WorstCase { callees, loops };
+    let results = solve(cfg, &analysis);
+    let mut remaining = results.entry(&cfg.start).cloned().unwrap_or_default();
+    if analysis.loops.contains_key(&cfg.start) {
+        analysis.enter(&cfg.start, &mut remaining);
+    }
+    Ok(remaining.0.remove(&None).unwrap_or_default())
+}
+
+/// Compute the worst-case cost of every CFG of a module, callees first.
+/// Results are in the same order as the CFGs.
+pub fn costs(cfgs: &[Cfg]) -> Vec<Result<Cost, CostErr>> {
+    let calls: Vec<HashSet<&Label>> = cfgs
+        .iter()
+        .map(|cfg| {
+            cfg.blocks
+                .values()
+                .flat_map(|b| b.body.iter())
+                .filter_map(|stmt| match stmt {
+                    Stmt::CallLocal(name) => Some(name),
+                    _ => None,
+                })
+                .collect()
+        })
+        .collect();
+
+    let mut results: Vec<Option<Result<Cost, CostErr>>> = vec![None; cfgs.len()];
+    let mut callees: HashMap<Label, u64> = HashMap::new();
+    let mut failed: HashSet<&Label> = HashSet::new();
+    let mut progress = true;
+    while progress {
+        progress = false;
+        for (i, cfg) in cfgs.iter().enumerate() {
+            if results[i].is_some() {
+                continue;
+            }
+            let done = |name: &&Label| callees.contains_key(*name) || failed.contains(name);
+            if !calls[i].iter().all(done) {
+                continue;
+            }
+            let result = match calls[i].iter().find(|name| failed.contains(**name)) {
+                Some(name) => Err(CostErr::Callee((*name).clone())),
+                None => worst_case(cfg, &callees),
+            };
+            if let Some(name) = &cfg.name {
+                match &result {
+                    Ok(cost) => {
+                        callees.insert(name.clone(), cost.insns);
+                    }
+                    Err(_) => {
+                        failed.insert(name);
+                    }
+                }
+            }
+            results[i] = Some(result);
+            progress = true;
+        }
+    }
+
+    // Functions that are left call themselves through some chain of calls,
+    // and the main program calls one of them.
+    results
+        .into_iter()
+        .zip(cfgs.iter().zip(calls))
+        .map(|(result, (cfg, calls))| {
+            result.unwrap_or_else(|| match &cfg.name {
+                Some(name) => Err(CostErr::Recursive(name.clone())),
+                None => {
+                    let mut pending: Vec<&Label> = calls
+                        .into_iter()
+                        .filter(|name| !callees.contains_key(*name))
+                        .collect();
+                    pending.sort();
+                    Err(CostErr::Callee(pending[0].clone()))
+                }
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests;
@@ -0,0 +1,132 @@
+use super::*;
+use crate::formula::FormulaBuilder;
+
+fn cost(src: &str) -> Result<Cost, CostErr> {
+    worst_case(&Cfg::parse(src), &HashMap::new())
+}
+
+fn create(src: &str) -> Vec<Cfg> {
+    let (_, ast) = crate::parse::module(src).unwrap();
+    let Ok(cfgs) = Cfg::create(ast, &mut FormulaBuilder::new()) else {
+        panic!("conversion failed")
+    };
+    cfgs
+}
+
+fn labels(path: &[Step]) -> Vec<&str> {
+    path.iter().map(|s| s.label.as_str()).collect()
+}
+
+#[test]
+fn branches() {
+    // The longer branch is taken, and asserts are free.
+    let src = "\
+jeq r1 0 short
+;# assert r1 >= 1
+mov r0 1
+add r0 1
+ja end
+short:
+mov r0 0
+end:
+exit
+";
+    let cost = cost(src).unwrap();
+    assert_eq!(labels(&cost.path), vec!["@0", "@1", "end"]);
+    assert_eq!(cost.insns, 5);
+}
+
+const LOOP: &str = "\
+mov r1 10
+loop:
+;# req r1 >= 1
+;# variant r1
+;# bound 10
+sub r1 1
+jgt r1 0 loop
+mov r0 0
+exit
+";
+
+#[test]
+fn loops() {
+    // Ten iterations go back to the header, and the last one leaves the loop.
+    let cost = cost(LOOP).unwrap();
+    assert_eq!(cost.insns, 2 + 10 * 2 + 2 + 2);
+    assert_eq!(labels(&cost.path), vec!["@0", "loop", "loop", "@1"]);
+    assert_eq!(cost.path[1].iterations, 10);
+    assert_eq!(labels(&cost.path[1].body), vec!["loop"]);
+}
+
+#[test]
+fn nested_loops() {
+    let src = "\
+mov r1 4
+outer:
+;# req r1 >= 1
+;# variant r1
+;# bound 4
+mov r2 3
+inner:
+;# req r2 >= 1
+;# variant r2
+;# bound 3
+sub r2 1
+jgt r2 0 inner
+sub r1 1
+jgt r1 0 outer
+mov r0 0
+exit
+";
+    // An iteration of the outer loop runs the inner one in full.
+    let inner = 3 * 2 + 2;
+    let outer = 2 + inner + 2;
+    assert_eq!(cost(src).unwrap().insns, 2 + 4 * outer + outer + 2);
+}
+
+#[test]
+fn unbounded() {
+    let src = LOOP.replace(";# bound 10\n", "");
+    assert_eq!(cost(&src), Err(CostErr::Unbounded("loop".to_owned())));
+    // Without a variant, nothing checks the bound.
+    let src = LOOP.replace(";# variant r1\n", "");
+    assert_eq!(cost(&src), Err(CostErr::Unchecked("loop".to_owned())));
+}
+
+#[test]
+fn calls() {
+    let src = "\
+call twice
+exit
+
+;# function twice
+mov r0 r1
+add r0 r1
+exit
+";
+    let cfgs = create(src);
+    let costs = costs(&cfgs);
+    assert_eq!(costs[1].as_ref().unwrap().insns, 3);
+    // The call itself and the instructions of the callee.
+    assert_eq!(costs[0].as_ref().unwrap().insns, 1 + 3 + 1);
+    assert_eq!(
+        worst_case(&cfgs[0], &HashMap::new()),
+        Err(CostErr::Callee("twice".to_owned()))
+    );
+}
+
+#[test]
+fn recursion() {
+    let src = "\
+call f
+exit
+
+;# function f
+call f
+exit
+";
+    let cfgs = create(src);
+    let costs = costs(&cfgs);
+    assert_eq!(costs[1], Err(CostErr::Recursive("f".to_owned())));
+    assert_eq!(costs[0], Err(CostErr::Callee("f".to_owned())));
+}
@@ -130,6 +130,7 @@ impl std::fmt::Display for Formula {
                 }
             }
             Formula::IsBuffer(ptr, sz) => f.write_fmt(format_args!("(is_buffer {ptr} {sz})")),
+            Formula::Held(_) => f.write_str("true"),
         }
     }
 }
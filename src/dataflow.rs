@@ -0,0 +1,390 @@
+//! A worklist framework for dataflow analyses over CFGs.
+//! Analyses provide a lattice of facts and transfer functions for statements, continuations and
+//! edges, where edges can refine facts depending on which way a conditional jump goes.
+//! Register liveness, reaching definitions and def-use chains are built on top of it.
+
+use std::{
+    collections::{HashMap, HashSet, VecDeque},
+    hash::Hash,
+};
+
+use crate::{
+    cfg::*,
+    init::{cont_uses, stmt_defs, stmt_kills, stmt_uses},
+};
+
+/// Facts ordered by how much they approximate, where joining moves up the order.
+pub trait Lattice: Clone + PartialEq {
+    /// Join another fact into this one, returning whether it changed.
+    fn join(&mut self, other: &Self) -> bool;
+}
+
+/// Sets joined by union, as for may-analyses.
+impl<T: Clone + Eq + Hash> Lattice for HashSet<T> {
+    fn join(&mut self, other: &Self) -> bool {
+        let prev_len = self.len();
+        self.extend(other.iter().cloned());
+        self.len() != prev_len
+    }
+}
+
+/// Facts with a least element below them, for program points that no path reaches yet.
+impl<T: Lattice> Lattice for Option<T> {
+    fn join(&mut self, other: &Self) -> bool {
+        match (self.as_mut(), other) {
+            (_, None) => false,
+            (None, Some(b)) => {
+                *self = Some(b.clone());
+                true
+            }
+            (Some(a), Some(b)) => a.join(b),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Direction {
+    Forward,
+    Backward,
+}
+
+pub trait Analysis {
+    type Fact: Lattice;
+    const DIRECTION: Direction;
+
+    /// The least fact, which every reachable block starts from.
+    fn bottom(&self, cfg: &Cfg) -> Self::Fact;
+
+    /// The fact at the start of the program for forward analyses,
+    /// or before every exit for backward ones.
+    fn boundary(&self, cfg: &Cfg) -> Self::Fact;
+
+    /// Apply the statement at `index` of a block.
+    fn transfer(&self, label: &Label, index: usize, stmt: &Stmt, fact: &mut Self::Fact);
+
+    /// Apply the continuation of a block, between its body and its edges.
+    fn transfer_cont(&self, _label: &Label, _next: &Continuation, _fact: &mut Self::Fact) {}
+
+    /// Apply the edge from a block to one of its targets.
+    fn edge(&self, _label: &Label, _next: &Continuation, _target: &Label, _fact: &mut Self::Fact) {}
+
+    /// Join a fact arriving along an edge that closes a cycle,
+    /// extrapolating so that lattices of infinite height still reach a fixpoint.
+    fn widen(&self, fact: &mut Self::Fact, other: &Self::Fact) -> bool {
+        fact.join(other)
+    }
+}
+
+/// The fixpoint of an analysis, with facts in program order regardless of its direction.
+pub struct Results<'a, A: Analysis> {
+    cfg: &'a Cfg,
+    analysis: &'a A,
+    /// Facts at the start of each reachable block.
+    entries: HashMap<&'a Label, A::Fact>,
+    /// Facts at the end of the body of each reachable block, before its continuation.
+    exits: HashMap<&'a Label, A::Fact>,
+}
+
+impl<'a, A: Analysis> Results<'a, A> {
+    /// Labels of the blocks that the analysis covers.
+    pub fn labels(&self) -> impl Iterator<Item = &'a Label> + '_ {
+        self.entries.keys().copied()
+    }
+
+    /// The fact at the start of a block.
+    pub fn entry(&self, label: &Label) -> Option<&A::Fact> {
+        self.entries.get(label)
+    }
+
+    /// The fact at the end of the body of a block, before its continuation.
+    pub fn exit(&self, label: &Label) -> Option<&A::Fact> {
+        self.exits.get(label)
+    }
+
+    /// Facts before each statement of a block, followed by the fact at the end of its body.
+    pub fn stmt_facts(&self, label: &Label) -> Vec<A::Fact> {
+        let body = &self.cfg.blocks[label].body;
+        match A::DIRECTION {
+            Direction::Forward => {
+                let mut fact = self.entries[label].clone();
+                let mut result = vec![fact.clone()];
+                for (index, stmt) in body.iter().enumerate() {
+                    self.analysis.transfer(label, index, stmt, &mut fact);
+                    result.push(fact.clone());
+                }
+                result
+            }
+            Direction::Backward => {
+                let mut fact = self.exits[label].clone();
+                let mut result = vec![fact.clone()];
+                for (index, stmt) in body.iter().enumerate().rev() {
+                    self.analysis.transfer(label, index, stmt, &mut fact);
+                    result.push(fact.clone());
+                }
+                result.reverse();
+                result
+            }
+        }
+    }
+}
+
+/// Compute the fixpoint of an analysis over the reachable blocks of a CFG.
+pub fn solve<'a, A: Analysis>(cfg: &'a Cfg, analysis: &'a A) -> Results<'a, A> {
+    let order = cfg.reverse_postorder();
+    let bottom = analysis.bottom(cfg);
+    let mut entries: HashMap<&Label, A::Fact> =
+        order.iter().map(|l| (*l, bottom.clone())).collect();
+    let mut exits = entries.clone();
+    let mut worklist: VecDeque<&Label> = order.iter().copied().collect();
+    let mut queued: HashSet<&Label> = order.iter().copied().collect();
+
+    match A::DIRECTION {
+        Direction::Forward => {
+            let position: HashMap<&Label, usize> =
+                order.iter().enumerate().map(|(i, l)| (*l, i)).collect();
+            let preds = cfg.predecessors();
+            // The fact flowing along an edge, given the fact at the end of the body of its source.
+            let along = |label: &Label, target: &Label, mut fact: A::Fact| {
+                let next = &cfg.blocks[label].next;
+                analysis.transfer_cont(label, next, &mut fact);
+                analysis.edge(label, next, target, &mut fact);
+                fact
+            };
+
+            entries.insert(&cfg.start, analysis.boundary(cfg));
+            while let Some(label) = worklist.pop_front() {
+                queued.remove(label);
+                let block = &cfg.blocks[label];
+                let mut fact = entries[label].clone();
+                for (index, stmt) in block.body.iter().enumerate() {
+                    analysis.transfer(label, index, stmt, &mut fact);
+                }
+                exits.insert(label, fact.clone());
+                for target in block.next.targets() {
+                    let fact = along(label, target, fact.clone());
+                    let entry = entries.get_mut(target).unwrap();
+                    let changed = match position[target] <= position[label] {
+                        true => analysis.widen(entry, &fact),
+                        false => entry.join(&fact),
+                    };
+                    if changed && queued.insert(target) {
+                        worklist.push_back(target);
+                    }
+                }
+            }
+
+            // A descending pass recovers some of the precision lost by widening.
+            for label in order.iter().copied() {
+                let mut fact = match label == &cfg.start {
+                    true => analysis.boundary(cfg),
+                    false => bottom.clone(),
+                };
+                for pred in preds[label].iter() {
+                    fact.join(&along(pred, label, exits[pred].clone()));
+                }
+                entries.insert(label, fact.clone());
+                for (index, stmt) in cfg.blocks[label].body.iter().enumerate() {
+                    analysis.transfer(label, index, stmt, &mut fact);
+                }
+                exits.insert(label, fact);
+            }
+        }
+        Direction::Backward => {
+            let preds = cfg.predecessors();
+            worklist = worklist.into_iter().rev().collect();
+            while let Some(label) = worklist.pop_front() {
+                queued.remove(label);
+                let block = &cfg.blocks[label];
+                let mut fact = match block.next {
+                    Continuation::Exit => analysis.boundary(cfg),
+                    _ => bottom.clone(),
+                };
+                for target in block.next.targets() {
+                    let mut target_fact = entries[target].clone();
+                    analysis.edge(label, &block.next, target, &mut target_fact);
+                    fact.join(&target_fact);
+                }
+                analysis.transfer_cont(label, &block.next, &mut fact);
+                exits.insert(label, fact.clone());
+                for (index, stmt) in block.body.iter().enumerate().rev() {
+                    analysis.transfer(label, index, stmt, &mut fact);
+                }
+                if entries[label] != fact {
+                    entries.insert(label, fact);
+                    for pred in preds[label].iter() {
+                        if queued.insert(pred) {
+                            worklist.push_back(pred);
+                        }
+                    }
+                }
+            }
+        }
+    }
+    Results {
+        cfg,
+        analysis,
+        entries,
+        exits,
+    }
+}
+
+/// Registers written by a statement, counting the ones that calls clobber.
+fn writes(stmt: &Stmt) -> Vec<Reg> {
+    let mut regs = stmt_defs(stmt);
+    regs.extend(stmt_kills(stmt));
+    regs
+}
+
+/// Registers whose value might still be read.
+pub struct Liveness;
+
+impl Analysis for Liveness {
+    type Fact = HashSet<Reg>;
+    const DIRECTION: Direction = Direction::Backward;
+
+    fn bottom(&self, _cfg: &Cfg) -> Self::Fact {
+        HashSet::new()
+    }
+
+    fn boundary(&self, _cfg: &Cfg) -> Self::Fact {
+        HashSet::new()
+    }
+
+    fn transfer(&self, _label: &Label, _index: usize, stmt: &Stmt, fact: &mut Self::Fact) {
+        for r in writes(stmt) {
+            fact.remove(&r);
+        }
+        fact.extend(stmt_uses(stmt));
+    }
+
+    fn transfer_cont(&self, _label: &Label, next: &Continuation, fact: &mut Self::Fact) {
+        fact.extend(cont_uses(next));
+    }
+}
+
+/// Compute the live registers of a CFG.
+pub fn liveness(cfg: &Cfg) -> Results<'_, Liveness> {
+    solve(cfg, &Liveness)
+}
+
+/// A place in a block: the statement at an index,
+/// or the continuation at the index just past the end of the body.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct Loc {
+    pub label: Label,
+    pub index: usize,
+}
+
+/// Where a register gets its value.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum DefSite {
+    /// The value that the register has when the program starts.
+    Entry,
+    At(Loc),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct Def {
+    pub reg: Reg,
+    pub site: DefSite,
+}
+
+/// Definitions of registers that might reach a program point without being overwritten.
+pub struct ReachingDefs;
+
+impl Analysis for ReachingDefs {
+    type Fact = HashSet<Def>;
+    const DIRECTION: Direction = Direction::Forward;
+
+    fn bottom(&self, _cfg: &Cfg) -> Self::Fact {
+        HashSet::new()
+    }
+
+    fn boundary(&self, _cfg: &Cfg) -> Self::Fact {
+        (0..11)
+            .filter_map(Reg::new)
+            .map(|reg| Def {
+                reg,
+                site: DefSite::Entry,
+            })
+            .collect()
+    }
+
+    fn transfer(&self, label: &Label, index: usize, stmt: &Stmt, fact: &mut Self::Fact) {
+        for reg in writes(stmt) {
+            fact.retain(|d| d.reg != reg);
+            fact.insert(Def {
+                reg,
+                site: DefSite::At(Loc {
+                    label: label.clone(),
+                    index,
+                }),
+            });
+        }
+    }
+}
+
+/// Compute the reaching definitions of a CFG.
+pub fn reaching_defs(cfg: &Cfg) -> Results<'_, ReachingDefs> {
+    solve(cfg, &ReachingDefs)
+}
+
+/// A read of a register.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct Use {
+    pub reg: Reg,
+    pub loc: Loc,
+}
+
+/// Links between the definitions of registers and the reads of them that they might reach.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct DefUse {
+    /// Definitions that each read might see.
+    pub defs: HashMap<Use, Vec<Def>>,
+    /// Reads that each definition might reach.
+    pub uses: HashMap<Def, Vec<Use>>,
+}
+
+/// Compute the def-use and use-def chains of a CFG.
+pub fn def_use(cfg: &Cfg) -> DefUse {
+    let reaching = reaching_defs(cfg);
+    let mut result = DefUse::default();
+    for label in reaching.labels() {
+        let block = &cfg.blocks[label];
+        let facts = reaching.stmt_facts(label);
+        let reads = block
+            .body
+            .iter()
+            .map(stmt_uses)
+            .chain([cont_uses(&block.next)])
+            .enumerate();
+        for (index, regs) in reads {
+            for reg in regs {
+                let u = Use {
+                    reg,
+                    loc: Loc {
+                        label: label.clone(),
+                        index,
+                    },
+                };
+                let mut defs: Vec<Def> = facts[index]
+                    .iter()
+                    .filter(|d| d.reg == reg)
+                    .cloned()
+                    .collect();
+                defs.sort();
+                for d in defs.iter() {
+                    result.uses.entry(d.clone()).or_default().push(u.clone());
+                }
+                result.defs.insert(u, defs);
+            }
+        }
+    }
+    for uses in result.uses.values_mut() {
+        uses.sort();
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests;
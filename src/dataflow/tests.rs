@@ -0,0 +1,137 @@
+use super::*;
+
+/// A diamond whose branches define `r3` differently.
+const DIAMOND: &str = "\
+a:
+    mov r1 1
+    mov r2 2
+    jeq r1 0 c
+b:
+    mov r3 r2
+    ja d
+c:
+    mov r3 3
+d:
+    mov r0 r3
+    exit
+";
+
+fn regs<const N: usize>(regs: [u8; N]) -> HashSet<Reg> {
+    regs.into_iter().filter_map(Reg::new).collect()
+}
+
+fn at(reg: u8, l: &str, index: usize) -> Def {
+    Def {
+        reg: Reg::new(reg).unwrap(),
+        site: DefSite::At(Loc {
+            label: label(l),
+            index,
+        }),
+    }
+}
+
+#[test]
+fn live_registers() {
+    let cfg = Cfg::parse(DIAMOND);
+    let live = liveness(&cfg);
+    assert_eq!(live.entry(&label("a")), Some(&regs([])));
+    assert_eq!(live.entry(&label("b")), Some(&regs([2])));
+    assert_eq!(live.entry(&label("c")), Some(&regs([])));
+    assert_eq!(live.entry(&label("d")), Some(&regs([3])));
+    // The comparison reads `r1` after the body of `a`.
+    assert_eq!(live.exit(&label("a")), Some(&regs([1, 2])));
+    assert_eq!(
+        live.stmt_facts(&label("a")),
+        vec![regs([]), regs([1]), regs([1, 2])]
+    );
+    assert_eq!(live.stmt_facts(&label("d")), vec![regs([3]), regs([0])]);
+}
+
+#[test]
+fn live_around_loop() {
+    let cfg = Cfg::parse("a:\n    mov r2 0\nl:\n    add r2 r1\n    jlt r2 10 l\ne:\n    exit\n");
+    let live = liveness(&cfg);
+    // `r1` is read on every iteration, and `r0` is read by the exit without being written.
+    assert_eq!(live.entry(&label("l")), Some(&regs([0, 1, 2])));
+    assert_eq!(live.entry(&label("a")), Some(&regs([0, 1])));
+}
+
+#[test]
+fn reaching_definitions() {
+    let cfg = Cfg::parse(DIAMOND);
+    let reaching = reaching_defs(&cfg);
+    let r3: HashSet<Def> = reaching
+        .entry(&label("d"))
+        .unwrap()
+        .iter()
+        .filter(|d| d.reg == Reg::R3)
+        .cloned()
+        .collect();
+    assert_eq!(r3, HashSet::from([at(3, "b", 0), at(3, "c", 0)]));
+    let r0 = Def {
+        reg: Reg::R0,
+        site: DefSite::Entry,
+    };
+    assert!(reaching.entry(&label("d")).unwrap().contains(&r0));
+    assert!(!reaching.exit(&label("d")).unwrap().contains(&r0));
+}
+
+#[test]
+fn chains() {
+    let cfg = Cfg::parse(DIAMOND);
+    let chains = def_use(&cfg);
+    let read = |reg: u8, l: &str, index: usize| Use {
+        reg: Reg::new(reg).unwrap(),
+        loc: Loc {
+            label: label(l),
+            index,
+        },
+    };
+    assert_eq!(
+        chains.defs[&read(3, "d", 0)],
+        vec![at(3, "b", 0), at(3, "c", 0)]
+    );
+    // The exit reads `r0` past the end of the body.
+    assert_eq!(chains.defs[&read(0, "d", 1)], vec![at(0, "d", 0)]);
+    assert_eq!(chains.uses[&at(1, "a", 0)], vec![read(1, "a", 2)]);
+}
+
+/// Registers that have been found to be 0 by a comparison on some path.
+struct ComparedZero;
+
+impl Analysis for ComparedZero {
+    type Fact = HashSet<Reg>;
+    const DIRECTION: Direction = Direction::Forward;
+
+    fn bottom(&self, _cfg: &Cfg) -> Self::Fact {
+        HashSet::new()
+    }
+
+    fn boundary(&self, _cfg: &Cfg) -> Self::Fact {
+        HashSet::new()
+    }
+
+    fn transfer(&self, _label: &Label, _index: usize, stmt: &Stmt, fact: &mut Self::Fact) {
+        for r in writes(stmt) {
+            fact.remove(&r);
+        }
+    }
+
+    fn edge(&self, _label: &Label, next: &Continuation, target: &Label, fact: &mut Self::Fact) {
+        if let Continuation::Jcc(Cc::Eq, reg, RegImm::Imm(0), target_t, _) = next {
+            if target == target_t {
+                fact.insert(*reg);
+            }
+        }
+    }
+}
+
+#[test]
+fn branch_sensitive_edges() {
+    let cfg = Cfg::parse(DIAMOND);
+    let results = solve(&cfg, &ComparedZero);
+    assert_eq!(results.exit(&label("a")), Some(&regs([])));
+    assert_eq!(results.entry(&label("b")), Some(&regs([])));
+    assert_eq!(results.entry(&label("c")), Some(&regs([1])));
+    assert_eq!(results.entry(&label("d")), Some(&regs([1])));
+}
@@ -0,0 +1,200 @@
+//! Analysis of stack usage.
+//! Each function may use at most [STACK_SIZE] bytes below its frame pointer,
+//! and the frames of a chain of BPF-to-BPF calls share the same limit.
+//! Accesses through stack pointers with an unknown offset are left to the verification conditions.
+
+use std::{
+    collections::{HashMap, HashSet},
+    fmt::{self, Display, Formatter},
+};
+
+use crate::{cfg::*, helpers::helper_by_id, types::TypeInfo, vc::STACK_SIZE};
+
+/// The kernel rounds the frame of every function in a call chain up to this many bytes.
+const FRAME_ALIGN: i64 = 32;
+
+/// The deepest use of the stack by a function.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Usage {
+    /// Number of bytes below `r10` that are used.
+    pub depth: i64,
+    pub label: Label,
+    pub site: Site,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DepthErrKind {
+    /// An access that uses more stack than a single frame has.
+    Frame(i64),
+    /// A call whose chain of frames uses more stack than is available.
+    Combined { depth: i64, chain: Vec<String> },
+}
+
+/// A use of the stack that exceeds the limit.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DepthErr {
+    /// The function containing the offending instruction, or `None` for the main program.
+    pub function: Option<Label>,
+    pub label: Label,
+    pub site: Site,
+    pub kind: DepthErrKind,
+}
+
+impl Display for DepthErr {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match &self.kind {
+            DepthErrKind::Frame(depth) => f.write_fmt(format_args!(
+                "Stack depth of {depth} bytes exceeds {STACK_SIZE}"
+            ))?,
+            DepthErrKind::Combined { depth, chain } => f.write_fmt(format_args!(
+                "Combined stack depth of {depth} bytes via {} exceeds {STACK_SIZE}",
+                chain.join(" -> ")
+            ))?,
+        }
+        f.write_fmt(format_args!(" by {}", self.site.describe(&self.label)))
+    }
+}
+
+/// Find the deepest access to the stack of a function,
+/// counting both loads and stores and the stack pointers passed to helpers.
+pub fn frame_usage(cfg: &Cfg, types: &TypeInfo) -> Option<Usage> {
+    let mut labels: Vec<&Label> = types.reachable().collect();
+    labels.sort();
+    let mut result: Option<Usage> = None;
+    for label in labels {
+        let block = &cfg.blocks[label];
+        let stmt_types = types.stmt_types(label, block);
+        for (index, stmt) in block.body.iter().enumerate() {
+            let types = &stmt_types[index];
+            let offsets: Vec<Offset> = match stmt {
+                Stmt::Load(_, _, mem_ref) | Stmt::Store(_, mem_ref, _) => {
+                    types.stack_offset(mem_ref).into_iter().collect()
+                }
+                Stmt::Call(id) if !helper_by_id(*id).exits => {
+                    [Reg::R1, Reg::R2, Reg::R3, Reg::R4, Reg::R5]
+                        .into_iter()
+                        .filter_map(|r| types.stack_offset(&MemRef(r, 0)))
+                        .collect()
+                }
+                _ => vec![],
+            };
+            for offset in offsets {
+                if result.as_ref().is_none_or(|u| -offset > u.depth) {
+                    result = Some(Usage {
+                        depth: -offset,
+                        label: label.clone(),
+                        site: Site::Stmt(index, stmt.clone()),
+                    });
+                }
+            }
+        }
+    }
+    result.filter(|u| u.depth > 0)
+}
+
+/// Size of a frame within a call chain.
+fn aligned(usage: &Option<Usage>) -> i64 {
+    let depth = usage.as_ref().map_or(0, |u| u.depth).max(1);
+    (depth + FRAME_ALIGN - 1) / FRAME_ALIGN * FRAME_ALIGN
+}
+
+/// The deepest chain of calls starting from a function.
+#[derive(Debug, Clone)]
+struct Chain<'a> {
+    depth: i64,
+    /// The call at the start of the chain and its callee, if there is one.
+    call: Option<(&'a Label, usize, &'a Label)>,
+}
+
+struct CallGraph<'a> {
+    cfgs: HashMap<Option<&'a Label>, (&'a Cfg, i64)>,
+    chains: HashMap<Option<&'a Label>, Chain<'a>>,
+}
+
+impl<'a> CallGraph<'a> {
+    /// Compute the deepest chain from a function.
+    /// Recursive calls are ignored, as they are rejected anyway.
+    fn chain(&mut self, name: Option<&'a Label>, visiting: &mut HashSet<&'a Label>) -> i64 {
+        if let Some(chain) = self.chains.get(&name) {
+            return chain.depth;
+        }
+        let (cfg, frame) = self.cfgs[&name];
+        let mut labels: Vec<&Label> = cfg.blocks.keys().collect();
+        labels.sort();
+        let mut best = Chain {
+            depth: frame,
+            call: None,
+        };
+        for label in labels {
+            for (index, stmt) in cfg.blocks[label].body.iter().enumerate() {
+                let Stmt::CallLocal(callee) = stmt else {
+                    continue;
+                };
+                if !self.cfgs.contains_key(&Some(callee)) || !visiting.insert(callee) {
+                    continue;
+                }
+                let depth = frame + self.chain(Some(callee), visiting);
+                visiting.remove(callee);
+                if depth > best.depth {
+                    best = Chain {
+                        depth,
+                        call: Some((label, index, callee)),
+                    };
+                }
+            }
+        }
+        let depth = best.depth;
+        self.chains.insert(name, best);
+        depth
+    }
+}
+
+/// Find the uses of the stack that exceed the limits,
+/// both within single functions and along chains of calls from the main program.
+pub fn depth_errors(cfgs: &[(Cfg, TypeInfo)]) -> Vec<DepthErr> {
+    let mut result = Vec::new();
+    let mut graph = CallGraph {
+        cfgs: HashMap::new(),
+        chains: HashMap::new(),
+    };
+    for (cfg, types) in cfgs {
+        let usage = frame_usage(cfg, types);
+        if let Some(u) = usage.as_ref().filter(|u| u.depth > STACK_SIZE) {
+            result.push(DepthErr {
+                function: cfg.name.clone(),
+                label: u.label.clone(),
+                site: u.site.clone(),
+                kind: DepthErrKind::Frame(u.depth),
+            });
+        }
+        graph.cfgs.insert(cfg.name.as_ref(), (cfg, aligned(&usage)));
+    }
+    if !graph.cfgs.contains_key(&None) {
+        return result;
+    }
+
+    let depth = graph.chain(None, &mut HashSet::new());
+    let Some((label, index, _)) = graph.chains[&None].call else {
+        return result;
+    };
+    if depth > STACK_SIZE {
+        // Follow the deepest calls to list the whole chain.
+        let mut chain = vec!["main".to_owned()];
+        let mut current = None;
+        while let Some((_, _, callee)) = graph.chains.get(&current).and_then(|c| c.call) {
+            chain.push(callee.clone());
+            current = Some(callee);
+        }
+        let (cfg, _) = graph.cfgs[&None];
+        result.push(DepthErr {
+            function: None,
+            label: label.clone(),
+            site: Site::Stmt(index, cfg.blocks[label].body[index].clone()),
+            kind: DepthErrKind::Combined { depth, chain },
+        });
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests;
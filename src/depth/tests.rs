@@ -0,0 +1,90 @@
+use super::*;
+use crate::{formula::FormulaBuilder, init::entry_regs, prog::ProgType, types::RegTypes};
+
+fn infer(cfg: &Cfg) -> TypeInfo {
+    let prog = ProgType::Function;
+    let entry = RegTypes::entry(cfg, &entry_regs(cfg, prog), prog);
+    TypeInfo::infer(cfg, entry, prog)
+}
+
+fn usage(src: &str) -> Option<(i64, Label, usize)> {
+    let cfg = Cfg::parse(src);
+    let types = infer(&cfg);
+    frame_usage(&cfg, &types).map(|u| {
+        let Site::Stmt(index, _) = u.site else {
+            panic!("not a statement: {:?}", u.site)
+        };
+        (u.depth, u.label, index)
+    })
+}
+
+fn errors(src: &str) -> Vec<DepthErr> {
+    let (_, ast) = crate::parse::module(src).unwrap();
+    let Ok(cfgs) = Cfg::create(ast, &mut FormulaBuilder::new()) else {
+        panic!("conversion failed")
+    };
+    let checked: Vec<(Cfg, TypeInfo)> = cfgs
+        .into_iter()
+        .map(|cfg| {
+            let types = infer(&cfg);
+            (cfg, types)
+        })
+        .collect();
+    depth_errors(&checked)
+}
+
+#[test]
+fn deepest_access() {
+    let src = "stxdw [r10 - 8] r1\nldxdw r0 [r10 - 24]\nstxw [r10 - 16] r1\nexit\n";
+    assert_eq!(usage(src), Some((24, "@0".to_owned(), 1)));
+    assert_eq!(usage("mov r0 0\nexit\n"), None);
+}
+
+#[test]
+fn derived_pointers() {
+    // Pointers derived from r10 count, including the ones passed to helpers.
+    let src = "mov r2 r10\nadd r2 -40\nstxdw [r2 + 8] r1\nexit\n";
+    assert_eq!(usage(src), Some((32, "@0".to_owned(), 2)));
+    let src = ";# map 1 value_size 8\nldmapfd r1 1\nmov r2 r10\nadd r2 -64\ncall 1\nexit\n";
+    assert_eq!(usage(src), Some((64, "@0".to_owned(), 3)));
+}
+
+#[test]
+fn frame_limit() {
+    let errors = errors("stdw [r10 - 520] 0\nmov r0 0\nexit\n");
+    assert_eq!(errors.len(), 1);
+    assert_eq!(errors[0].kind, DepthErrKind::Frame(520));
+    assert_eq!(errors[0].function, None);
+}
+
+#[test]
+fn call_chains() {
+    let src = "\
+stdw [r10 - 200] 0
+call f
+exit
+
+;# function f
+stdw [r10 - 300] 0
+mov r0 0
+exit
+";
+    // Frames are rounded up to 224 and 320 bytes.
+    let errors = errors(src);
+    assert_eq!(errors.len(), 1);
+    assert_eq!(
+        errors[0].kind,
+        DepthErrKind::Combined {
+            depth: 544,
+            chain: vec!["main".to_owned(), "f".to_owned()],
+        }
+    );
+    assert_eq!(
+        errors[0].site,
+        Site::Stmt(1, Stmt::CallLocal("f".to_owned()))
+    );
+
+    // Each frame fits on its own, and so does the chain when it is shallower.
+    let src = src.replace("[r10 - 300]", "[r10 - 200]");
+    assert!(self::errors(&src).is_empty());
+}
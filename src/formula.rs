@@ -1,6 +1,6 @@
 //! A stateful builder for formulas.
 
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 
 use crate::ast::*;
 
@@ -30,6 +30,11 @@ impl FormulaBuilder {
         Formula::Bin(FBinOp::And, Box::new((a, b)))
     }
 
+    /// Conjoin formulas, starting from `true`.
+    pub fn and_all(&self, fs: impl IntoIterator<Item = Formula>) -> Formula {
+        fs.into_iter().fold(self.top(), |a, b| self.and(a, b))
+    }
+
     pub fn asym_and(&self, a: Formula, b: Formula) -> Formula {
         Formula::Bin(FBinOp::AndAsym, Box::new((a, b)))
     }
@@ -105,7 +110,8 @@ impl FormulaBuilder {
                     None
                 }
             }
-            Formula::Val(_) => None,
+            Formula::Held(reg) if reg == prev => Some(Formula::Held(new.clone())),
+            Formula::Val(_) | Formula::Held(_) => None,
         }
     }
 
@@ -138,6 +144,55 @@ impl FormulaBuilder {
         }
     }
 
+    /// Collect the variables that occur free in a formula.
+    pub fn free_vars(&self, f: &Formula) -> HashSet<Ident> {
+        let mut vars = HashSet::new();
+        self.collect_free_vars(f, &mut vars);
+        vars
+    }
+
+    fn collect_free_vars(&self, f: &Formula, vars: &mut HashSet<Ident>) {
+        match f {
+            Formula::Val(_) => (),
+            Formula::Not(inner) => self.collect_free_vars(inner, vars),
+            Formula::Bin(_, fs) => {
+                self.collect_free_vars(&fs.0, vars);
+                self.collect_free_vars(&fs.1, vars);
+            }
+            Formula::Quant(_, qvar, inner) => {
+                let mut inner_vars = HashSet::new();
+                self.collect_free_vars(inner, &mut inner_vars);
+                inner_vars.remove(qvar);
+                vars.extend(inner_vars);
+            }
+            Formula::Rel(_, e1, e2) => {
+                self.collect_expr_vars(e1, vars);
+                self.collect_expr_vars(e2, vars);
+            }
+            Formula::IsBuffer(ptr, sz) => {
+                vars.insert(ptr.clone());
+                self.collect_expr_vars(sz, vars);
+            }
+            Formula::Held(reg) => {
+                vars.insert(reg.clone());
+            }
+        }
+    }
+
+    fn collect_expr_vars(&self, e: &Expr, vars: &mut HashSet<Ident>) {
+        match e {
+            Expr::Val(_) => (),
+            Expr::Var(x) => {
+                vars.insert(x.clone());
+            }
+            Expr::Unary(_, inner) => self.collect_expr_vars(inner, vars),
+            Expr::Binary(_, es) => {
+                self.collect_expr_vars(&es.0, vars);
+                self.collect_expr_vars(&es.1, vars);
+            }
+        }
+    }
+
     pub fn rel(&self, cc: Cc, a: Expr, b: Expr) -> Formula {
         Formula::Rel(cc, a, b)
     }
@@ -165,6 +220,11 @@ impl FormulaBuilder {
         (Expr::Var(id.clone()), id)
     }
 
+    /// Get the register that a variable represents, if any.
+    pub fn reg_of(&self, ident: &Ident) -> Option<Reg> {
+        Reg::new(ident.strip_prefix('r')?.parse().ok()?)
+    }
+
     pub fn val(&self, i: Imm) -> Expr {
         Expr::Val(i)
     }
@@ -0,0 +1,203 @@
+//! Models of the helper functions that programs can call.
+
+use crate::ast::{Imm, Reg};
+
+/// What a helper returns in `r0`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Ret {
+    Scalar,
+    /// A pointer to a value of the map passed in `r1`, or null if there is none.
+    MapValue,
+    /// A pointer to memory of the size passed in `r2`, or null if it can't be allocated.
+    MemOrNull,
+    /// A pointer to a socket, or null if none matches.
+    SockOrNull,
+}
+
+/// Size of `struct bpf_sock`, which socket pointers point to.
+pub const SOCK_SIZE: i64 = 80;
+
+/// An argument that a helper passes to a callback.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CbArg {
+    Scalar,
+    /// The context passed in `r3` of the call.
+    Ctx,
+    /// A pointer to a value of the map that the call refers to.
+    MapValue,
+}
+
+/// Contract that a helper imposes on the callback passed in `r2`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Callback {
+    /// Arguments passed in `r1` onwards.
+    pub args: &'static [CbArg],
+    /// Largest return value that the helper accepts.
+    pub max_ret: Imm,
+}
+
+/// `long (*)(u32 index, void *ctx)`, returning 1 to stop.
+const LOOP_CALLBACK: Callback = Callback {
+    args: &[CbArg::Scalar, CbArg::Ctx],
+    max_ret: 1,
+};
+
+/// `long (*)(map, key, value, void *ctx)`, returning 1 to stop.
+const FOR_EACH_CALLBACK: Callback = Callback {
+    args: &[CbArg::Scalar, CbArg::Scalar, CbArg::MapValue, CbArg::Ctx],
+    max_ret: 1,
+};
+
+/// `int (*)(map, key, value)`, which must return 0.
+const TIMER_CALLBACK: Callback = Callback {
+    args: &[CbArg::Scalar, CbArg::Scalar, CbArg::MapValue],
+    max_ret: 0,
+};
+
+/// How a helper affects the resources that the program holds.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RefEffect {
+    None,
+    /// Returns a reference in `r0` that must be released.
+    Acquire,
+    /// Releases the reference passed in `r1`.
+    Release,
+    /// Takes the spin lock of the map value passed in `r1`.
+    Lock,
+    /// Releases the spin lock of the map value passed in `r1`.
+    Unlock,
+}
+
+/// Memory that a helper fills in, given by the registers holding its address and size.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Written {
+    pub ptr: Reg,
+    pub size: Reg,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Helper {
+    pub id: Imm,
+    pub name: &'static str,
+    /// Number of arguments, which it takes in `r1` onwards.
+    pub args: u8,
+    pub ret: Ret,
+    /// Whether the helper may move or resize the packet data,
+    /// invalidating all pointers into it.
+    pub changes_pkt: bool,
+    pub refs: RefEffect,
+    /// Whether the helper ends the program if it succeeds, as tail calls do.
+    pub exits: bool,
+    pub callback: Option<Callback>,
+    /// Memory that the helper writes, which may be uninitialized beforehand.
+    pub writes: Option<Written>,
+}
+
+const fn helper(id: Imm, name: &'static str, args: u8, ret: Ret) -> Helper {
+    Helper {
+        id,
+        name,
+        args,
+        ret,
+        changes_pkt: false,
+        refs: RefEffect::None,
+        exits: false,
+        callback: None,
+        writes: None,
+    }
+}
+
+const fn ref_helper(id: Imm, name: &'static str, args: u8, ret: Ret, refs: RefEffect) -> Helper {
+    Helper {
+        refs,
+        ..helper(id, name, args, ret)
+    }
+}
+
+const fn callback_helper(id: Imm, name: &'static str, args: u8, callback: Callback) -> Helper {
+    Helper {
+        callback: Some(callback),
+        ..helper(id, name, args, Ret::Scalar)
+    }
+}
+
+const fn exit_helper(id: Imm, name: &'static str, args: u8) -> Helper {
+    Helper {
+        exits: true,
+        ..helper(id, name, args, Ret::Scalar)
+    }
+}
+
+const fn write_helper(id: Imm, name: &'static str, args: u8, ptr: Reg, size: Reg) -> Helper {
+    Helper {
+        writes: Some(Written { ptr, size }),
+        ..helper(id, name, args, Ret::Scalar)
+    }
+}
+
+const fn pkt_helper(id: Imm, name: &'static str, args: u8) -> Helper {
+    Helper {
+        changes_pkt: true,
+        ..helper(id, name, args, Ret::Scalar)
+    }
+}
+
+#[rustfmt::skip]
+const HELPERS: &[Helper] = &[
+    helper(1,  "map_lookup_elem",      2, Ret::MapValue),
+    helper(2,  "map_update_elem",      4, Ret::Scalar),
+    helper(3,  "map_delete_elem",      2, Ret::Scalar),
+    helper(5,  "ktime_get_ns",         0, Ret::Scalar),
+    helper(6,  "trace_printk",         5, Ret::Scalar),
+    helper(7,  "get_prandom_u32",      0, Ret::Scalar),
+    helper(8,  "get_smp_processor_id", 0, Ret::Scalar),
+    helper(14, "get_current_pid_tgid", 0, Ret::Scalar),
+    helper(15, "get_current_uid_gid",  0, Ret::Scalar),
+    exit_helper(12, "tail_call", 3),
+    write_helper(4,   "probe_read",            3, Reg::R1, Reg::R2),
+    write_helper(16,  "get_current_comm",      2, Reg::R1, Reg::R2),
+    write_helper(26,  "skb_load_bytes",        4, Reg::R3, Reg::R4),
+    write_helper(45,  "probe_read_str",        3, Reg::R1, Reg::R2),
+    write_helper(112, "probe_read_user",       3, Reg::R1, Reg::R2),
+    write_helper(113, "probe_read_kernel",     3, Reg::R1, Reg::R2),
+    write_helper(114, "probe_read_user_str",   3, Reg::R1, Reg::R2),
+    write_helper(115, "probe_read_kernel_str", 3, Reg::R1, Reg::R2),
+    pkt_helper(9,  "skb_store_bytes",  5),
+    pkt_helper(10, "l3_csum_replace",  5),
+    pkt_helper(11, "l4_csum_replace",  5),
+    pkt_helper(18, "skb_vlan_push",    3),
+    pkt_helper(19, "skb_vlan_pop",     1),
+    pkt_helper(31, "skb_change_proto", 3),
+    pkt_helper(38, "skb_change_tail",  3),
+    pkt_helper(39, "skb_pull_data",    2),
+    pkt_helper(43, "skb_change_head",  3),
+    pkt_helper(44, "xdp_adjust_head",  2),
+    pkt_helper(50, "skb_adjust_room",  4),
+    pkt_helper(54, "xdp_adjust_meta",  2),
+    pkt_helper(65, "xdp_adjust_tail",  2),
+    ref_helper(84,  "sk_lookup_tcp",   5, Ret::SockOrNull, RefEffect::Acquire),
+    ref_helper(85,  "sk_lookup_udp",   5, Ret::SockOrNull, RefEffect::Acquire),
+    ref_helper(86,  "sk_release",      1, Ret::Scalar,     RefEffect::Release),
+    ref_helper(93,  "spin_lock",       1, Ret::Scalar,     RefEffect::Lock),
+    ref_helper(94,  "spin_unlock",     1, Ret::Scalar,     RefEffect::Unlock),
+    ref_helper(99,  "skc_lookup_tcp",  5, Ret::SockOrNull, RefEffect::Acquire),
+    ref_helper(131, "ringbuf_reserve", 3, Ret::MemOrNull,  RefEffect::Acquire),
+    ref_helper(132, "ringbuf_submit",  2, Ret::Scalar,     RefEffect::Release),
+    ref_helper(133, "ringbuf_discard", 2, Ret::Scalar,     RefEffect::Release),
+    callback_helper(164, "for_each_map_elem",  4, FOR_EACH_CALLBACK),
+    callback_helper(170, "timer_set_callback", 2, TIMER_CALLBACK),
+    callback_helper(181, "loop",               4, LOOP_CALLBACK),
+];
+
+/// Look up the model of a helper by its ID.
+/// Unknown helpers are treated as taking no arguments and returning a scalar.
+pub fn helper_by_id(id: Imm) -> Helper {
+    HELPERS
+        .iter()
+        .find(|h| h.id == id)
+        .copied()
+        .unwrap_or(helper(id, "unknown", 0, Ret::Scalar))
+}
+
+#[cfg(test)]
+mod tests;
@@ -0,0 +1,38 @@
+use super::*;
+
+#[test]
+fn lookup() {
+    let lookup = helper_by_id(1);
+    assert_eq!(
+        (lookup.name, lookup.args, lookup.ret),
+        ("map_lookup_elem", 2, Ret::MapValue)
+    );
+    let sock = helper_by_id(84);
+    assert_eq!((sock.ret, sock.refs), (Ret::SockOrNull, RefEffect::Acquire));
+    let unknown = helper_by_id(1000);
+    assert_eq!(
+        (unknown.name, unknown.args, unknown.ret),
+        ("unknown", 0, Ret::Scalar)
+    );
+}
+
+#[test]
+fn unique_ids() {
+    for (i, a) in HELPERS.iter().enumerate() {
+        assert!(HELPERS[i + 1..].iter().all(|b| a.id != b.id), "{}", a.name);
+    }
+}
+
+#[test]
+fn arguments() {
+    for h in HELPERS.iter() {
+        assert!(h.args <= 5, "{}", h.name);
+        // Buffers that a helper writes and callbacks in r2 are among its arguments.
+        if let Some(Written { ptr, size }) = h.writes {
+            assert!(ptr.get() <= h.args && size.get() <= h.args, "{}", h.name);
+        }
+        if h.callback.is_some() {
+            assert!(h.args >= 2, "{}", h.name);
+        }
+    }
+}
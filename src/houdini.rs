@@ -0,0 +1,307 @@
+//! Houdini-style inference of loop invariants.
+//! Candidates are generated at each cut point from templates:
+//! comparisons between registers, comparisons of scalars with the constants of the program,
+//! and the conjuncts of the program's requirement, with `is_buffer` facts moved to other registers.
+//! Those that the prover can't show to hold on entry and be preserved,
+//! assuming all remaining candidates, are dropped until the rest are mutually inductive.
+//! Each round generates one set of conditions requiring all remaining candidates,
+//! and splits the goals that establish or preserve them into one for each candidate.
+
+use std::collections::{HashMap, HashSet};
+
+use crate::{
+    ast::FBinOp,
+    bounds::Facts,
+    cfg::*,
+    dataflow::{liveness, reaching_defs, DefSite},
+    formula::FormulaBuilder,
+    infer::conjuncts,
+    interval::constants,
+    loops::cut_points,
+    prover::{Prover, ProverErr},
+    types::{RegType, TypeInfo},
+    vc::{frame, vc},
+};
+
+/// Candidate invariants at each cut point that aren't already part of its `req`.
+fn candidates(cfg: &Cfg, types: &TypeInfo, f: &FormulaBuilder) -> HashMap<Label, Vec<Formula>> {
+    let live = liveness(cfg);
+    let reaching = reaching_defs(cfg);
+    let frame = frame(f);
+    let mut consts: Vec<Imm> = constants(cfg).into_iter().filter(|c| *c >= 0).collect();
+    consts.sort();
+    consts.dedup();
+
+    let mut result = HashMap::new();
+    for cut in cut_points(cfg) {
+        let block = &cfg.blocks[&cut.label];
+        let Some(regs_types) = types.stmt_types(&cut.label, block).into_iter().next() else {
+            continue;
+        };
+        // Registers that are still read, or still hold their initial value.
+        let regs: Vec<Reg> = (0..10)
+            .filter_map(Reg::new)
+            .filter(|r| regs_types.get(*r) != RegType::NotInit)
+            .filter(|r| {
+                live.entry(&cut.label).is_some_and(|l| l.contains(r))
+                    || reaching.entry(&cut.label).is_some_and(|defs| {
+                        defs.iter()
+                            .filter(|d| d.reg == *r)
+                            .all(|d| d.site == DefSite::Entry)
+                    })
+            })
+            .collect();
+
+        let mut cands = Vec::new();
+        for (i, a) in regs.iter().enumerate() {
+            for b in regs.iter() {
+                if a != b {
+                    cands.push(f.rel(Cc::Lt, f.reg(*a).0, f.reg(*b).0));
+                    cands.push(f.rel(Cc::Le, f.reg(*a).0, f.reg(*b).0));
+                }
+            }
+            for b in regs[i + 1..].iter() {
+                cands.push(f.eq(f.reg(*a).0, f.reg(*b).0));
+            }
+            if regs_types.get(*a) == RegType::Scalar {
+                for c in consts.iter() {
+                    cands.push(f.eq(f.reg(*a).0, f.val(*c)));
+                    cands.push(f.rel(Cc::Lt, f.reg(*a).0, f.val(*c)));
+                    cands.push(f.rel(Cc::Le, f.reg(*a).0, f.val(*c)));
+                    if *c > 0 {
+                        cands.push(f.rel(Cc::Ge, f.reg(*a).0, f.val(*c)));
+                    }
+                }
+            }
+        }
+        for c in conjuncts(&cfg.requires) {
+            match c {
+                // Buffers may have been passed on to other registers.
+                Formula::IsBuffer(ptr, size) if f.reg_of(ptr).is_some() => {
+                    let sizes: Vec<Expr> = match size {
+                        Expr::Var(x) if f.reg_of(x).is_some() => {
+                            regs.iter().map(|r| f.reg(*r).0).collect()
+                        }
+                        size => vec![size.clone()],
+                    };
+                    for p in regs.iter() {
+                        for s in sizes.iter() {
+                            cands.push(f.is_buffer(f.reg(*p).1, s.clone()));
+                        }
+                    }
+                }
+                Formula::Held(_) => (),
+                c => cands.push(c.clone()),
+            }
+        }
+
+        let mut existing: Vec<Formula> = match &block.require {
+            Some(require) => conjuncts(require).into_iter().cloned().collect(),
+            None => vec![],
+        };
+        existing.extend(conjuncts(&frame).into_iter().cloned());
+        cands.retain(|c| match existing.contains(c) {
+            true => false,
+            false => {
+                existing.push(c.clone());
+                true
+            }
+        });
+        result.insert(cut.label, cands);
+    }
+    result
+}
+
+/// A copy of a CFG whose verification conditions hold if the given requirements of its cut points
+/// are inductive. They are its only requirements, and nothing else is checked:
+/// contracts, assertions, accesses and divisions are taken to hold.
+fn checked(cfg: &Cfg, requires: &HashMap<Label, Formula>, f: &FormulaBuilder) -> Cfg {
+    let mut cfg = cfg.clone();
+    cfg.ensures = f.top();
+    cfg.tail_requires = f.top();
+    for contract in cfg.contracts.values_mut() {
+        contract.requires = f.top();
+    }
+    for (l, block) in cfg.blocks.iter_mut() {
+        block.require = requires.get(l).cloned();
+        block.variant = None;
+        block.bound = None;
+        for stmt in block.body.iter_mut() {
+            if let Stmt::Assert(_) = stmt {
+                *stmt = Stmt::Assert(f.top());
+            }
+        }
+    }
+    cfg
+}
+
+/// The cut points whose requirements a goal must establish or preserve:
+/// those reached from the start for the entry goal, or from a cut point for its own goal,
+/// without passing through another one.
+fn targets<'a>(cfg: &'a Cfg, cuts: &HashSet<&'a Label>, goal: &str) -> HashSet<&'a Label> {
+    let mut stack = match cfg.blocks.get_key_value(goal) {
+        Some((label, _)) if cuts.contains(label) => cfg.successors(label),
+        _ => vec![&cfg.start],
+    };
+    let mut visited = HashSet::new();
+    let mut targets = HashSet::new();
+    while let Some(label) = stack.pop() {
+        if !visited.insert(label) {
+            continue;
+        }
+        match cuts.contains(label) {
+            true => {
+                targets.insert(label);
+            }
+            false => stack.extend(cfg.successors(label)),
+        }
+    }
+    targets
+}
+
+/// A goal with the marked candidates replaced by `true`, except for one of them,
+/// leaving out the conjuncts that become `true`.
+/// Each candidate is marked by a conjunction with `m = 0` for a fresh variable `m`,
+/// which carries it through the renaming of registers in the verification conditions.
+fn only(goal: &Formula, markers: &HashSet<Ident>, keep: &Ident) -> Formula {
+    match goal {
+        Formula::Bin(FBinOp::And, fs) => match &fs.0 {
+            Formula::Rel(Cc::Eq, Expr::Var(m), _) if m == keep => fs.1.clone(),
+            Formula::Rel(Cc::Eq, Expr::Var(m), _) if markers.contains(m) => Formula::Val(true),
+            _ => match (only(&fs.0, markers, keep), only(&fs.1, markers, keep)) {
+                (Formula::Val(true), b) => b,
+                (a, Formula::Val(true)) => a,
+                (a, b) => Formula::Bin(FBinOp::And, Box::new((a, b))),
+            },
+        },
+        Formula::Bin(op, fs) => Formula::Bin(
+            *op,
+            Box::new((only(&fs.0, markers, keep), only(&fs.1, markers, keep))),
+        ),
+        Formula::Not(inner) => Formula::Not(Box::new(only(inner, markers, keep))),
+        Formula::Quant(q, v, inner) => {
+            Formula::Quant(*q, v.clone(), Box::new(only(inner, markers, keep)))
+        }
+        goal => goal.clone(),
+    }
+}
+
+/// Add the candidate invariants that the prover shows to be inductive to the `req` of their cut points.
+/// The assumptions of `known` hold at the start of their blocks in every round.
+pub fn houdini(
+    cfg: &mut Cfg,
+    types: &TypeInfo,
+    known: &Facts,
+    f: &mut FormulaBuilder,
+    prover: &impl Prover,
+) -> Result<(), ProverErr> {
+    let mut cands = candidates(cfg, types, f);
+    let safe: HashSet<(Label, usize)> = cfg
+        .blocks
+        .iter()
+        .flat_map(|(l, b)| b.body.iter().enumerate().map(move |(i, s)| (l, i, s)))
+        .filter(|(_, _, s)| {
+            matches!(
+                s,
+                Stmt::Load(_, _, _)
+                    | Stmt::Store(_, _, _)
+                    | Stmt::Binary(_, BinAlu::Div | BinAlu::Mod, _, _)
+            )
+        })
+        .map(|(l, i, _)| (l.clone(), i))
+        .collect();
+
+    loop {
+        // Every cut point may assume its requirement and all of its remaining candidates.
+        let mut assumptions = known.assumptions.clone();
+        for (label, cs) in cands.iter() {
+            let require = cfg.blocks[label].require.iter().cloned();
+            let assumed = require.chain(known.assumptions.get(label).cloned());
+            if let Some(a) = assumed.chain(cs.iter().cloned()).reduce(|a, b| f.and(a, b)) {
+                assumptions.insert(label.clone(), a);
+            }
+        }
+        let facts = Facts {
+            assumptions,
+            safe: safe.clone(),
+        };
+
+        // One set of conditions requires all remaining candidates, each under a marker.
+        let mut markers: HashMap<Ident, (Label, usize)> = HashMap::new();
+        let mut requires = HashMap::new();
+        for (label, cs) in cands.iter() {
+            let mut marked = Vec::new();
+            for (i, c) in cs.iter().enumerate() {
+                let (m, m_id) = f.var(String::from("cand"));
+                markers.insert(m_id, (label.clone(), i));
+                // Built directly, as `only` relies on the marker being the left conjunct.
+                let marker = f.eq(m, f.val(0));
+                marked.push(Formula::Bin(FBinOp::And, Box::new((marker, c.clone()))));
+            }
+            if let Some(require) = marked.into_iter().reduce(|a, b| f.and(a, b)) {
+                requires.insert(label.clone(), require);
+            }
+        }
+        let marker_ids: HashSet<Ident> = markers.keys().cloned().collect();
+        let mut by_cut: HashMap<&Label, Vec<&Ident>> = HashMap::new();
+        for (m, (label, _)) in markers.iter() {
+            by_cut.entry(label).or_default().push(m);
+        }
+        let cuts: HashSet<&Label> = cands.keys().collect();
+
+        // Goals that establish or preserve candidates are split into one for each of them,
+        // which it is dropped unless all are valid.
+        let mut goals = Vec::new();
+        let mut owners = Vec::new();
+        for (name, goal) in vc(checked(cfg, &requires, f), types, &facts, f) {
+            for target in targets(cfg, &cuts, &name) {
+                for m in by_cut.get(target).into_iter().flatten() {
+                    let split = only(&goal, &marker_ids, m);
+                    assert!(
+                        f.free_vars(&split).is_disjoint(&marker_ids),
+                        "candidate marker left in goal {name}"
+                    );
+                    goals.push((format!("c{}_{name}", goals.len()), split));
+                    owners.push(markers[*m].clone());
+                }
+            }
+        }
+        if goals.is_empty() {
+            break;
+        }
+        let names: Vec<String> = goals.iter().map(|(name, _)| name.clone()).collect();
+        let valid = prover.prove(goals)?;
+
+        let mut failed: HashMap<Label, HashSet<usize>> = HashMap::new();
+        for (name, (label, i)) in names.iter().zip(owners) {
+            if !valid.contains(name) {
+                failed.entry(label).or_default().insert(i);
+            }
+        }
+        if failed.is_empty() {
+            break;
+        }
+        for (label, failed) in failed {
+            let mut i = 0;
+            cands.get_mut(&label).unwrap().retain(|_| {
+                i += 1;
+                !failed.contains(&(i - 1))
+            });
+        }
+    }
+
+    for (label, cs) in cands {
+        let block = cfg.blocks.get_mut(&label).unwrap();
+        let Some(invariant) = cs.into_iter().reduce(|a, b| f.and(a, b)) else {
+            continue;
+        };
+        block.require = Some(match block.require.take() {
+            Some(require) => f.and(require, invariant),
+            None => invariant,
+        });
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests;
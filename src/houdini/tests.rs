@@ -0,0 +1,197 @@
+use std::cell::Cell;
+
+use super::*;
+use crate::{ast::QType, prog::ProgType, types::RegTypes};
+
+/// Loops nested in each other.
+const NESTED: &str = "\
+a:
+    mov r1 0
+outer:
+    mov r2 0
+inner:
+    add r2 1
+    jlt r2 10 inner
+latch:
+    add r1 1
+    jlt r1 10 outer
+end:
+    exit
+";
+
+#[test]
+fn goal_targets() {
+    let cfg = Cfg::parse(NESTED);
+    let (outer, inner) = (String::from("outer"), String::from("inner"));
+    let cuts = HashSet::from([&outer, &inner]);
+    assert_eq!(targets(&cfg, &cuts, "entry"), HashSet::from([&outer]));
+    assert_eq!(targets(&cfg, &cuts, "outer"), HashSet::from([&inner]));
+    assert_eq!(
+        targets(&cfg, &cuts, "inner"),
+        HashSet::from([&outer, &inner])
+    );
+}
+
+#[test]
+fn split_goal() {
+    let mut f = FormulaBuilder::new();
+    let (m0, m0_id) = f.var(String::from("cand"));
+    let (m1, m1_id) = f.var(String::from("cand"));
+    let markers = HashSet::from([m0_id.clone(), m1_id.clone()]);
+    let (v, v_id) = f.var(String::from("v"));
+    let r1 = f.reg(Reg::R1).0;
+
+    // The candidates have been renamed along with the registers they mention.
+    let c0 = f.rel(Cc::Lt, v.clone(), f.val(10));
+    let c1 = f.rel(Cc::Le, r1.clone(), v.clone());
+    let post = f.and(
+        f.and(f.eq(m0, f.val(0)), c0.clone()),
+        f.and(f.eq(m1, f.val(0)), c1.clone()),
+    );
+    let assigned = f.eq(v.clone(), f.binop(BinAlu::Add, r1, f.val(1)));
+    let goal = f.forall(v_id.clone(), f.implies(assigned.clone(), post));
+
+    let only_c0 = f.forall(v_id.clone(), f.implies(assigned.clone(), c0));
+    assert_eq!(only(&goal, &markers, &m0_id), only_c0);
+    let only_c1 = f.forall(v_id, f.implies(assigned, c1));
+    assert_eq!(only(&goal, &markers, &m1_id), only_c1);
+}
+
+/// A prover that tries every assignment of values from [DOMAIN] to the variables of a goal,
+/// which is enough for the small loops it is run on.
+#[derive(Default)]
+struct Enumerate {
+    rounds: Cell<usize>,
+}
+
+/// The values of the loop counters below and their neighbours, along with a frame pointer.
+const DOMAIN: [Imm; 14] = [0, 1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 512];
+
+impl Enumerate {
+    fn expr(e: &Expr, env: &HashMap<Ident, Imm>) -> Imm {
+        match e {
+            Expr::Val(i) => *i,
+            Expr::Var(x) => env[x],
+            Expr::Unary(UnAlu::Neg, a) => Self::expr(a, env).wrapping_neg(),
+            Expr::Binary(op, es) => {
+                let (a, b) = (Self::expr(&es.0, env), Self::expr(&es.1, env));
+                match op {
+                    BinAlu::Mov => b,
+                    BinAlu::Add => a.wrapping_add(b),
+                    BinAlu::Sub => a.wrapping_sub(b),
+                    BinAlu::Mul => a.wrapping_mul(b),
+                    BinAlu::Mod => (a as u64).checked_rem(b as u64).unwrap_or(a as u64) as Imm,
+                    op => panic!("unsupported operation {op:?}"),
+                }
+            }
+            e => panic!("unsupported expression {e:?}"),
+        }
+    }
+
+    fn holds(goal: &Formula, env: &mut HashMap<Ident, Imm>) -> bool {
+        match goal {
+            Formula::Val(b) => *b,
+            Formula::Not(g) => !Self::holds(g, env),
+            Formula::Bin(op, gs) => {
+                let a = Self::holds(&gs.0, env);
+                match op {
+                    FBinOp::And | FBinOp::AndAsym => a && Self::holds(&gs.1, env),
+                    FBinOp::Or => a || Self::holds(&gs.1, env),
+                    FBinOp::Implies => !a || Self::holds(&gs.1, env),
+                    FBinOp::Iff => a == Self::holds(&gs.1, env),
+                }
+            }
+            Formula::Quant(q, x, g) => {
+                let prev = env.get(x).copied();
+                let mut values = DOMAIN.iter().map(|v| {
+                    env.insert(x.clone(), *v);
+                    Self::holds(g, env)
+                });
+                let result = match q {
+                    QType::Forall => values.all(|b| b),
+                    QType::Exists => values.any(|b| b),
+                };
+                match prev {
+                    Some(v) => env.insert(x.clone(), v),
+                    None => env.remove(x),
+                };
+                result
+            }
+            Formula::Rel(cc, a, b) => {
+                let (a, b) = (Self::expr(a, env), Self::expr(b, env));
+                let (ua, ub) = (a as u64, b as u64);
+                match cc {
+                    Cc::Eq => a == b,
+                    Cc::Ne => a != b,
+                    Cc::Gt => ua > ub,
+                    Cc::Ge => ua >= ub,
+                    Cc::Lt => ua < ub,
+                    Cc::Le => ua <= ub,
+                    Cc::Set => a & b != 0,
+                    Cc::Sgt => a > b,
+                    Cc::Sge => a >= b,
+                    Cc::Slt => a < b,
+                    Cc::Sle => a <= b,
+                }
+            }
+            goal => panic!("unsupported formula {goal:?}"),
+        }
+    }
+
+    fn valid(goal: &Formula) -> bool {
+        let vars: Vec<Ident> = FormulaBuilder::new().free_vars(goal).into_iter().collect();
+        let mut env = HashMap::new();
+        let mut assignment = vec![0; vars.len()];
+        loop {
+            for (x, i) in vars.iter().zip(assignment.iter()) {
+                env.insert(x.clone(), DOMAIN[*i]);
+            }
+            if !Self::holds(goal, &mut env) {
+                return false;
+            }
+            // Move on to the next assignment, counting in base `DOMAIN.len()`.
+            let Some(i) = assignment.iter().position(|i| i + 1 < DOMAIN.len()) else {
+                return true;
+            };
+            assignment[i] += 1;
+            assignment[..i].fill(0);
+        }
+    }
+}
+
+impl Prover for Enumerate {
+    fn prove(&self, goals: Vec<(String, Formula)>) -> Result<HashSet<String>, ProverErr> {
+        self.rounds.set(self.rounds.get() + 1);
+        Ok(goals
+            .into_iter()
+            .filter(|(_, goal)| Self::valid(goal))
+            .map(|(name, _)| name)
+            .collect())
+    }
+}
+
+#[test]
+fn inductive_candidates() {
+    // Counting up from 0, so that the counter is at most 9 at the header.
+    let src = "mov r1 0\nloop:\nadd r1 1\njlt r1 10 loop\nmov r0 r1\nexit\n";
+    let mut cfg = Cfg::parse(src);
+    let prog = ProgType::Function;
+    let init = HashSet::from([Reg::R1, Reg::R10]);
+    let types = TypeInfo::infer(&cfg, RegTypes::entry(&cfg, &init, prog), prog);
+    let mut f = FormulaBuilder::new();
+    let prover = Enumerate::default();
+    houdini(&mut cfg, &types, &Facts::default(), &mut f, &prover).unwrap();
+
+    // Only the bounds that hold on entry and after every iteration are kept,
+    // which takes several rounds as candidates that others relied on are dropped.
+    let require = cfg.blocks["loop"].require.clone().unwrap();
+    let r1 = f.reg(Reg::R1).0;
+    assert_eq!(
+        conjuncts(&require),
+        vec![
+            &f.rel(Cc::Lt, r1.clone(), f.val(10)),
+            &f.rel(Cc::Le, r1, f.val(10)),
+        ]
+    );
+    assert!(prover.rounds.get() > 1);
+}
@@ -0,0 +1,93 @@
+//! Inference of loop invariants.
+//! The zones at each cut point are added to its `req` annotation,
+//! along with the requirements of the program on registers that still hold their initial value,
+//! so that loops traversing buffers need no annotations.
+
+use std::collections::HashSet;
+
+use crate::{
+    ast::FBinOp,
+    cfg::*,
+    dataflow::{reaching_defs, solve, DefSite},
+    formula::FormulaBuilder,
+    loops::cut_points,
+    prog::ProgType,
+    vc::frame,
+    zone::{ZoneAnalysis, Zones},
+};
+
+/// Split a formula into its conjuncts.
+pub fn conjuncts(formula: &Formula) -> Vec<&Formula> {
+    match formula {
+        Formula::Bin(FBinOp::And | FBinOp::AndAsym, fs) => {
+            let mut result = conjuncts(&fs.0);
+            result.extend(conjuncts(&fs.1));
+            result
+        }
+        Formula::Val(true) => vec![],
+        formula => vec![formula],
+    }
+}
+
+/// Add the invariants inferred at each cut point to its `req`.
+pub fn infer_invariants(cfg: &mut Cfg, prog: ProgType, f: &FormulaBuilder) {
+    let analysis = ZoneAnalysis::new(cfg, prog, f);
+    let zones = solve(cfg, &analysis);
+    let reaching = reaching_defs(cfg);
+    let frame = frame(f);
+    let mut inferred = Vec::new();
+    for cut in cut_points(cfg) {
+        let Some(zone @ Zones(Some(_))) = zones.entry(&cut.label) else {
+            continue;
+        };
+        let mut invariant = zone.formulas(f);
+
+        // The frame pointer is assumed at every cut point anyway.
+        let initial: HashSet<Reg> = (0..10)
+            .filter_map(Reg::new)
+            .filter(|reg| {
+                reaching.entry(&cut.label).is_some_and(|defs| {
+                    defs.iter()
+                        .filter(|d| d.reg == *reg)
+                        .all(|d| d.site == DefSite::Entry)
+                })
+            })
+            .collect();
+        for c in conjuncts(&cfg.requires) {
+            let vars = f.free_vars(c);
+            let kept = !matches!(c, Formula::Held(_))
+                && vars
+                    .iter()
+                    .all(|x| f.reg_of(x).is_some_and(|r| initial.contains(&r)));
+            if kept {
+                invariant.push(c.clone());
+            }
+        }
+
+        let block = &cfg.blocks[&cut.label];
+        let mut existing: Vec<Formula> = match &block.require {
+            Some(require) => conjuncts(require).into_iter().cloned().collect(),
+            None => vec![],
+        };
+        existing.extend(conjuncts(&frame).into_iter().cloned());
+        invariant.retain(|c| match existing.contains(c) {
+            true => false,
+            false => {
+                existing.push(c.clone());
+                true
+            }
+        });
+        if !invariant.is_empty() {
+            inferred.push((cut.label, invariant));
+        }
+    }
+
+    for (label, invariant) in inferred {
+        let block = cfg.blocks.get_mut(&label).unwrap();
+        let invariant = invariant.into_iter().reduce(|a, b| f.and(a, b)).unwrap();
+        block.require = Some(match block.require.take() {
+            Some(require) => f.and(require, invariant),
+            None => invariant,
+        });
+    }
+}
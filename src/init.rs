@@ -0,0 +1,249 @@
+//! Definite initialization analysis of registers.
+//! The kernel rejects programs that read a register before writing it,
+//! including exiting without setting `r0`.
+
+use std::{
+    collections::{HashMap, HashSet, VecDeque},
+    fmt::{self, Display, Formatter},
+};
+
+use crate::{
+    cfg::*,
+    dataflow::{solve, Analysis, Direction, Lattice},
+    helpers::helper_by_id,
+    prog::ProgType,
+};
+
+/// A read of a register that might not have been initialized.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UninitReg {
+    pub reg: Reg,
+    pub label: Label,
+    pub site: Site,
+    /// Blocks of a path on which the register is never written,
+    /// starting from either the program start or a block whose helper call clears it.
+    pub path: Vec<Label>,
+}
+
+impl Display for UninitReg {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        let UninitReg {
+            reg,
+            label,
+            site,
+            path,
+        } = self;
+        f.write_fmt(format_args!(
+            "Read of uninitialized register r{} by {}",
+            reg.get(),
+            site.describe(label)
+        ))?;
+        f.write_fmt(format_args!(", reachable via {}", path.join(" -> ")))
+    }
+}
+
+/// Registers that are initialized when the program starts:
+/// the context pointer `r1` and the frame pointer `r10`.
+/// Functions, including subprograms, take their arguments in `r1`-`r5` instead.
+pub fn entry_regs(cfg: &Cfg, prog: ProgType) -> HashSet<Reg> {
+    let mut regs = HashSet::from([Reg::R1, Reg::R10]);
+    if cfg.name.is_some() || prog == ProgType::Function {
+        regs.extend([Reg::R2, Reg::R3, Reg::R4, Reg::R5]);
+    }
+    regs
+}
+
+/// Registers read by a statement.
+pub fn stmt_uses(stmt: &Stmt) -> Vec<Reg> {
+    let mut uses = Vec::new();
+    let mut use_src = |src: &RegImm| {
+        if let RegImm::Reg(r) = src {
+            uses.push(*r);
+        }
+    };
+    match stmt {
+        Stmt::Unary(_, _, reg) => uses.push(*reg),
+        Stmt::Binary(_, BinAlu::Mov, _, src) => use_src(src),
+        Stmt::Binary(_, _, dst, src) => {
+            use_src(src);
+            uses.push(*dst);
+        }
+        Stmt::Store(_, MemRef(reg, _), src) => {
+            use_src(src);
+            uses.push(*reg);
+        }
+        Stmt::Load(_, _, MemRef(reg, _)) => uses.push(*reg),
+        // Helpers read as many arguments as they take, subprograms all of them.
+        Stmt::Call(id) => uses.extend((1..=helper_by_id(*id).args).filter_map(Reg::new)),
+        Stmt::CallLocal(_) => uses.extend((1..=5).filter_map(Reg::new)),
+        Stmt::Assert(_) | Stmt::LoadImm(_, _) | Stmt::LoadMapFd(_, _) | Stmt::LoadFunc(_, _) => (),
+    }
+    uses
+}
+
+/// Registers written by a statement.
+/// Calls write `r0` and leave the argument registers `r1`-`r5` uninitialized.
+pub fn stmt_defs(stmt: &Stmt) -> Vec<Reg> {
+    match stmt {
+        Stmt::Unary(_, _, dst)
+        | Stmt::Binary(_, _, dst, _)
+        | Stmt::Load(_, dst, _)
+        | Stmt::LoadImm(dst, _)
+        | Stmt::LoadMapFd(dst, _)
+        | Stmt::LoadFunc(dst, _) => vec![*dst],
+        Stmt::Call(_) | Stmt::CallLocal(_) => vec![Reg::R0],
+        Stmt::Store(_, _, _) | Stmt::Assert(_) => vec![],
+    }
+}
+
+/// Registers whose value is destroyed by a statement.
+pub fn stmt_kills(stmt: &Stmt) -> Vec<Reg> {
+    match stmt {
+        Stmt::Call(_) | Stmt::CallLocal(_) => vec![Reg::R1, Reg::R2, Reg::R3, Reg::R4, Reg::R5],
+        _ => vec![],
+    }
+}
+
+/// Registers read by a continuation.
+pub fn cont_uses(cont: &Continuation) -> Vec<Reg> {
+    match cont {
+        Continuation::Exit => vec![Reg::R0],
+        Continuation::Jmp(_) => vec![],
+        Continuation::Jcc(_, lhs, RegImm::Reg(rhs), _, _) => vec![*lhs, *rhs],
+        Continuation::Jcc(_, lhs, RegImm::Imm(_), _, _) => vec![*lhs],
+    }
+}
+
+/// Registers that are initialized on every path, joined by intersection.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Init(HashSet<Reg>);
+
+impl Lattice for Init {
+    fn join(&mut self, other: &Self) -> bool {
+        let prev_len = self.0.len();
+        self.0.retain(|r| other.0.contains(r));
+        self.0.len() != prev_len
+    }
+}
+
+struct InitAnalysis<'a>(&'a HashSet<Reg>);
+
+impl Analysis for InitAnalysis<'_> {
+    type Fact = Init;
+    const DIRECTION: Direction = Direction::Forward;
+
+    fn bottom(&self, _cfg: &Cfg) -> Init {
+        Init((0..11).filter_map(Reg::new).collect())
+    }
+
+    fn boundary(&self, _cfg: &Cfg) -> Init {
+        Init(self.0.clone())
+    }
+
+    fn transfer(&self, _label: &Label, _index: usize, stmt: &Stmt, init: &mut Init) {
+        for r in stmt_kills(stmt) {
+            init.0.remove(&r);
+        }
+        init.0.extend(stmt_defs(stmt));
+    }
+}
+
+/// Find all reads of registers that aren't initialized on every path leading to them.
+pub fn uninit_regs(cfg: &Cfg, entry: &HashSet<Reg>) -> Vec<UninitReg> {
+    // Compute the registers that are definitely initialized at the entry of each block.
+    let analysis = InitAnalysis(entry);
+    let states = solve(cfg, &analysis);
+
+    // Report reads of registers that aren't in the fixpoint.
+    let mut result = Vec::new();
+    for label in states.labels() {
+        let block = &cfg.blocks[label];
+        let facts = states.stmt_facts(label);
+        let sites = block
+            .body
+            .iter()
+            .enumerate()
+            .map(|(index, stmt)| (stmt_uses(stmt), Site::Stmt(index, stmt.clone())))
+            .chain([(cont_uses(&block.next), Site::Cont(block.next.clone()))]);
+        for ((uses, site), init) in sites.zip(facts) {
+            for reg in uses {
+                if !init.0.contains(&reg) {
+                    result.push(UninitReg {
+                        reg,
+                        label: label.clone(),
+                        site: site.clone(),
+                        path: uninit_path(cfg, entry, label, reg),
+                    });
+                }
+            }
+        }
+    }
+    result.sort_by(|a, b| (&a.label, a.reg.get()).cmp(&(&b.label, b.reg.get())));
+    result
+}
+
+/// Find a shortest path to the entry of `target`
+/// that doesn't pass through any block initializing `reg`.
+fn uninit_path(cfg: &Cfg, entry: &HashSet<Reg>, target: &Label, reg: Reg) -> Vec<Label> {
+    // Whether the register is initialized at the end of a block.
+    let init_at_end = |label: &Label, initially: bool| {
+        let mut init = initially;
+        for stmt in cfg.blocks[label].body.iter() {
+            if stmt_kills(stmt).contains(&reg) {
+                init = false;
+            }
+            if stmt_defs(stmt).contains(&reg) {
+                init = true;
+            }
+        }
+        init
+    };
+
+    let mut preds: HashMap<&Label, Option<&Label>> = HashMap::new();
+    let mut queue = VecDeque::new();
+    // If the register isn't initialized at the start, the path can start there.
+    if !entry.contains(&reg) {
+        preds.insert(&cfg.start, None);
+        queue.push_back(&cfg.start);
+    } else {
+        // Otherwise, the path must start from a block that clears the register.
+        for label in cfg.blocks.keys() {
+            if !init_at_end(label, true) {
+                for t in cfg.blocks[label].next.targets() {
+                    preds.entry(t).or_insert(Some(label));
+                    queue.push_back(t);
+                }
+            }
+        }
+    }
+
+    while let Some(label) = queue.pop_front() {
+        if label == target {
+            break;
+        }
+        if init_at_end(label, false) {
+            continue;
+        }
+        for t in cfg.blocks[label].next.targets() {
+            if !preds.contains_key(t) {
+                preds.insert(t, Some(label));
+                queue.push_back(t);
+            }
+        }
+    }
+
+    let mut path = vec![target.clone()];
+    let mut current = target;
+    while let Some(Some(pred)) = preds.get(current) {
+        path.push((*pred).clone());
+        current = pred;
+        if current == target {
+            break;
+        }
+    }
+    path.reverse();
+    path
+}
+
+#[cfg(test)]
+mod tests;
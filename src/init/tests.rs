@@ -0,0 +1,75 @@
+use super::*;
+
+/// The uninitialized registers that a program reads, with the blocks they are read in.
+fn uninit(src: &str, prog: ProgType) -> Vec<(u8, Label)> {
+    let cfg = Cfg::parse(src);
+    uninit_regs(&cfg, &entry_regs(&cfg, prog))
+        .into_iter()
+        .map(|u| (u.reg.get(), u.label))
+        .collect()
+}
+
+#[test]
+fn entry() {
+    let mut cfg = Cfg::parse("mov r0 0\nexit\n");
+    let args = HashSet::from([Reg::R1, Reg::R2, Reg::R3, Reg::R4, Reg::R5, Reg::R10]);
+    assert_eq!(entry_regs(&cfg, ProgType::Function), args);
+    assert_eq!(
+        entry_regs(&cfg, ProgType::Xdp),
+        HashSet::from([Reg::R1, Reg::R10])
+    );
+    cfg.name = Some("f".to_owned());
+    assert_eq!(entry_regs(&cfg, ProgType::Xdp), args);
+}
+
+#[test]
+fn preconditions_initialize_nothing() {
+    let src = ";# requires r6 >= 0\nmov r0 r6\nexit\n";
+    assert_eq!(uninit(src, ProgType::Function), vec![(6, "@0".to_owned())]);
+}
+
+#[test]
+fn exit_reads_r0() {
+    assert_eq!(uninit("exit\n", ProgType::Xdp), vec![(0, "@0".to_owned())]);
+}
+
+#[test]
+fn one_path() {
+    let src = "\
+jeq r1 0 skip
+mov r0 1
+skip:
+exit
+";
+    assert_eq!(uninit(src, ProgType::Xdp), vec![(0, "skip".to_owned())]);
+}
+
+#[test]
+fn helper_arguments() {
+    // map_lookup_elem reads its two arguments, ktime_get_ns none.
+    let lookup = "ldmapfd r1 0\nmov r0 0\ncall 1\nexit\n";
+    assert_eq!(uninit(lookup, ProgType::Xdp), vec![(2, "@0".to_owned())]);
+    let time = "call 5\ncall 5\nexit\n";
+    assert!(uninit(time, ProgType::Xdp).is_empty());
+    // A call leaves the argument registers uninitialized for the next one.
+    let again = "mov r2 0\ncall 1\ncall 1\nexit\n";
+    assert_eq!(
+        uninit(again, ProgType::Xdp),
+        vec![(1, "@0".to_owned()), (2, "@0".to_owned())]
+    );
+}
+
+#[test]
+fn local_call_arguments() {
+    let src = "\
+call f
+exit
+
+;# function f
+mov r0 0
+exit
+";
+    let regs: Vec<u8> = uninit(src, ProgType::Xdp).iter().map(|u| u.0).collect();
+    assert_eq!(regs, vec![2, 3, 4, 5]);
+    assert!(uninit(src, ProgType::Function).is_empty());
+}
@@ -0,0 +1,332 @@
+//! An abstract domain of intervals, approximating each register by a range of values.
+//! Ranges are of unsigned 64-bit values,
+//! and become unbounded whenever an operation might wrap around only part of them.
+
+use crate::{cfg::*, dataflow::Lattice};
+
+/// An inclusive range of unsigned values.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Interval {
+    pub lo: u64,
+    pub hi: u64,
+}
+
+impl Interval {
+    pub const TOP: Interval = Interval {
+        lo: 0,
+        hi: u64::MAX,
+    };
+
+    pub fn constant(value: u64) -> Self {
+        Interval {
+            lo: value,
+            hi: value,
+        }
+    }
+
+    /// Values of a word of the given size, zero-extended.
+    pub fn word(size: WordSize) -> Self {
+        match size {
+            WordSize::B64 => Self::TOP,
+            size => Interval {
+                lo: 0,
+                hi: u64::MAX >> (64 - 8 * size.bytes()),
+            },
+        }
+    }
+
+    pub fn as_const(&self) -> Option<u64> {
+        (self.lo == self.hi).then_some(self.lo)
+    }
+
+    fn hull(&self, other: &Interval) -> Interval {
+        Interval {
+            lo: self.lo.min(other.lo),
+            hi: self.hi.max(other.hi),
+        }
+    }
+
+    fn meet(&self, other: &Interval) -> Option<Interval> {
+        let lo = self.lo.max(other.lo);
+        let hi = self.hi.min(other.hi);
+        (lo <= hi).then_some(Interval { lo, hi })
+    }
+
+    /// Keep the range if it fits in the lower 32 bits, as the results of 32-bit operations do.
+    fn truncate(self) -> Interval {
+        match self.hi <= u32::MAX as u64 {
+            true => self,
+            false => Interval::word(WordSize::B32),
+        }
+    }
+
+    /// Whether every value is nonnegative when read as signed.
+    fn nonnegative(&self) -> bool {
+        self.hi <= i64::MAX as u64
+    }
+
+    /// A range with both ends computed by a wrapping operation,
+    /// which is only exact if both or neither of them wrapped around.
+    fn wrapping((lo, lo_wrapped): (u64, bool), (hi, hi_wrapped): (u64, bool)) -> Interval {
+        match lo_wrapped == hi_wrapped {
+            true => Interval { lo, hi },
+            false => Interval::TOP,
+        }
+    }
+
+    fn binary(op: BinAlu, a: Interval, b: Interval) -> Interval {
+        match op {
+            BinAlu::Mov => b,
+            BinAlu::Add => {
+                Interval::wrapping(a.lo.overflowing_add(b.lo), a.hi.overflowing_add(b.hi))
+            }
+            BinAlu::Sub => {
+                Interval::wrapping(a.lo.overflowing_sub(b.hi), a.hi.overflowing_sub(b.lo))
+            }
+            BinAlu::Mul => match a.hi.checked_mul(b.hi) {
+                Some(hi) => Interval {
+                    lo: a.lo * b.lo,
+                    hi,
+                },
+                None => Interval::TOP,
+            },
+            // Division by zero results in zero, and the remainder of it in the dividend.
+            BinAlu::Div if b.lo == 0 => Interval { lo: 0, hi: a.hi },
+            BinAlu::Div => Interval {
+                lo: a.lo / b.hi,
+                hi: a.hi / b.lo,
+            },
+            BinAlu::Mod if b.lo == 0 => Interval { lo: 0, hi: a.hi },
+            BinAlu::Mod => Interval {
+                lo: 0,
+                hi: a.hi.min(b.hi - 1),
+            },
+            BinAlu::And => Interval {
+                lo: 0,
+                hi: a.hi.min(b.hi),
+            },
+            BinAlu::Or => Interval {
+                lo: a.lo.max(b.lo),
+                hi: u64::MAX
+                    .checked_shr(a.hi.max(b.hi).leading_zeros())
+                    .unwrap_or(0),
+            },
+            BinAlu::Xor => Interval {
+                lo: 0,
+                hi: u64::MAX
+                    .checked_shr(a.hi.max(b.hi).leading_zeros())
+                    .unwrap_or(0),
+            },
+            BinAlu::Lsh => match b.as_const() {
+                Some(k) if k < 64 && a.hi.leading_zeros() as u64 >= k => Interval {
+                    lo: a.lo << k,
+                    hi: a.hi << k,
+                },
+                _ => Interval::TOP,
+            },
+            BinAlu::Rsh if b.hi < 64 => Interval {
+                lo: a.lo >> b.hi,
+                hi: a.hi >> b.lo,
+            },
+            BinAlu::Rsh => Interval { lo: 0, hi: a.hi },
+            BinAlu::Arsh if a.nonnegative() => Interval::binary(BinAlu::Rsh, a, b),
+            BinAlu::Arsh => Interval::TOP,
+        }
+    }
+}
+
+/// Ranges of the registers, or `None` if the program point is unreachable.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Intervals(pub Option<[Interval; 11]>);
+
+impl Lattice for Intervals {
+    fn join(&mut self, other: &Self) -> bool {
+        let Some(b) = &other.0 else {
+            return false;
+        };
+        let Some(a) = &mut self.0 else {
+            self.0 = other.0;
+            return true;
+        };
+        let prev = *a;
+        for (a, b) in a.iter_mut().zip(b.iter()) {
+            *a = a.hull(b);
+        }
+        *a != prev
+    }
+}
+
+impl Intervals {
+    pub fn get(&self, reg: Reg) -> Option<Interval> {
+        self.0.map(|regs| regs[reg.get() as usize])
+    }
+
+    pub fn set(&mut self, reg: Reg, interval: Interval) {
+        if let Some(regs) = &mut self.0 {
+            regs[reg.get() as usize] = interval;
+        }
+    }
+
+    pub fn src(&self, src: &RegImm) -> Option<Interval> {
+        match src {
+            RegImm::Reg(r) => self.get(*r),
+            RegImm::Imm(i) => Some(Interval::constant(*i as u64)),
+        }
+    }
+
+    pub fn step(&mut self, stmt: &Stmt) {
+        let (dst, value) = match stmt {
+            Stmt::Binary(_, BinAlu::Sub | BinAlu::Xor, dst, RegImm::Reg(src)) if dst == src => {
+                (*dst, Interval::constant(0))
+            }
+            Stmt::Binary(size, op, dst, src) => {
+                let (Some(mut a), Some(mut b)) = (self.get(*dst), self.src(src)) else {
+                    return;
+                };
+                match size {
+                    WordSize::B64 => (*dst, Interval::binary(*op, a, b)),
+                    _ => {
+                        // Immediates are truncated to 32 bits like registers are.
+                        a = a.truncate();
+                        b = match src {
+                            RegImm::Imm(i) => Interval::constant(*i as u32 as u64),
+                            RegImm::Reg(_) => b.truncate(),
+                        };
+                        // The sign bit of 32-bit arithmetic shifts is bit 31.
+                        if *op == BinAlu::Arsh && a.hi > i32::MAX as u64 {
+                            return self.set(*dst, Interval::word(WordSize::B32));
+                        }
+                        (*dst, Interval::binary(*op, a, b).truncate())
+                    }
+                }
+            }
+            Stmt::Unary(size, UnAlu::Neg, dst) => {
+                let value = match self.get(*dst).and_then(|a| a.as_const()) {
+                    Some(a) if *size == WordSize::B64 => Interval::constant(a.wrapping_neg()),
+                    Some(a) => Interval::constant((a as u32).wrapping_neg() as u64),
+                    None => Interval::word(*size),
+                };
+                (*dst, value)
+            }
+            Stmt::Unary(size, _, dst) => (*dst, Interval::word(*size)),
+            Stmt::Load(size, dst, _) => (*dst, Interval::word(*size)),
+            Stmt::LoadImm(dst, i) => (*dst, Interval::constant(*i as u64)),
+            Stmt::LoadMapFd(dst, _) | Stmt::LoadFunc(dst, _) => (*dst, Interval::TOP),
+            Stmt::Call(_) | Stmt::CallLocal(_) => {
+                for r in 0..=5 {
+                    self.set(Reg::new(r).unwrap(), Interval::TOP);
+                }
+                return;
+            }
+            Stmt::Store(_, _, _) | Stmt::Assert(_) => return,
+        };
+        self.set(dst, value);
+    }
+
+    /// Restrict the ranges to the values satisfying a comparison,
+    /// which leaves none of them if it can't hold.
+    pub fn assume(&mut self, cc: Cc, lhs: Reg, rhs: &RegImm) {
+        let (Some(a), Some(b)) = (self.get(lhs), self.src(rhs)) else {
+            return;
+        };
+        let Some(cc) = unsigned(cc, a, b) else {
+            return;
+        };
+        let refined = match cc {
+            Cc::Eq => a.meet(&b).map(|i| (i, i)),
+            Cc::Ne => match (a.as_const(), b.as_const()) {
+                (Some(x), Some(y)) if x == y => None,
+                (_, Some(y)) => Some((shrink(a, y), b)),
+                (Some(x), _) => Some((a, shrink(b, x))),
+                _ => Some((a, b)),
+            },
+            Cc::Lt => less(a, b, 1),
+            Cc::Le => less(a, b, 0),
+            Cc::Gt => less(b, a, 1).map(|(b, a)| (a, b)),
+            Cc::Ge => less(b, a, 0).map(|(b, a)| (a, b)),
+            Cc::Set => match a.hi == 0 || b.hi == 0 {
+                true => None,
+                false => Some((a, b)),
+            },
+            Cc::Sgt | Cc::Sge | Cc::Slt | Cc::Sle => unreachable!(),
+        };
+        match refined {
+            Some((a, b)) => {
+                self.set(lhs, a);
+                if let RegImm::Reg(rhs) = rhs {
+                    self.set(*rhs, b);
+                }
+            }
+            None => self.0 = None,
+        }
+    }
+}
+
+/// The unsigned comparison that agrees with a comparison of values in the given ranges.
+/// Signed comparisons only agree with unsigned ones for nonnegative values.
+pub fn unsigned(cc: Cc, a: Interval, b: Interval) -> Option<Cc> {
+    let cc = match cc {
+        Cc::Sgt | Cc::Sge | Cc::Slt | Cc::Sle if !a.nonnegative() || !b.nonnegative() => {
+            return None;
+        }
+        Cc::Sgt => Cc::Gt,
+        Cc::Sge => Cc::Ge,
+        Cc::Slt => Cc::Lt,
+        Cc::Sle => Cc::Le,
+        cc => cc,
+    };
+    Some(cc)
+}
+
+/// Restrict `a < b + gap`, that is `a <= b` if `gap` is 0 and `a < b` if it is 1.
+fn less(a: Interval, b: Interval, gap: u64) -> Option<(Interval, Interval)> {
+    let a_hi = a.hi.min(b.hi.checked_sub(gap)?);
+    let b_lo = b.lo.max(a.lo.checked_add(gap)?);
+    let a = Interval { lo: a.lo, hi: a_hi };
+    let b = Interval { lo: b_lo, hi: b.hi };
+    (a.lo <= a.hi && b.lo <= b.hi).then_some((a, b))
+}
+
+/// Remove a value from a range, if it is one of its ends.
+fn shrink(a: Interval, value: u64) -> Interval {
+    match (a.lo == value, a.hi == value) {
+        (true, _) => Interval { lo: a.lo + 1, ..a },
+        (_, true) => Interval { hi: a.hi - 1, ..a },
+        _ => a,
+    }
+}
+
+/// The comparison that holds exactly when the given one doesn't.
+pub fn negated(cc: Cc) -> Option<Cc> {
+    let cc = match cc {
+        Cc::Eq => Cc::Ne,
+        Cc::Ne => Cc::Eq,
+        Cc::Gt => Cc::Le,
+        Cc::Ge => Cc::Lt,
+        Cc::Lt => Cc::Ge,
+        Cc::Le => Cc::Gt,
+        Cc::Sgt => Cc::Sle,
+        Cc::Sge => Cc::Slt,
+        Cc::Slt => Cc::Sge,
+        Cc::Sle => Cc::Sgt,
+        Cc::Set => return None,
+    };
+    Some(cc)
+}
+
+/// Immediates of a program, which loops are likely to be bounded by.
+pub fn constants(cfg: &Cfg) -> Vec<Imm> {
+    let mut result = Vec::new();
+    for block in cfg.blocks.values() {
+        for stmt in block.body.iter() {
+            match stmt {
+                Stmt::Binary(_, _, _, RegImm::Imm(i)) | Stmt::LoadImm(_, i) => result.push(*i),
+                _ => (),
+            }
+        }
+        if let Continuation::Jcc(_, _, RegImm::Imm(i), _, _) = block.next {
+            result.push(i);
+        }
+    }
+    result
+}
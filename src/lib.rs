@@ -1,7 +1,25 @@
 pub mod ast;
+pub mod bounds;
 pub mod cfg;
+pub mod cost;
+pub mod dataflow;
+pub mod depth;
 //pub mod cvc5;
 pub mod formula;
+pub mod helpers;
+pub mod houdini;
+pub mod infer;
+pub mod init;
+pub mod interval;
+pub mod loops;
 pub mod parse;
+pub mod prog;
+pub mod prover;
+pub mod reach;
+pub mod refs;
+pub mod stack;
+pub mod tnum;
+pub mod types;
 pub mod vc;
 pub mod whyml;
+pub mod zone;
@@ -0,0 +1,159 @@
+//! Cut points of cycles.
+//! The verification conditions cut every cycle using the `req` annotations at its cut points,
+//! so cut points without one are reported.
+//! Irreducible loops, which can be entered at several blocks, are cut at every entry.
+
+use std::{
+    collections::HashSet,
+    fmt::{self, Display, Formatter},
+};
+
+use crate::cfg::*;
+
+/// A block that cuts the cycles of a region,
+/// so that the verification conditions need an invariant there.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CutPoint {
+    pub label: Label,
+    /// The strongly connected region that the block is an entry of.
+    pub region: HashSet<Label>,
+    /// Blocks of the region that jump to the cut point, closing its cycles.
+    pub latches: Vec<Label>,
+    /// The other entries of the region if it is irreducible, that is, it has several.
+    pub other_entries: Vec<Label>,
+}
+
+/// Choose the blocks at which to cut the cycles of a CFG,
+/// such that every cycle passes through at least one of them.
+/// Every strongly connected region is cut at each of its entries,
+/// and what remains of it once they are removed is cut the same way.
+/// For loops with a single entry, this is the header of the natural loop.
+pub fn cut_points(cfg: &Cfg) -> Vec<CutPoint> {
+    let preds = cfg.predecessors();
+    let mut result = Vec::new();
+    let mut worklist: Vec<HashSet<&Label>> = vec![cfg.reachable()];
+    while let Some(nodes) = worklist.pop() {
+        for scc in cfg.sccs_within(&nodes) {
+            let region: HashSet<&Label> = scc.iter().copied().collect();
+            let cyclic = scc.len() > 1 || preds[scc[0]].contains(&scc[0]);
+            if !cyclic {
+                continue;
+            }
+            let entries: Vec<&Label> = scc
+                .iter()
+                .copied()
+                .filter(|l| *l == &cfg.start || preds[l].iter().any(|p| !region.contains(p)))
+                .collect();
+            for entry in entries.iter() {
+                let mut latches: Vec<Label> = preds[entry]
+                    .iter()
+                    .filter(|p| region.contains(*p))
+                    .map(|p| (*p).clone())
+                    .collect();
+                latches.sort();
+                latches.dedup();
+                result.push(CutPoint {
+                    label: (*entry).clone(),
+                    region: region.iter().map(|l| (*l).clone()).collect(),
+                    latches,
+                    other_entries: entries
+                        .iter()
+                        .filter(|e| *e != entry)
+                        .map(|e| (*e).clone())
+                        .collect(),
+                });
+            }
+            let mut inner = region;
+            for entry in entries {
+                inner.remove(entry);
+            }
+            worklist.push(inner);
+        }
+    }
+    result.sort_by(|a, b| a.label.cmp(&b.label));
+    result
+}
+
+/// A cut point without an invariant, so that `true` is used in its place.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MissingInvariant(pub CutPoint);
+
+impl Display for MissingInvariant {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        let CutPoint {
+            label,
+            latches,
+            other_entries,
+            ..
+        } = &self.0;
+        let edges = latches
+            .iter()
+            .map(|l| format!("{l} -> {label}"))
+            .collect::<Vec<_>>()
+            .join(", ");
+        if other_entries.is_empty() {
+            f.write_fmt(format_args!(
+                "Loop header {label} has no invariant, so `true` is assumed on back edges {edges}"
+            ))
+        } else {
+            f.write_fmt(format_args!(
+                "Irreducible loop can be entered at {label} and {}, and {label} has no invariant, \
+                 so `true` is assumed on edges {edges}",
+                other_entries.join(", ")
+            ))
+        }
+    }
+}
+
+/// Find the cut points that lack a `req` annotation.
+pub fn missing_invariants(cfg: &Cfg) -> Vec<MissingInvariant> {
+    cut_points(cfg)
+        .into_iter()
+        .filter(|c| cfg.blocks[&c.label].require.is_none())
+        .map(MissingInvariant)
+        .collect()
+}
+
+/// A loop header with a variant whose cycles pass through another cut point.
+/// The value of the variant at the start of an iteration is lost there,
+/// so its decrease can't be checked on the back edges.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NestedVariant {
+    pub label: Label,
+    pub inner: Label,
+}
+
+impl Display for NestedVariant {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        let NestedVariant { label, inner } = self;
+        f.write_fmt(format_args!(
+            "Variant of loop header {label} is unsupported, as its cycles pass through cut point {inner}"
+        ))
+    }
+}
+
+/// Find the loop headers with a variant whose region contains other cut points.
+pub fn nested_variants(cfg: &Cfg) -> Vec<NestedVariant> {
+    let cuts = cut_points(cfg);
+    let mut result = Vec::new();
+    for cut in cuts.iter() {
+        if cfg.blocks[&cut.label].variant.is_none() {
+            continue;
+        }
+        let inner = cuts
+            .iter()
+            .filter(|c| c.label != cut.label && cut.region.contains(&c.label))
+            .map(|c| &c.label)
+            .min();
+        if let Some(inner) = inner {
+            result.push(NestedVariant {
+                label: cut.label.clone(),
+                inner: inner.clone(),
+            });
+        }
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests;
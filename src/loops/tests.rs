@@ -0,0 +1,145 @@
+use super::*;
+
+#[test]
+fn variants_of_nested_loops() {
+    // The outer variant is lost at the inner header, so it's rejected.
+    let src = "\
+mov r1 10
+outer:
+;# req r1 >= 1
+;# variant r1
+mov r2 10
+inner:
+;# req r2 >= 1
+;# variant r2
+sub r2 1
+jgt r2 0 inner
+sub r1 1
+jgt r1 0 outer
+mov r0 0
+exit
+";
+    let cfg = Cfg::parse(src);
+    assert_eq!(
+        nested_variants(&cfg),
+        vec![NestedVariant {
+            label: "outer".to_owned(),
+            inner: "inner".to_owned(),
+        }]
+    );
+
+    // A variant on the innermost loop alone is fine.
+    let src = src.replace(";# req r1 >= 1\n;# variant r1\n", ";# req r1 >= 1\n");
+    assert!(nested_variants(&Cfg::parse(&src)).is_empty());
+}
+
+#[test]
+fn natural_loops() {
+    let src = "\
+mov r1 0
+loop:
+add r1 1
+jgt r1 5 end
+jlt r1 3 loop
+ja loop
+end:
+mov r0 0
+exit
+";
+    let cuts = cut_points(&Cfg::parse(src));
+    assert_eq!(cuts.len(), 1);
+    let cut = &cuts[0];
+    assert_eq!(cut.label, "loop");
+    assert_eq!(cut.latches, vec!["@1".to_owned(), "@2".to_owned()]);
+    assert!(cut.other_entries.is_empty());
+    assert!(!cut.region.contains("end"));
+}
+
+#[test]
+fn missing() {
+    let src = "mov r1 0\nloop:\nadd r1 1\njlt r1 10 loop\nmov r0 0\nexit\n";
+    let missing = missing_invariants(&Cfg::parse(src));
+    assert_eq!(missing.len(), 1);
+    assert_eq!(
+        missing[0].to_string(),
+        "Loop header loop has no invariant, so `true` is assumed on back edges loop -> loop"
+    );
+    let src = src.replace("loop:\n", "loop:\n;# req r1 <= 10\n");
+    assert!(missing_invariants(&Cfg::parse(&src)).is_empty());
+}
+
+#[test]
+fn nested_loops() {
+    let src = "\
+mov r1 0
+outer:
+mov r2 0
+inner:
+add r2 1
+jlt r2 10 inner
+add r1 1
+jlt r1 10 outer
+mov r0 0
+exit
+";
+    let cuts = cut_points(&Cfg::parse(src));
+    let labels: Vec<&str> = cuts.iter().map(|c| c.label.as_str()).collect();
+    assert_eq!(labels, vec!["inner", "outer"]);
+    assert!(cuts[1].region.contains("inner"));
+    assert!(!cuts[0].region.contains("outer"));
+}
+
+const IRREDUCIBLE: &str = "\
+mov r1 0
+jgt r2 5 b
+a:
+add r1 1
+b:
+jlt r1 10 a
+mov r0 r1
+exit
+";
+
+#[test]
+fn irreducible() {
+    // The region can be entered at both blocks, so both are cut.
+    let cuts = cut_points(&Cfg::parse(IRREDUCIBLE));
+    let labels: Vec<&str> = cuts.iter().map(|c| c.label.as_str()).collect();
+    assert_eq!(labels, vec!["a", "b"]);
+    assert_eq!(cuts[0].other_entries, vec!["b".to_owned()]);
+    assert_eq!(cuts[1].other_entries, vec!["a".to_owned()]);
+    assert_eq!(cuts[0].latches, vec!["b".to_owned()]);
+    assert_eq!(cuts[1].latches, vec!["a".to_owned()]);
+
+    let missing = missing_invariants(&Cfg::parse(IRREDUCIBLE));
+    assert_eq!(
+        missing[1].to_string(),
+        "Irreducible loop can be entered at b and a, and b has no invariant, \
+         so `true` is assumed on edges a -> b"
+    );
+    let src = IRREDUCIBLE.replace("a:\n", "a:\n;# req r1 < 10\n");
+    let missing = missing_invariants(&Cfg::parse(&src));
+    let labels: Vec<&str> = missing.iter().map(|m| m.0.label.as_str()).collect();
+    assert_eq!(labels, vec!["b"]);
+}
+
+#[test]
+fn irreducible_inner_cycles() {
+    // Once the entries are cut, the cycle that remains within the region is cut as well.
+    let src = "\
+jgt r2 5 b
+a:
+add r1 1
+c:
+add r1 2
+jlt r1 5 c
+b:
+jlt r1 10 a
+mov r0 r1
+exit
+";
+    let cuts = cut_points(&Cfg::parse(src));
+    let labels: Vec<&str> = cuts.iter().map(|c| c.label.as_str()).collect();
+    assert_eq!(labels, vec!["a", "b", "c"]);
+    assert!(cuts[2].other_entries.is_empty());
+}
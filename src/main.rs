@@ -3,9 +3,22 @@ use argh::FromArgs;
 use std::{ffi::OsString, process::ExitCode, str::FromStr};
 
 use ebpf_vc::{
+    bounds::{bounds_errors, facts, Facts},
     cfg::{Cfg, ConvertErr},
+    cost::{costs, KERNEL_MAX_INSNS},
+    depth::depth_errors,
     formula::FormulaBuilder,
+    houdini::houdini,
+    infer::infer_invariants,
+    init::{entry_regs, uninit_regs},
+    loops::{missing_invariants, nested_variants},
     parse::module,
+    prog::ProgType,
+    prover::Why3,
+    reach::dead_code,
+    refs::ref_errors,
+    stack::uninit_reads,
+    types::{RegTypes, TypeInfo},
     vc::vc,
     whyml,
 };
@@ -19,6 +32,32 @@ struct EbpfVc {
     /// proof obligation format (default is WhyML)
     #[argh(option, default = "OutputFmt::WhyML")]
     format: OutputFmt,
+    /// program type, which determines the context and return codes (default is function)
+    #[argh(option, default = "ProgType::Function")]
+    prog_type: ProgType,
+    /// check that the program doesn't leak pointers, as required for unprivileged programs
+    #[argh(switch)]
+    unprivileged: bool,
+    /// check that no run executes more than this many instructions, given bounds on the loops
+    #[argh(option)]
+    max_insns: Option<u64>,
+    /// don't add the ranges of registers inferred at loop headers to their invariants
+    #[argh(switch)]
+    no_infer: bool,
+    /// predict whether the kernel accepts the program from the known bits and bounds of registers,
+    /// instead of generating conditions, taking the bounds of loops on trust
+    #[argh(switch)]
+    kernel: bool,
+    /// assume the bounds of registers that the kernel tracks, and skip the accesses they show to be safe,
+    /// which is unsound if the tracking is wrong
+    #[argh(switch)]
+    assume_bounds: bool,
+    /// infer invariants at loop headers from candidates, keeping those that the prover shows to be inductive
+    #[argh(switch)]
+    houdini: bool,
+    /// prover that Why3 runs on the candidates of --houdini (default is CVC4,1.8)
+    #[argh(option, default = "String::from(\"CVC4,1.8\")")]
+    prover: String,
 }
 
 enum OutputFmt {
@@ -61,20 +100,183 @@ fn main() -> ExitCode {
     //eprintln!("{ast:#?}\n");
 
     let mut f = FormulaBuilder::new();
-    let preprocess_res: Result<Cfg, ConvertErr> = Cfg::create(ast, &mut f);
-    let processed_ast = match preprocess_res {
+    let preprocess_res: Result<Vec<Cfg>, ConvertErr> = Cfg::create(ast, &mut f);
+    let cfgs = match preprocess_res {
         Ok(p) => p,
         Err(e) => {
             eprintln!("error: {e}");
             return ExitCode::FAILURE;
         }
     };
-    //eprintln!("{processed_ast:#?}\n");
+    //eprintln!("{cfgs:#?}\n");
 
-    let vc_res = vc(processed_ast, &mut f);
+    let mut failed = false;
+    // The kernel rejects loops that it can't show to terminate within its limit.
+    let max_insns = match opts.kernel {
+        true => Some(opts.max_insns.unwrap_or(KERNEL_MAX_INSNS)),
+        false => opts.max_insns,
+    };
+    if let Some(limit) = max_insns {
+        for (cfg, cost) in cfgs.iter().zip(costs(&cfgs)) {
+            let error = match &cfg.name {
+                Some(name) => format!("error in {name}"),
+                None => "error".to_owned(),
+            };
+            match cost {
+                Err(e) => {
+                    eprintln!("{error}: {e}");
+                    failed = true;
+                }
+                // Subprograms count towards the instructions of their callers.
+                Ok(cost) if cfg.name.is_none() => {
+                    if cost.insns > limit {
+                        eprintln!("{error}: Run might exceed {limit} instructions, with {cost}");
+                        failed = true;
+                    } else {
+                        eprintln!("note: Runs take at most {cost}");
+                    }
+                }
+                Ok(_) => (),
+            }
+        }
+    }
+
+    // Subprograms are plain BPF functions, checked against their own contracts.
+    let mut checked = Vec::new();
+    for mut cfg in cfgs {
+        let prog = match cfg.name {
+            Some(_) => ProgType::Function,
+            None => opts.prog_type,
+        };
+        prog.apply(&mut cfg, &f);
+        if !opts.no_infer && !opts.kernel {
+            infer_invariants(&mut cfg, prog, &f);
+        }
+        match check(&cfg, prog, opts.unprivileged, opts.kernel) {
+            Some(types) => checked.push((cfg, types)),
+            None => failed = true,
+        }
+    }
+    if failed {
+        return ExitCode::FAILURE;
+    }
+    let depth_errs = depth_errors(&checked);
+    for e in depth_errs.iter() {
+        match &e.function {
+            Some(name) => eprintln!("error in {name}: {e}"),
+            None => eprintln!("error: {e}"),
+        }
+    }
+    if !depth_errs.is_empty() {
+        return ExitCode::FAILURE;
+    }
+    if opts.kernel {
+        eprintln!("note: The kernel verifier is expected to accept the program");
+        return ExitCode::SUCCESS;
+    }
+
+    let prover = Why3 {
+        prover: opts.prover,
+    };
+    let mut vc_res = Vec::new();
+    for (mut cfg, types) in checked {
+        let name = cfg.name.clone();
+        let assumed = |cfg: &Cfg, f: &FormulaBuilder| match opts.assume_bounds {
+            true => facts(cfg, &types, f),
+            false => Facts::default(),
+        };
+        if opts.houdini {
+            let known = assumed(&cfg, &f);
+            if let Err(e) = houdini(&mut cfg, &types, &known, &mut f, &prover) {
+                eprintln!("error: {e}");
+                return ExitCode::FAILURE;
+            }
+        }
+        let facts = assumed(&cfg, &f);
+        let goals = vc(cfg, &types, &facts, &mut f);
+        vc_res.extend(goals.into_iter().map(|(label, goal)| match &name {
+            Some(name) => (format!("{name}_{label}"), goal),
+            None => (label, goal),
+        }));
+    }
     match opts.format {
         OutputFmt::WhyML => println!("{}", whyml::Conditions(vc_res)),
         OutputFmt::CVC5 => eprintln!("Architecture currently cannot support both formats"),
     }
     ExitCode::SUCCESS
 }
+
+/// Run the static analyses on a CFG, printing any errors.
+/// In kernel mode, the accesses are checked against the bounds of the registers as well.
+/// Returns the inferred types if there are none.
+fn check(cfg: &Cfg, prog: ProgType, unprivileged: bool, kernel: bool) -> Option<TypeInfo> {
+    let error = match &cfg.name {
+        Some(name) => format!("error in {name}"),
+        None => "error".to_owned(),
+    };
+    let entry_regs = entry_regs(cfg, prog);
+    let uninit_regs = uninit_regs(cfg, &entry_regs);
+    for e in uninit_regs.iter() {
+        eprintln!("{error}: {e}");
+    }
+    let entry_types = RegTypes::entry(cfg, &entry_regs, prog);
+    let types = TypeInfo::infer(cfg, entry_types, prog);
+    let uninit_stack = uninit_reads(cfg, &types);
+    for e in uninit_stack.iter() {
+        eprintln!("{error}: {e}");
+    }
+    let mut type_errs = types.errors(cfg);
+    if unprivileged {
+        type_errs.extend(types.leaks(cfg));
+    }
+    for e in type_errs.iter() {
+        eprintln!("{error}: {e}");
+    }
+    let bounds_errs = match kernel && type_errs.is_empty() {
+        true => bounds_errors(cfg, &types),
+        false => vec![],
+    };
+    for e in bounds_errs.iter() {
+        eprintln!("{error}: {e}");
+    }
+    let ref_errs = ref_errors(cfg);
+    for e in ref_errs.iter() {
+        eprintln!("{error}: {e}");
+    }
+    let (dead_errs, dead_warnings): (Vec<_>, Vec<_>) =
+        dead_code(cfg).into_iter().partition(|d| d.is_error());
+    for e in dead_errs.iter() {
+        eprintln!("{error}: {e}");
+    }
+    let warning = match &cfg.name {
+        Some(name) => format!("warning in {name}"),
+        None => "warning".to_owned(),
+    };
+    for w in dead_warnings.iter() {
+        eprintln!("{warning}: {w}");
+    }
+    // The kernel needs no invariants, nor variants.
+    let variant_errs = match kernel {
+        true => vec![],
+        false => nested_variants(cfg),
+    };
+    for e in variant_errs.iter() {
+        eprintln!("{error}: {e}");
+    }
+    if !kernel {
+        for w in missing_invariants(cfg).iter() {
+            eprintln!("{warning}: {w}");
+        }
+    }
+    if !uninit_regs.is_empty()
+        || !uninit_stack.is_empty()
+        || !type_errs.is_empty()
+        || !bounds_errs.is_empty()
+        || !ref_errs.is_empty()
+        || !dead_errs.is_empty()
+        || !variant_errs.is_empty()
+    {
+        return None;
+    }
+    Some(types)
+}
@@ -231,11 +231,29 @@ fn cont(i: &str) -> Res<Cont> {
 
 fn stmt(i: &str) -> Res<Stmt> {
     let call = map(preceded(pair(tag("call"), space1), imm), Stmt::Call);
+    let call_local = map(preceded(pair(tag("call"), space1), ident), |name| {
+        Stmt::CallLocal(name.to_owned())
+    });
     let load_imm = map(instr!(tag("lddw"), reg, imm), |(_, reg, imm)| {
         Stmt::LoadImm(reg, imm)
     });
-    // Missing: LoadMapFd
-    alt((unary, binary, load, load_imm, store, call))(i)
+    let load_map_fd = map(instr!(tag("ldmapfd"), reg, imm), |(_, reg, imm)| {
+        Stmt::LoadMapFd(reg, imm)
+    });
+    let load_func = map(instr!(tag("ldfunc"), reg, ident), |(_, reg, name)| {
+        Stmt::LoadFunc(reg, name.to_owned())
+    });
+    alt((
+        unary,
+        binary,
+        load,
+        load_imm,
+        load_map_fd,
+        load_func,
+        store,
+        call,
+        call_local,
+    ))(i)
 }
 
 // Assertion parsing
@@ -315,7 +333,9 @@ fn formula(i: &str) -> Res<Formula> {
         parens(tuple((ident, char(','), space0, expr, space0))),
     ))
     .map(|(_, _, (id, _, _, e, _))| Formula::IsBuffer(id.to_owned(), e));
-    alt((parenthesized, val, not, binary, quant, rel, is_buffer))(i)
+    let held =
+        preceded(pair(tag("held"), space0), parens(ident)).map(|id| Formula::Held(id.to_owned()));
+    alt((parenthesized, val, not, binary, quant, rel, is_buffer, held))(i)
 }
 
 fn formula_line(i: &str) -> Res<Logic> {
@@ -324,6 +344,8 @@ fn formula_line(i: &str) -> Res<Logic> {
         alt((
             preceded(pair(tag("assert"), space0), map(formula, Logic::Assert)),
             preceded(pair(tag("req"), space0), map(formula, Logic::Require)),
+            preceded(pair(tag("variant"), space0), map(expr, Logic::Variant)),
+            preceded(pair(tag("bound"), space0), map(num, Logic::Bound)),
         )),
     )(i)
 }
@@ -360,20 +382,64 @@ fn line(i: &str) -> Res<Line> {
     ))(i)
 }
 
+fn map_def(i: &str) -> Res<'_, MapDef> {
+    preceded(
+        tuple((tag(";#"), space0, tag("map"), space1)),
+        tuple((imm, space1, tag("value_size"), space1, num)),
+    )
+    .map(|(fd, _, _, _, value_size)| MapDef { fd, value_size })
+    .parse(i)
+}
+
+fn requirement(i: &str) -> Res<'_, Formula> {
+    preceded(tuple((tag(";#"), space0, tag("requires"), space0)), formula)(i)
+}
+
+fn ensurance(i: &str) -> Res<'_, Formula> {
+    preceded(tuple((tag(";#"), space0, tag("ensures"), space0)), formula)(i)
+}
+
+fn tail_requirement(i: &str) -> Res<'_, Formula> {
+    preceded(
+        tuple((tag(";#"), space0, tag("tail_requires"), space0)),
+        formula,
+    )(i)
+}
+
+fn function(i: &str) -> Res<'_, Function> {
+    let header = preceded(tuple((tag(";#"), space0, tag("function"), space1)), ident);
+    let components = tuple((
+        terminated(header, line_sep),
+        many0(terminated(requirement, line_sep)),
+        many0(terminated(ensurance, line_sep)),
+        preceded(space0, separated_list0(line_sep, line)),
+    ));
+    map(components, |(name, rs, es, ls)| Function {
+        name: name.to_owned(),
+        requires: rs,
+        ensures: es,
+        lines: ls,
+    })(i)
+}
+
 pub fn module(i: &str) -> Res<Module> {
-    let requirement = preceded(tuple((tag(";#"), space0, tag("requires"), space0)), formula);
-    let ensurance = preceded(tuple((tag(";#"), space0, tag("ensures"), space0)), formula);
     let components = tuple((
+        many0(terminated(map_def, line_sep)),
         many0(terminated(requirement, line_sep)),
         many0(terminated(ensurance, line_sep)),
+        many0(terminated(tail_requirement, line_sep)),
         preceded(space0, separated_list0(line_sep, line)),
+        many0(preceded(line_sep, function)),
     ));
     delimited(
         opt(line_sep),
-        map(components, |(rs, es, ls)| Module {
+        map(components, |(ms, rs, es, ts, ls, fs)| Module {
+            maps: ms,
             lines: ls,
             requires: rs,
             ensures: es,
+            tail_requires: ts,
+            functions: fs,
         }),
         pair(opt(line_sep), eof),
     )(i)
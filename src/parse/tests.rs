@@ -136,10 +136,10 @@ fn registers() {
     parses(reg, "r7", Reg::new(7).unwrap());
     parses(reg, "r8", Reg::new(8).unwrap());
     parses(reg, "r9", Reg::new(9).unwrap());
+    parses(reg, "r10", Reg::R10);
 
     rejects(reg, "r");
     rejects(reg, "rcx");
-    rejects(reg, "r10");
     rejects(reg, "r11");
     rejects(reg, "%r11");
 }
@@ -184,12 +184,12 @@ fn register_or_immediates() {
 
 #[test]
 fn memory_references() {
-    parses(mem_ref, "[r0]", MemRef(Reg::R0, None));
-    parses(mem_ref, "[ r0 ]", MemRef(Reg::R0, None));
-    parses(mem_ref, "[ r9 + 24 ]", MemRef(Reg::R9, Some(24)));
-    parses(mem_ref, "[ r5 -\t 3 ]", MemRef(Reg::R5, Some(-3)));
-    parses(mem_ref, "[ r5 -\t 0b1001001 ]", MemRef(Reg::R5, Some(-0b1001001)));
-    parses(mem_ref, "[r2+0xdeadbeef]", MemRef(Reg::R2, Some(0xdeadbeef)));
+    parses(mem_ref, "[r0]", MemRef(Reg::R0, 0));
+    parses(mem_ref, "[ r0 ]", MemRef(Reg::R0, 0));
+    parses(mem_ref, "[ r9 + 24 ]", MemRef(Reg::R9, 24));
+    parses(mem_ref, "[ r5 -\t 3 ]", MemRef(Reg::R5, -3));
+    parses(mem_ref, "[ r5 -\t 0b1001001 ]", MemRef(Reg::R5, -0b1001001));
+    parses(mem_ref, "[r2+0xdeadbeef]", MemRef(Reg::R2, 0xdeadbeef));
 
     rejects(mem_ref, "r0");
     rejects(mem_ref, "[r0");
@@ -297,27 +297,37 @@ fn binary_instrs() {
 
 #[test]
 fn load_instructions() {
-    parses(stmt, "ldxb r0  [r1]", Stmt::Load(WordSize::B8, Reg::R0, MemRef(Reg::R1, None)));
-    parses(stmt, "ldxh r0  [r1]", Stmt::Load(WordSize::B16, Reg::R0, MemRef(Reg::R1, None)));
-    parses(stmt, "ldxw r0  [r1]", Stmt::Load(WordSize::B32, Reg::R0, MemRef(Reg::R1, None)));
-    parses(stmt, "ldxdw r0 [r1]", Stmt::Load(WordSize::B64, Reg::R0, MemRef(Reg::R1, None)));
+    parses(stmt, "ldxb r0  [r1]", Stmt::Load(WordSize::B8, Reg::R0, MemRef(Reg::R1, 0)));
+    parses(stmt, "ldxh r0  [r1]", Stmt::Load(WordSize::B16, Reg::R0, MemRef(Reg::R1, 0)));
+    parses(stmt, "ldxw r0  [r1]", Stmt::Load(WordSize::B32, Reg::R0, MemRef(Reg::R1, 0)));
+    parses(stmt, "ldxdw r0 [r1]", Stmt::Load(WordSize::B64, Reg::R0, MemRef(Reg::R1, 0)));
     parses(stmt, "lddw r0, 123", Stmt::LoadImm(Reg::R0, 123));
+    parses(stmt, "ldmapfd r1, 3", Stmt::LoadMapFd(Reg::R1, 3));
+    parses(stmt, "ldfunc r2, step", Stmt::LoadFunc(Reg::R2, "step".to_owned()));
 
     rejects(stmt, "ld r0 [r1]");
     rejects(stmt, "ldx r0 [r1]");
 }
 
+#[test]
+fn call_instructions() {
+    parses(stmt, "call 1", Stmt::Call(1));
+    parses(stmt, "call double", Stmt::CallLocal("double".to_owned()));
+
+    rejects(stmt, "call");
+}
+
 #[test]
 fn store_instructions() {
-    parses(stmt, "stb  [r0] 123", Stmt::Store(WordSize::B8, MemRef(Reg::R0, None), RegImm::Imm(123)));
-    parses(stmt, "sth  [r0] 123", Stmt::Store(WordSize::B16, MemRef(Reg::R0, None), RegImm::Imm(123)));
-    parses(stmt, "stw  [r0] 123", Stmt::Store(WordSize::B32, MemRef(Reg::R0, None), RegImm::Imm(123)));
-    parses(stmt, "stdw [r0] 123", Stmt::Store(WordSize::B64, MemRef(Reg::R0, None), RegImm::Imm(123)));
+    parses(stmt, "stb  [r0] 123", Stmt::Store(WordSize::B8, MemRef(Reg::R0, 0), RegImm::Imm(123)));
+    parses(stmt, "sth  [r0] 123", Stmt::Store(WordSize::B16, MemRef(Reg::R0, 0), RegImm::Imm(123)));
+    parses(stmt, "stw  [r0] 123", Stmt::Store(WordSize::B32, MemRef(Reg::R0, 0), RegImm::Imm(123)));
+    parses(stmt, "stdw [r0] 123", Stmt::Store(WordSize::B64, MemRef(Reg::R0, 0), RegImm::Imm(123)));
 
-    parses(stmt, "stxb  [r0] r1", Stmt::Store(WordSize::B8, MemRef(Reg::R0, None), RegImm::Reg(Reg::R1)));
-    parses(stmt, "stxh  [r0] r1", Stmt::Store(WordSize::B16, MemRef(Reg::R0, None), RegImm::Reg(Reg::R1)));
-    parses(stmt, "stxw  [r0] r1", Stmt::Store(WordSize::B32, MemRef(Reg::R0, None), RegImm::Reg(Reg::R1)));
-    parses(stmt, "stxdw [r0] r1", Stmt::Store(WordSize::B64, MemRef(Reg::R0, None), RegImm::Reg(Reg::R1)));
+    parses(stmt, "stxb  [r0] r1", Stmt::Store(WordSize::B8, MemRef(Reg::R0, 0), RegImm::Reg(Reg::R1)));
+    parses(stmt, "stxh  [r0] r1", Stmt::Store(WordSize::B16, MemRef(Reg::R0, 0), RegImm::Reg(Reg::R1)));
+    parses(stmt, "stxw  [r0] r1", Stmt::Store(WordSize::B32, MemRef(Reg::R0, 0), RegImm::Reg(Reg::R1)));
+    parses(stmt, "stxdw [r0] r1", Stmt::Store(WordSize::B64, MemRef(Reg::R0, 0), RegImm::Reg(Reg::R1)));
 
     rejects(stmt, "st [r0] r1");
     rejects(stmt, "stx [r0] r1");
@@ -325,22 +335,22 @@ fn store_instructions() {
 
 #[test]
 fn jump_instructions() {
-    parses(stmt, "ja label", Stmt::Jmp("label".to_owned()));
-    parses(stmt, "jeq r0 0 l", Stmt::Jcc(Cc::Eq, Reg::R0, RegImm::Imm(0), "l".to_owned()));
-    parses(stmt, "jeq r0 r1 l", Stmt::Jcc(Cc::Eq, Reg::R0, RegImm::Reg(Reg::R1), "l".to_owned()));
-
-    parses(stmt, "jgt r0 0 l", Stmt::Jcc(Cc::Gt, Reg::R0, RegImm::Imm(0), "l".to_owned()));
-    parses(stmt, "jge r0 0 l", Stmt::Jcc(Cc::Ge, Reg::R0, RegImm::Imm(0), "l".to_owned()));
-    parses(stmt, "jlt r0 0 l", Stmt::Jcc(Cc::Lt, Reg::R0, RegImm::Imm(0), "l".to_owned()));
-    parses(stmt, "jle r0 0 l", Stmt::Jcc(Cc::Le, Reg::R0, RegImm::Imm(0), "l".to_owned()));
-    parses(stmt, "jset r0 0 l", Stmt::Jcc(Cc::Set, Reg::R0, RegImm::Imm(0), "l".to_owned()));
-    parses(stmt, "jne r0 0 l", Stmt::Jcc(Cc::Ne, Reg::R0, RegImm::Imm(0), "l".to_owned()));
-    parses(stmt, "jsgt r0 0 l", Stmt::Jcc(Cc::Sgt, Reg::R0, RegImm::Imm(0), "l".to_owned()));
-    parses(stmt, "jsge r0 0 l", Stmt::Jcc(Cc::Sge, Reg::R0, RegImm::Imm(0), "l".to_owned()));
-    parses(stmt, "jslt r0 0 l", Stmt::Jcc(Cc::Slt, Reg::R0, RegImm::Imm(0), "l".to_owned()));
-    parses(stmt, "jsle r0 0 l", Stmt::Jcc(Cc::Sle, Reg::R0, RegImm::Imm(0), "l".to_owned()));
-
-    rejects(stmt, "jeq 0 r1 l");
+    parses(cont, "ja label", Cont::Jmp("label".to_owned()));
+    parses(cont, "jeq r0 0 l", Cont::Jcc(Cc::Eq, Reg::R0, RegImm::Imm(0), "l".to_owned()));
+    parses(cont, "jeq r0 r1 l", Cont::Jcc(Cc::Eq, Reg::R0, RegImm::Reg(Reg::R1), "l".to_owned()));
+
+    parses(cont, "jgt r0 0 l", Cont::Jcc(Cc::Gt, Reg::R0, RegImm::Imm(0), "l".to_owned()));
+    parses(cont, "jge r0 0 l", Cont::Jcc(Cc::Ge, Reg::R0, RegImm::Imm(0), "l".to_owned()));
+    parses(cont, "jlt r0 0 l", Cont::Jcc(Cc::Lt, Reg::R0, RegImm::Imm(0), "l".to_owned()));
+    parses(cont, "jle r0 0 l", Cont::Jcc(Cc::Le, Reg::R0, RegImm::Imm(0), "l".to_owned()));
+    parses(cont, "jset r0 0 l", Cont::Jcc(Cc::Set, Reg::R0, RegImm::Imm(0), "l".to_owned()));
+    parses(cont, "jne r0 0 l", Cont::Jcc(Cc::Ne, Reg::R0, RegImm::Imm(0), "l".to_owned()));
+    parses(cont, "jsgt r0 0 l", Cont::Jcc(Cc::Sgt, Reg::R0, RegImm::Imm(0), "l".to_owned()));
+    parses(cont, "jsge r0 0 l", Cont::Jcc(Cc::Sge, Reg::R0, RegImm::Imm(0), "l".to_owned()));
+    parses(cont, "jslt r0 0 l", Cont::Jcc(Cc::Slt, Reg::R0, RegImm::Imm(0), "l".to_owned()));
+    parses(cont, "jsle r0 0 l", Cont::Jcc(Cc::Sle, Reg::R0, RegImm::Imm(0), "l".to_owned()));
+
+    rejects(cont, "jeq 0 r1 l");
 }
 
 #[test]
@@ -419,24 +429,57 @@ fn formulas() {
             f.binop(BinAlu::Add, f.binop(BinAlu::Mov, f.unop(UnAlu::Neg, x.clone()), z.clone()), y.clone())
         )
     );
+    parses(formula, "held(r6)", Formula::Held("r6".to_owned()));
 }
 
 #[test]
 fn assertions() {
-    let f = crate::formulaBuilder::new();
-    parses(assertion, ";# assert true", f.top());
-    parses(assertion, ";# assert false", f.bot());
+    let f = crate::formula::FormulaBuilder::new();
+    parses(formula_line, ";# assert true", Logic::Assert(f.top()));
+    parses(formula_line, ";# assert false", Logic::Assert(f.bot()));
 
     let x = f.var_ident("x".to_owned());
     let y = f.var_ident("y".to_owned());
-    parses(assertion, ";# assert x <> y", f.rel(Cc::Ne, x, y));
+    parses(formula_line, ";# assert x <> y", Logic::Assert(f.rel(Cc::Ne, x, y)));
+}
+
+#[test]
+fn variants() {
+    let f = crate::formula::FormulaBuilder::new();
+    let r1 = f.var_ident("r1".to_owned());
+    let r2 = f.var_ident("r2".to_owned());
+    parses(formula_line, ";# variant r1", Logic::Variant(r1.clone()));
+    parses(formula_line, ";# variant sub(r2, r1)", Logic::Variant(f.binop(BinAlu::Sub, r2, r1)));
+    rejects(formula_line, ";# variant");
+}
+
+#[test]
+fn bounds() {
+    parses(formula_line, ";# bound 32", Logic::Bound(32));
+    parses(formula_line, ";#bound 0x10", Logic::Bound(16));
+    rejects(formula_line, ";# bound r1");
+}
+
+#[test]
+fn tail_requirements() {
+    let f = crate::formula::FormulaBuilder::new();
+    parses(tail_requirement, ";# tail_requires true", f.top());
+    parses(tail_requirement, ";#tail_requires is_buffer(r1, 24)", f.is_buffer("r1".to_owned(), f.val(24)));
+    rejects(tail_requirement, ";# requires true");
+}
+
+#[test]
+fn map_declarations() {
+    parses(map_def, ";# map 1 value_size 16", MapDef { fd: 1, value_size: 16 });
+    parses(map_def, ";#map 3 value_size 0x40", MapDef { fd: 3, value_size: 64 });
+    rejects(map_def, ";# map 1");
+    rejects(map_def, ";# map value_size 16");
 }
 
 #[test]
 fn gcd() {
-    parses(module, include_str!("../../test_asm/exit.asm"), vec![Line::Stmtit)]);
-    accepts(module, include_str!("../../test_asm/gcd.asm"));
-    parses(
+    accepts(module, include_str!("../../samples/gcd.asm"));
+    accepts(
         module,
         "
             ;; Solution to day 1, part 1 of Advent of Code 2022.
@@ -486,39 +529,6 @@ fn gcd() {
             end:
                 exit
         ",
-        vec![
-            Line::Instr(Stmt::Binary(WordSize::B64, BinAlu::Mov, Reg::R0, RegImm::Imm(0))),
-            Line::Instr(Stmt::Binary(WordSize::B64, BinAlu::Mov, Reg::R3, RegImm::Imm(0))),
-            Line::Instr(Stmt::Binary(WordSize::B64, BinAlu::Mov, Reg::R5, RegImm::Imm(0))),
-            Line::Instr(Stmt::Binary(WordSize::B64, BinAlu::Mov, Reg::R6, RegImm::Imm(0))),
-            Line::Label("outer".to_owned()),
-            Line::Instr(Stmt::Jcc(Cc::Eq, Reg::R3, RegImm::Reg(Reg::R2), "submit".to_owned())),
-            Line::Instr(Stmt::Binary(WordSize::B64, BinAlu::Mov, Reg::R4, RegImm::Reg(Reg::R1))),
-            Line::Instr(Stmt::Binary(WordSize::B64, BinAlu::Add, Reg::R4, RegImm::Reg(Reg::R3))),
-            Line::Instr(Stmt::Load(WordSize::B8, Reg::R4, MemRef(Reg::R4, None))),
-            Line::Instr(Stmt::Binary(WordSize::B64, BinAlu::Add, Reg::R3, RegImm::Imm(1))),
-            Line::Instr(Stmt::Jcc(Cc::Eq, Reg::R4, RegImm::Imm(10), "submit".to_owned())),
-            Line::Label("inner".to_owned()),
-            Line::Instr(Stmt::Binary(WordSize::B64, BinAlu::Mul, Reg::R5, RegImm::Imm(10))),
-            Line::Instr(Stmt::Binary(WordSize::B64, BinAlu::Add, Reg::R5, RegImm::Reg(Reg::R4))),
-            Line::Instr(Stmt::Binary(WordSize::B64, BinAlu::Sub, Reg::R5, RegImm::Imm(48))),
-            Line::Instr(Stmt::Binary(WordSize::B64, BinAlu::Mov, Reg::R4, RegImm::Reg(Reg::R1))),
-            Line::Instr(Stmt::Binary(WordSize::B64, BinAlu::Add, Reg::R4, RegImm::Reg(Reg::R3))),
-            Line::Instr(Stmt::Load(WordSize::B8, Reg::R4, MemRef(Reg::R4, None))),
-            Line::Instr(Stmt::Binary(WordSize::B64, BinAlu::Add, Reg::R3, RegImm::Imm(1))),
-            Line::Instr(Stmt::Jcc(Cc::Ne, Reg::R4, RegImm::Imm(10), "inner".to_owned())),
-            Line::Instr(Stmt::Binary(WordSize::B64, BinAlu::Add, Reg::R6, RegImm::Reg(Reg::R5))),
-            Line::Instr(Stmt::Binary(WordSize::B64, BinAlu::Mov, Reg::R5, RegImm::Imm(0))),
-            Line::Instr(Stmt::Jmp("outer".to_owned())),
-            Line::Label("submit".to_owned()),
-            Line::Instr(Stmt::Jcc(Cc::Gt, Reg::R0, RegImm::Reg(Reg::R6), "skip".to_owned())),
-            Line::Instr(Stmt::Binary(WordSize::B64, BinAlu::Mov, Reg::R0, RegImm::Reg(Reg::R6))),
-            Line::Label("skip".to_owned()),
-            Line::Instr(Stmt::Binary(WordSize::B64, BinAlu::Mov, Reg::R6, RegImm::Imm(0))),
-            Line::Instr(Stmt::Jcc(Cc::Eq, Reg::R3, RegImm::Reg(Reg::R2), "end".to_owned())),
-            Line::Instr(Stmt::Jmp("outer".to_owned())),
-            Line::Label("end".to_owned()),
-            Line::Instr(Stmt        ],
     );
 }
 
@@ -0,0 +1,279 @@
+//! Program types, their contexts and their built-in contracts.
+
+use std::str::FromStr;
+
+use crate::{cfg::*, formula::FormulaBuilder};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ProgType {
+    /// A plain BPF function taking its arguments in `r1`-`r5`.
+    Function,
+    SocketFilter,
+    Xdp,
+    /// A tc classifier or action.
+    SchedCls,
+    Kprobe,
+    Tracepoint,
+}
+
+impl FromStr for ProgType {
+    type Err = &'static str;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let prog_type = match s.to_ascii_lowercase().as_str() {
+            "function" => Self::Function,
+            "socket_filter" | "socket" => Self::SocketFilter,
+            "xdp" => Self::Xdp,
+            "tc" | "classifier" | "sched_cls" => Self::SchedCls,
+            "kprobe" => Self::Kprobe,
+            "tracepoint" => Self::Tracepoint,
+            _ => return Err("unknown program type"),
+        };
+        Ok(prog_type)
+    }
+}
+
+/// What a context field holds.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FieldKind {
+    Scalar,
+    /// Pointer to the start of the packet data.
+    PacketData,
+    /// Pointer to the end of the packet data.
+    PacketEnd,
+}
+
+/// A field of a context structure.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Field {
+    pub name: &'static str,
+    pub offset: Offset,
+    pub size: i64,
+    pub kind: FieldKind,
+}
+
+const fn field(name: &'static str, offset: Offset, size: i64) -> Field {
+    ptr_field(name, offset, size, FieldKind::Scalar)
+}
+
+const fn ptr_field(name: &'static str, offset: Offset, size: i64, kind: FieldKind) -> Field {
+    Field {
+        name,
+        offset,
+        size,
+        kind,
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Layout {
+    /// Only the listed fields can be accessed, except the denied ones.
+    Fields {
+        fields: &'static [Field],
+        denied: &'static [&'static str],
+        writable: &'static [&'static str],
+    },
+    /// Any aligned read within the bounds of the context is allowed.
+    Raw,
+}
+
+/// The layout of the context that a program receives in `r1`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Ctx {
+    pub size: i64,
+    layout: Layout,
+}
+
+#[rustfmt::skip]
+const SK_BUFF: &[Field] = &[
+    field("len",             0,   4),
+    field("pkt_type",        4,   4),
+    field("mark",            8,   4),
+    field("queue_mapping",   12,  4),
+    field("protocol",        16,  4),
+    field("vlan_present",    20,  4),
+    field("vlan_tci",        24,  4),
+    field("vlan_proto",      28,  4),
+    field("priority",        32,  4),
+    field("ingress_ifindex", 36,  4),
+    field("ifindex",         40,  4),
+    field("tc_index",        44,  4),
+    field("cb",              48,  20),
+    field("hash",            68,  4),
+    field("tc_classid",      72,  4),
+    ptr_field("data",        76,  4, FieldKind::PacketData),
+    ptr_field("data_end",    80,  4, FieldKind::PacketEnd),
+    field("napi_id",         84,  4),
+    field("family",          88,  4),
+    field("remote_ip4",      92,  4),
+    field("local_ip4",       96,  4),
+    field("remote_ip6",      100, 16),
+    field("local_ip6",       116, 16),
+    field("remote_port",     132, 4),
+    field("local_port",      136, 4),
+    field("data_meta",       140, 4),
+    field("flow_keys",       144, 8),
+    field("tstamp",          152, 8),
+    field("wire_len",        160, 4),
+    field("gso_segs",        164, 4),
+    field("sk",              168, 8),
+    field("gso_size",        176, 4),
+    field("tstamp_type",     180, 1),
+    field("hwtstamp",        184, 8),
+];
+
+#[rustfmt::skip]
+const XDP_MD: &[Field] = &[
+    ptr_field("data",        0,  4, FieldKind::PacketData),
+    ptr_field("data_end",    4,  4, FieldKind::PacketEnd),
+    field("data_meta",       8,  4),
+    field("ingress_ifindex", 12, 4),
+    field("rx_queue_index",  16, 4),
+    field("egress_ifindex",  20, 4),
+];
+
+const SOCKET_FILTER_CTX: Ctx = Ctx {
+    size: 192,
+    layout: Layout::Fields {
+        fields: SK_BUFF,
+        denied: &[
+            "tc_classid",
+            "data",
+            "data_end",
+            "family",
+            "remote_ip4",
+            "local_ip4",
+            "remote_ip6",
+            "local_ip6",
+            "remote_port",
+            "local_port",
+            "data_meta",
+            "flow_keys",
+            "tstamp",
+            "wire_len",
+            "sk",
+            "hwtstamp",
+        ],
+        writable: &["cb"],
+    },
+};
+
+const SCHED_CLS_CTX: Ctx = Ctx {
+    size: 192,
+    layout: Layout::Fields {
+        fields: SK_BUFF,
+        denied: &["flow_keys"],
+        writable: &[
+            "mark",
+            "queue_mapping",
+            "priority",
+            "tc_index",
+            "cb",
+            "tc_classid",
+            "tstamp",
+        ],
+    },
+};
+
+const XDP_CTX: Ctx = Ctx {
+    size: 24,
+    layout: Layout::Fields {
+        fields: XDP_MD,
+        denied: &[],
+        writable: &[],
+    },
+};
+
+/// `struct pt_regs` on x86-64.
+const KPROBE_CTX: Ctx = Ctx {
+    size: 168,
+    layout: Layout::Raw,
+};
+
+/// Tracepoint records can be up to `PERF_MAX_TRACE_SIZE` bytes.
+const TRACEPOINT_CTX: Ctx = Ctx {
+    size: 2048,
+    layout: Layout::Raw,
+};
+
+impl Ctx {
+    /// Find the field that an access falls within.
+    pub fn field(&self, offset: Offset, size: WordSize) -> Option<Field> {
+        let end = offset + size.bytes();
+        match self.layout {
+            Layout::Raw => None,
+            Layout::Fields { fields, .. } => fields
+                .iter()
+                .find(|f| f.offset <= offset && end <= f.offset + f.size)
+                .copied(),
+        }
+    }
+
+    /// Whether an access at a constant offset is allowed.
+    pub fn allows(&self, offset: Offset, size: WordSize, write: bool) -> bool {
+        let bytes = size.bytes();
+        if offset < 0 || offset + bytes > self.size {
+            return false;
+        }
+        match self.layout {
+            Layout::Raw => !write && offset % bytes == 0,
+            Layout::Fields {
+                denied, writable, ..
+            } => match self.field(offset, size) {
+                Some(field) => {
+                    !denied.contains(&field.name) && (!write || writable.contains(&field.name))
+                }
+                None => false,
+            },
+        }
+    }
+}
+
+/// Return codes of XDP programs range from `XDP_ABORTED` to `XDP_REDIRECT`.
+const XDP_MAX_ACTION: Imm = 4;
+/// Return codes of tc programs range from `TC_ACT_OK` to `TC_ACT_TRAP`,
+/// besides `TC_ACT_UNSPEC`, which is `-1`.
+const TC_MAX_ACTION: Imm = 8;
+const TC_ACT_UNSPEC: Imm = -1;
+
+impl ProgType {
+    pub fn ctx(&self) -> Option<Ctx> {
+        match self {
+            ProgType::Function => None,
+            ProgType::SocketFilter => Some(SOCKET_FILTER_CTX),
+            ProgType::Xdp => Some(XDP_CTX),
+            ProgType::SchedCls => Some(SCHED_CLS_CTX),
+            ProgType::Kprobe => Some(KPROBE_CTX),
+            ProgType::Tracepoint => Some(TRACEPOINT_CTX),
+        }
+    }
+
+    /// Precondition that the program can assume about its context.
+    pub fn requires(&self, f: &FormulaBuilder) -> Formula {
+        match self.ctx() {
+            Some(ctx) => f.is_buffer(f.reg(Reg::R1).1, f.val(ctx.size)),
+            None => f.top(),
+        }
+    }
+
+    /// Postcondition on the return code of the program.
+    pub fn ensures(&self, f: &FormulaBuilder) -> Formula {
+        let r0 = f.reg(Reg::R0).0;
+        match self {
+            ProgType::Xdp => f.rel(Cc::Le, r0, f.val(XDP_MAX_ACTION)),
+            ProgType::SchedCls => f.or(
+                f.rel(Cc::Le, r0.clone(), f.val(TC_MAX_ACTION)),
+                f.eq(r0, f.val(TC_ACT_UNSPEC)),
+            ),
+            _ => f.top(),
+        }
+    }
+
+    /// Add the built-in contract of the program type to a CFG.
+    pub fn apply(&self, cfg: &mut Cfg, f: &FormulaBuilder) {
+        cfg.requires = f.and(self.requires(f), cfg.requires.clone());
+        cfg.ensures = f.and(cfg.ensures.clone(), self.ensures(f));
+    }
+}
+
+#[cfg(test)]
+mod tests;
@@ -0,0 +1,64 @@
+use super::*;
+
+#[test]
+fn names() {
+    assert_eq!("XDP".parse(), Ok(ProgType::Xdp));
+    assert_eq!("classifier".parse(), Ok(ProgType::SchedCls));
+    assert_eq!("socket".parse(), Ok(ProgType::SocketFilter));
+    assert!("lsm".parse::<ProgType>().is_err());
+}
+
+#[test]
+fn fields() {
+    let ctx = XDP_CTX;
+    let data_end = ctx.field(4, WordSize::B32).unwrap();
+    assert_eq!(
+        (data_end.name, data_end.kind),
+        ("data_end", FieldKind::PacketEnd)
+    );
+    // Narrow accesses fall within the field, but not ones across two of them.
+    assert_eq!(
+        ctx.field(14, WordSize::B16).unwrap().name,
+        "ingress_ifindex"
+    );
+    assert_eq!(ctx.field(2, WordSize::B32), None);
+    assert_eq!(KPROBE_CTX.field(0, WordSize::B64), None);
+}
+
+#[test]
+fn field_access() {
+    let ctx = SOCKET_FILTER_CTX;
+    assert!(ctx.allows(0, WordSize::B32, false));
+    assert!(!ctx.allows(0, WordSize::B32, true));
+    assert!(ctx.allows(48, WordSize::B32, true));
+    // Socket filters can't see the packet pointers, classifiers can.
+    assert!(!ctx.allows(76, WordSize::B32, false));
+    assert!(SCHED_CLS_CTX.allows(76, WordSize::B32, false));
+    assert!(!ctx.allows(-4, WordSize::B32, false));
+    assert!(!ctx.allows(190, WordSize::B32, false));
+}
+
+#[test]
+fn raw_access() {
+    let ctx = KPROBE_CTX;
+    assert!(ctx.allows(160, WordSize::B64, false));
+    assert!(!ctx.allows(164, WordSize::B64, false));
+    assert!(!ctx.allows(168, WordSize::B8, false));
+    assert!(!ctx.allows(0, WordSize::B64, true));
+}
+
+#[test]
+fn contracts() {
+    let f = FormulaBuilder::new();
+    let r0 = f.reg(Reg::R0).0;
+    assert_eq!(ProgType::Function.requires(&f), f.top());
+    assert_eq!(ProgType::Kprobe.ensures(&f), f.top());
+    assert_eq!(
+        ProgType::Xdp.ensures(&f),
+        f.rel(Cc::Le, r0, f.val(XDP_MAX_ACTION))
+    );
+    assert_eq!(
+        ProgType::Xdp.requires(&f),
+        f.is_buffer(f.reg(Reg::R1).1, f.val(24))
+    );
+}
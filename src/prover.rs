@@ -0,0 +1,102 @@
+//! Discharging verification conditions with an external prover through Why3.
+
+use std::{
+    collections::HashSet,
+    fmt::{self, Display, Formatter},
+    process::Command,
+    sync::atomic::{AtomicUsize, Ordering},
+};
+
+use crate::{cfg::Formula, whyml};
+
+/// Seconds that the prover may spend on each goal.
+pub const TIMEOUT: u32 = 5;
+
+#[derive(Debug)]
+pub enum ProverErr {
+    /// Why3 couldn't be run, or its input couldn't be written.
+    Io(std::io::Error),
+    /// Why3 failed without reporting on any goal, with its error output.
+    Failed(String),
+}
+
+impl Display for ProverErr {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            ProverErr::Io(e) => f.write_fmt(format_args!("Failed to run why3: {e}")),
+            ProverErr::Failed(stderr) => {
+                f.write_fmt(format_args!("Why3 failed: {}", stderr.trim()))
+            }
+        }
+    }
+}
+
+/// Something that discharges verification conditions.
+pub trait Prover {
+    /// Try to prove the goals, returning the names of the ones that are valid.
+    fn prove(&self, goals: Vec<(String, Formula)>) -> Result<HashSet<String>, ProverErr>;
+}
+
+/// A prover that Why3 knows, such as `CVC4,1.8` or `Z3`.
+pub struct Why3 {
+    pub prover: String,
+}
+
+impl Prover for Why3 {
+    fn prove(&self, goals: Vec<(String, Formula)>) -> Result<HashSet<String>, ProverErr> {
+        static FILES: AtomicUsize = AtomicUsize::new(0);
+        let n = FILES.fetch_add(1, Ordering::Relaxed);
+        let path = std::env::temp_dir().join(format!("ebpf-vc-{}-{n}.mlw", std::process::id()));
+        std::fs::write(&path, whyml::Conditions(goals).to_string()).map_err(ProverErr::Io)?;
+        let output = Command::new("why3")
+            .args(["--debug=ignore_unused_vars", "prove", "-P", &self.prover])
+            .args(["-t", &TIMEOUT.to_string()])
+            .arg(&path)
+            .output();
+        let _ = std::fs::remove_file(&path);
+        let output = output.map_err(ProverErr::Io)?;
+
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        let (valid, reported) = results(&stdout);
+        if !output.status.success() && reported == 0 {
+            let stderr = String::from_utf8_lossy(&output.stderr);
+            return Err(ProverErr::Failed(stderr.into_owned()));
+        }
+        Ok(valid)
+    }
+}
+
+/// Read the valid goals from the output of `why3 prove`, along with the number of goals reported.
+/// Results are either on the line of their goal, as in `file.mlw Top loop: Valid (0.01s)`,
+/// or on a line of their own after a `Goal loop.` line.
+fn results(output: &str) -> (HashSet<String>, usize) {
+    let mut valid = HashSet::new();
+    let mut reported = 0;
+    let mut goal = None;
+    for line in output.lines().map(str::trim) {
+        if let Some(name) = line.strip_prefix("Goal ") {
+            goal = Some(name.trim_end_matches('.').to_owned());
+            continue;
+        }
+        let (name, result) = match line.strip_prefix("Prover result is:") {
+            Some(result) => match goal.take() {
+                Some(name) => (name, result),
+                None => continue,
+            },
+            None => match line.split_once(": ") {
+                Some((left, result)) if left.split_whitespace().count() >= 3 => {
+                    (left.split_whitespace().last().unwrap().to_owned(), result)
+                }
+                _ => continue,
+            },
+        };
+        reported += 1;
+        if result.trim_start().starts_with("Valid") {
+            valid.insert(name);
+        }
+    }
+    (valid, reported)
+}
+
+#[cfg(test)]
+mod tests;
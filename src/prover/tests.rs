@@ -0,0 +1,38 @@
+use super::*;
+
+#[test]
+fn results_on_own_lines() {
+    let output = "\
+File \"/tmp/ebpf-vc-1-0.mlw\", line 5, characters 5-12:
+Goal c0_loop.
+Prover result is: Valid (0.01s, 12 steps).
+
+File \"/tmp/ebpf-vc-1-0.mlw\", line 7, characters 5-13:
+Goal c1_entry.
+Prover result is: Timeout (5.00s).
+";
+    let (valid, reported) = results(output);
+    assert_eq!(valid, HashSet::from([String::from("c0_loop")]));
+    assert_eq!(reported, 2);
+}
+
+#[test]
+fn results_on_goal_lines() {
+    let output = "\
+/tmp/ebpf-vc-1-0.mlw Top c0_loop: Valid (0.01s, 12 steps).
+/tmp/ebpf-vc-1-0.mlw Top c1_entry: Unknown (other) (0.02s).
+/tmp/ebpf-vc-1-0.mlw Top c2_outer: Valid (0.03s, 40 steps).
+";
+    let (valid, reported) = results(output);
+    assert_eq!(
+        valid,
+        HashSet::from([String::from("c0_loop"), String::from("c2_outer")])
+    );
+    assert_eq!(reported, 3);
+}
+
+#[test]
+fn results_without_goals() {
+    let output = "File \"/tmp/ebpf-vc-1-0.mlw\", line 3, characters 0-3:\nsyntax error\n";
+    assert_eq!(results(output), (HashSet::new(), 0));
+}
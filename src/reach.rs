@@ -0,0 +1,251 @@
+//! Reachability of blocks.
+//! The kernel rejects programs with instructions that no path reaches.
+//! Blocks that are only reachable through branches whose condition is constant are reported too,
+//! using constants propagated through registers.
+
+use std::fmt::{self, Display, Formatter};
+
+use crate::{
+    cfg::*,
+    dataflow::{solve, Analysis, Direction, Lattice},
+};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DeadKind {
+    /// A block that no path from the start reaches.
+    Unreachable,
+    /// A conditional jump whose condition always has the same value.
+    ConstBranch(bool),
+    /// A block that is only reachable through branches that are never taken.
+    Infeasible,
+}
+
+/// Code that is never executed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Dead {
+    pub label: Label,
+    pub site: Site,
+    pub kind: DeadKind,
+}
+
+impl Dead {
+    /// Whether the kernel rejects the program because of it.
+    pub fn is_error(&self) -> bool {
+        self.kind == DeadKind::Unreachable
+    }
+}
+
+impl Display for Dead {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        let Dead { label, site, kind } = self;
+        match kind {
+            DeadKind::Unreachable => f.write_fmt(format_args!("Block {label} is unreachable"))?,
+            DeadKind::ConstBranch(taken) => {
+                f.write_fmt(format_args!("Condition is always {taken}"))?
+            }
+            DeadKind::Infeasible => f.write_fmt(format_args!(
+                "Block {label} is only reachable through branches that are never taken"
+            ))?,
+        }
+        f.write_fmt(format_args!(" at {}", site.describe(label)))
+    }
+}
+
+/// Known values of registers at a program point.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Consts([Option<i64>; 11]);
+
+impl Lattice for Consts {
+    fn join(&mut self, other: &Consts) -> bool {
+        let mut changed = false;
+        for (a, b) in self.0.iter_mut().zip(other.0.iter()) {
+            if a.is_some() && a != b {
+                *a = None;
+                changed = true;
+            }
+        }
+        changed
+    }
+}
+
+impl Consts {
+    fn get(&self, reg: Reg) -> Option<i64> {
+        self.0[reg.get() as usize]
+    }
+
+    fn src(&self, src: &RegImm) -> Option<i64> {
+        match src {
+            RegImm::Reg(r) => self.get(*r),
+            RegImm::Imm(i) => Some(*i),
+        }
+    }
+
+    fn step(&mut self, stmt: &Stmt) {
+        let (dst, value) = match stmt {
+            Stmt::Unary(WordSize::B64, UnAlu::Neg, dst) => {
+                (*dst, self.get(*dst).map(i64::wrapping_neg))
+            }
+            Stmt::Binary(WordSize::B64, op, dst, src) => {
+                let value = match (self.get(*dst), self.src(src)) {
+                    (_, Some(b)) if *op == BinAlu::Mov => Some(b),
+                    (Some(a), Some(b)) => Some(binary(*op, a, b)),
+                    _ => None,
+                };
+                (*dst, value)
+            }
+            // 32-bit moves zero-extend the immediate.
+            Stmt::Binary(WordSize::B32, BinAlu::Mov, dst, RegImm::Imm(i)) => {
+                (*dst, Some(*i as u32 as i64))
+            }
+            Stmt::LoadImm(dst, i) => (*dst, Some(*i)),
+            Stmt::Unary(_, _, dst)
+            | Stmt::Binary(_, _, dst, _)
+            | Stmt::Load(_, dst, _)
+            | Stmt::LoadMapFd(dst, _)
+            | Stmt::LoadFunc(dst, _) => (*dst, None),
+            Stmt::Call(_) | Stmt::CallLocal(_) => {
+                for r in 0..=5 {
+                    self.0[r] = None;
+                }
+                return;
+            }
+            Stmt::Store(_, _, _) | Stmt::Assert(_) => return,
+        };
+        self.0[dst.get() as usize] = value;
+    }
+
+    /// Value of the condition of a conditional jump, if it is constant.
+    fn cond(&self, cc: Cc, lhs: Reg, rhs: &RegImm) -> Option<bool> {
+        if *rhs == RegImm::Reg(lhs) && cc != Cc::Set {
+            return Some(matches!(cc, Cc::Eq | Cc::Ge | Cc::Le | Cc::Sge | Cc::Sle));
+        }
+        let (a, b) = (self.get(lhs)?, self.src(rhs)?);
+        let (ua, ub) = (a as u64, b as u64);
+        let value = match cc {
+            Cc::Eq => a == b,
+            Cc::Ne => a != b,
+            Cc::Gt => ua > ub,
+            Cc::Ge => ua >= ub,
+            Cc::Lt => ua < ub,
+            Cc::Le => ua <= ub,
+            Cc::Set => a & b != 0,
+            Cc::Sgt => a > b,
+            Cc::Sge => a >= b,
+            Cc::Slt => a < b,
+            Cc::Sle => a <= b,
+        };
+        Some(value)
+    }
+}
+
+/// Evaluate a 64-bit operation the way the kernel does, including division by zero.
+fn binary(op: BinAlu, a: i64, b: i64) -> i64 {
+    let (ua, ub) = (a as u64, b as u64);
+    match op {
+        BinAlu::Mov => b,
+        BinAlu::Add => a.wrapping_add(b),
+        BinAlu::Sub => a.wrapping_sub(b),
+        BinAlu::Mul => a.wrapping_mul(b),
+        BinAlu::Div => ua.checked_div(ub).unwrap_or(0) as i64,
+        BinAlu::Mod => ua.checked_rem(ub).unwrap_or(ua) as i64,
+        BinAlu::And => a & b,
+        BinAlu::Or => a | b,
+        BinAlu::Xor => a ^ b,
+        BinAlu::Lsh => (ua << (ub & 63)) as i64,
+        BinAlu::Rsh => (ua >> (ub & 63)) as i64,
+        BinAlu::Arsh => a >> (ub & 63),
+    }
+}
+
+/// Targets of a continuation that might be taken given the values at the end of a block,
+/// along with the value of its condition if it is constant.
+fn feasible<'a>(next: &'a Continuation, consts: &Consts) -> (Vec<&'a Label>, Option<bool>) {
+    match next {
+        Continuation::Jcc(cc, lhs, rhs, target_t, target_f) => match consts.cond(*cc, *lhs, rhs) {
+            Some(true) => (vec![target_t], Some(true)),
+            Some(false) => (vec![target_f], Some(false)),
+            None => (vec![target_t, target_f], None),
+        },
+        next => (next.targets(), None),
+    }
+}
+
+/// The first place of a block, used to locate it.
+fn first_site(block: &Block) -> Site {
+    match block.body.first() {
+        Some(stmt) => Site::Stmt(0, stmt.clone()),
+        None => Site::Cont(block.next.clone()),
+    }
+}
+
+/// Constant propagation along the branches that might be taken,
+/// where blocks that no such branch reaches keep `None`.
+struct ConstProp;
+
+impl Analysis for ConstProp {
+    type Fact = Option<Consts>;
+    const DIRECTION: Direction = Direction::Forward;
+
+    fn bottom(&self, _cfg: &Cfg) -> Option<Consts> {
+        None
+    }
+
+    fn boundary(&self, _cfg: &Cfg) -> Option<Consts> {
+        Some(Consts([None; 11]))
+    }
+
+    fn transfer(&self, _label: &Label, _index: usize, stmt: &Stmt, fact: &mut Option<Consts>) {
+        if let Some(consts) = fact {
+            consts.step(stmt);
+        }
+    }
+
+    fn edge(&self, _label: &Label, next: &Continuation, target: &Label, fact: &mut Option<Consts>) {
+        if let Some(consts) = fact {
+            if !feasible(next, consts).0.contains(&target) {
+                *fact = None;
+            }
+        }
+    }
+}
+
+/// Find all blocks that are never executed and branches that always go the same way.
+pub fn dead_code(cfg: &Cfg) -> Vec<Dead> {
+    let reachable = cfg.reachable();
+    let mut result: Vec<Dead> = cfg
+        .blocks
+        .iter()
+        .filter(|(label, _)| !reachable.contains(label))
+        .map(|(label, block)| Dead {
+            label: label.clone(),
+            site: first_site(block),
+            kind: DeadKind::Unreachable,
+        })
+        .collect();
+
+    let consts = solve(cfg, &ConstProp);
+    for label in consts.labels() {
+        let block = &cfg.blocks[label];
+        let kind = match consts.exit(label) {
+            Some(Some(exit)) => match feasible(&block.next, exit) {
+                (_, Some(taken)) => DeadKind::ConstBranch(taken),
+                (_, None) => continue,
+            },
+            _ => DeadKind::Infeasible,
+        };
+        let site = match kind {
+            DeadKind::Infeasible => first_site(block),
+            _ => Site::Cont(block.next.clone()),
+        };
+        result.push(Dead {
+            label: label.clone(),
+            site,
+            kind,
+        });
+    }
+    result.sort_by(|a, b| a.label.cmp(&b.label));
+    result
+}
+
+#[cfg(test)]
+mod tests;
@@ -0,0 +1,77 @@
+use super::*;
+
+fn dead(src: &str) -> Vec<(Label, DeadKind)> {
+    dead_code(&Cfg::parse(src))
+        .into_iter()
+        .map(|d| (d.label, d.kind))
+        .collect()
+}
+
+#[test]
+fn unreachable() {
+    let src = "mov r0 0\nexit\nskipped:\nmov r0 1\nexit\n";
+    assert_eq!(
+        dead(src),
+        vec![("skipped".to_owned(), DeadKind::Unreachable)]
+    );
+    assert!(dead_code(&Cfg::parse(src))[0].is_error());
+}
+
+#[test]
+fn constant_branches() {
+    let src = "\
+mov r1 4
+add r1 2
+jeq r1 6 six
+mov r0 1
+exit
+six:
+mov r0 0
+exit
+";
+    assert_eq!(
+        dead(src),
+        vec![
+            ("@0".to_owned(), DeadKind::ConstBranch(true)),
+            ("@1".to_owned(), DeadKind::Infeasible),
+        ]
+    );
+    assert!(dead_code(&Cfg::parse(src)).iter().all(|d| !d.is_error()));
+}
+
+#[test]
+fn joins() {
+    // The value of r2 differs between the branches, so the second jump is unknown.
+    let src = "\
+mov r2 1
+jeq r1 0 zero
+mov r2 2
+zero:
+jeq r2 1 one
+mov r0 1
+exit
+one:
+mov r0 0
+exit
+";
+    assert!(dead(src).is_empty());
+    // Calls clobber the argument registers.
+    let src = "mov r1 0\ncall 7\njeq r1 0 zero\nmov r0 1\nexit\nzero:\nmov r0 0\nexit\n";
+    assert!(dead(src).is_empty());
+}
+
+#[test]
+fn loops() {
+    // The constant holds on entry but not once the loop goes round.
+    let src = "\
+mov r1 0
+loop:
+jgt r1 10 end
+add r1 1
+ja loop
+end:
+mov r0 0
+exit
+";
+    assert!(dead(src).is_empty());
+}
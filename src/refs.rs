@@ -0,0 +1,349 @@
+//! Reference tracking of acquired resources.
+//! References returned by helpers such as `sk_lookup_tcp` or `ringbuf_reserve` must be released
+//! exactly once on every path before the program exits, and spin locks must be unlocked.
+//! References are only tracked in registers, not through stack spills.
+
+use std::{
+    collections::HashSet,
+    fmt::{self, Display, Formatter},
+};
+
+use crate::{
+    cfg::*,
+    dataflow::{solve, Analysis, Direction, Lattice},
+    formula::FormulaBuilder,
+    helpers::{helper_by_id, RefEffect},
+};
+
+/// Where a reference comes from.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum Origin {
+    /// The helper call at an index of a block.
+    Call(Label, usize),
+    /// A register with a `held` annotation at the start of a block.
+    Carried(Label, Reg),
+}
+
+impl Display for Origin {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            Origin::Call(label, index) => f.write_fmt(format_args!("acquired at {label}:{index}")),
+            Origin::Carried(label, reg) => {
+                f.write_fmt(format_args!("held by r{} at {label}", reg.get()))
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RefErrKind {
+    /// A reference that might not be released before exiting or being acquired again.
+    Unreleased(Origin),
+    /// Release of a reference that might already have been released.
+    DoubleRelease(Reg, Origin),
+    /// Release of a register that doesn't hold a reference.
+    InvalidRelease(Reg),
+    /// A spin lock that might not be unlocked before exiting.
+    LockHeld,
+    /// Unlocking a spin lock that might not be held.
+    DoubleUnlock,
+    /// Taking a spin lock while one might already be held.
+    NestedLock,
+    /// A `held` annotation on a register that might not hold a reference.
+    NotHeld(Reg),
+}
+
+/// A misuse of acquired resources.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RefErr {
+    pub label: Label,
+    pub site: Site,
+    pub kind: RefErrKind,
+}
+
+impl Display for RefErr {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        let RefErr { label, site, kind } = self;
+        match kind {
+            RefErrKind::Unreleased(origin) => {
+                f.write_fmt(format_args!("Reference {origin} might not be released"))?
+            }
+            RefErrKind::DoubleRelease(reg, origin) => f.write_fmt(format_args!(
+                "Release of r{} might release reference {origin} twice",
+                reg.get()
+            ))?,
+            RefErrKind::InvalidRelease(reg) => f.write_fmt(format_args!(
+                "Release of r{}, which doesn't hold a reference",
+                reg.get()
+            ))?,
+            RefErrKind::LockHeld => f.write_str("Spin lock might not be unlocked")?,
+            RefErrKind::DoubleUnlock => {
+                f.write_str("Unlock of a spin lock that might not be held")?
+            }
+            RefErrKind::NestedLock => f.write_str("Spin lock taken while one might be held")?,
+            RefErrKind::NotHeld(reg) => f.write_fmt(format_args!(
+                "Annotation held(r{}) might not hold",
+                reg.get()
+            ))?,
+        }
+        f.write_fmt(format_args!(" by {}", site.describe(label)))
+    }
+}
+
+/// Held references and locks at a program point.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct State {
+    /// The reference that each register holds, if it is the same on every path.
+    regs: [Option<Origin>; 11],
+    /// References held on some path.
+    may_hold: HashSet<Origin>,
+    /// References held on every path.
+    must_hold: HashSet<Origin>,
+    may_lock: bool,
+    must_lock: bool,
+}
+
+impl State {
+    fn entry() -> Self {
+        Self {
+            regs: Default::default(),
+            may_hold: HashSet::new(),
+            must_hold: HashSet::new(),
+            may_lock: false,
+            must_lock: false,
+        }
+    }
+
+    fn reg(&self, reg: Reg) -> Option<&Origin> {
+        self.regs[reg.get() as usize].as_ref()
+    }
+
+    fn set_reg(&mut self, reg: Reg, origin: Option<Origin>) {
+        self.regs[reg.get() as usize] = origin;
+    }
+
+    /// Forget a reference after it has been released.
+    /// Registers keep referring to it so that releasing it again can be reported.
+    fn drop_ref(&mut self, origin: &Origin) {
+        self.may_hold.remove(origin);
+        self.must_hold.remove(origin);
+    }
+
+    /// Apply the statement at `index` of a block, returning the errors that it causes.
+    fn step(&mut self, label: &Label, index: usize, stmt: &Stmt) -> Vec<RefErrKind> {
+        let mut errs = Vec::new();
+        match stmt {
+            Stmt::Binary(WordSize::B64, BinAlu::Mov, dst, RegImm::Reg(src)) => {
+                self.set_reg(*dst, self.reg(*src).cloned());
+            }
+            Stmt::Unary(_, _, dst)
+            | Stmt::Binary(_, _, dst, _)
+            | Stmt::Load(_, dst, _)
+            | Stmt::LoadImm(dst, _)
+            | Stmt::LoadMapFd(dst, _)
+            | Stmt::LoadFunc(dst, _) => self.set_reg(*dst, None),
+            Stmt::Call(id) => {
+                let helper = helper_by_id(*id);
+                // Nothing may be held when control can leave the program.
+                if helper.exits {
+                    errs.extend(self.exit());
+                }
+                let refs = helper.refs;
+                match refs {
+                    RefEffect::None => (),
+                    RefEffect::Acquire => {
+                        let origin = Origin::Call(label.clone(), index);
+                        if self.may_hold.contains(&origin) {
+                            errs.push(RefErrKind::Unreleased(origin.clone()));
+                        }
+                        self.may_hold.insert(origin.clone());
+                        self.must_hold.insert(origin);
+                    }
+                    RefEffect::Release => match self.reg(Reg::R1).cloned() {
+                        Some(origin) => {
+                            if !self.must_hold.contains(&origin) {
+                                errs.push(RefErrKind::DoubleRelease(Reg::R1, origin.clone()));
+                            }
+                            self.drop_ref(&origin);
+                        }
+                        None => errs.push(RefErrKind::InvalidRelease(Reg::R1)),
+                    },
+                    RefEffect::Lock => {
+                        if self.may_lock {
+                            errs.push(RefErrKind::NestedLock);
+                        }
+                        self.may_lock = true;
+                        self.must_lock = true;
+                    }
+                    RefEffect::Unlock => {
+                        if !self.must_lock {
+                            errs.push(RefErrKind::DoubleUnlock);
+                        }
+                        self.may_lock = false;
+                        self.must_lock = false;
+                    }
+                }
+                // Caller-saved registers are clobbered by helpers.
+                for r in [Reg::R1, Reg::R2, Reg::R3, Reg::R4, Reg::R5] {
+                    self.set_reg(r, None);
+                }
+                let acquired = refs == RefEffect::Acquire;
+                self.set_reg(
+                    Reg::R0,
+                    acquired.then(|| Origin::Call(label.clone(), index)),
+                );
+            }
+            Stmt::CallLocal(_) => {
+                for r in [Reg::R0, Reg::R1, Reg::R2, Reg::R3, Reg::R4, Reg::R5] {
+                    self.set_reg(r, None);
+                }
+            }
+            Stmt::Store(_, _, _) | Stmt::Assert(_) => (),
+        }
+        errs
+    }
+
+    /// Errors caused by exiting in this state.
+    fn exit(&self) -> Vec<RefErrKind> {
+        let mut errs: Vec<RefErrKind> = self
+            .may_hold
+            .iter()
+            .map(|o| RefErrKind::Unreleased(o.clone()))
+            .collect();
+        if self.may_lock {
+            errs.push(RefErrKind::LockHeld);
+        }
+        errs
+    }
+
+    /// State on the edge from a block to one of its targets,
+    /// along with any `held` annotations of the target that it violates.
+    /// Comparing a nullable reference against 0 means that nothing is held on the null edge.
+    fn branch(&self, cfg: &Cfg, next: &Continuation, target: &Label) -> (State, Vec<RefErrKind>) {
+        let mut state = self.clone();
+        if let Continuation::Jcc(cc @ (Cc::Eq | Cc::Ne), reg, RegImm::Imm(0), target_t, target_f) =
+            next
+        {
+            let null = (*cc == Cc::Eq) == (target == target_t);
+            if let (Some(origin), true, true) = (self.reg(*reg), target_t != target_f, null) {
+                state.drop_ref(origin);
+            }
+        }
+
+        // Generalize annotated references so that loops can carry them.
+        let mut errs = Vec::new();
+        for reg in cfg.blocks[target].require.iter().flat_map(held_regs) {
+            match state.reg(reg).cloned() {
+                Some(origin) if state.must_hold.contains(&origin) => {
+                    let carried = Origin::Carried(target.clone(), reg);
+                    for r in state.regs.iter_mut() {
+                        if r.as_ref() == Some(&origin) {
+                            *r = Some(carried.clone());
+                        }
+                    }
+                    state.may_hold.remove(&origin);
+                    state.must_hold.remove(&origin);
+                    state.may_hold.insert(carried.clone());
+                    state.must_hold.insert(carried);
+                }
+                _ => errs.push(RefErrKind::NotHeld(reg)),
+            }
+        }
+        (state, errs)
+    }
+}
+
+impl Lattice for State {
+    fn join(&mut self, other: &Self) -> bool {
+        let prev = self.clone();
+        for (a, b) in self.regs.iter_mut().zip(other.regs.iter()) {
+            if a != b {
+                *a = None;
+            }
+        }
+        self.may_hold.extend(other.may_hold.iter().cloned());
+        self.must_hold.retain(|o| other.must_hold.contains(o));
+        self.may_lock |= other.may_lock;
+        self.must_lock &= other.must_lock;
+        *self != prev
+    }
+}
+
+/// Registers that a formula annotates as holding references.
+fn held_regs(f: &Formula) -> Vec<Reg> {
+    match f {
+        Formula::Held(reg) => FormulaBuilder::new().reg_of(reg).into_iter().collect(),
+        Formula::Not(inner) | Formula::Quant(_, _, inner) => held_regs(inner),
+        Formula::Bin(_, fs) => {
+            let mut regs = held_regs(&fs.0);
+            regs.extend(held_regs(&fs.1));
+            regs
+        }
+        Formula::Val(_) | Formula::Rel(_, _, _) | Formula::IsBuffer(_, _) => vec![],
+    }
+}
+
+struct Refs<'a>(&'a Cfg);
+
+impl Analysis for Refs<'_> {
+    type Fact = Option<State>;
+    const DIRECTION: Direction = Direction::Forward;
+
+    fn bottom(&self, _cfg: &Cfg) -> Self::Fact {
+        None
+    }
+
+    fn boundary(&self, _cfg: &Cfg) -> Self::Fact {
+        Some(State::entry())
+    }
+
+    fn transfer(&self, label: &Label, index: usize, stmt: &Stmt, fact: &mut Self::Fact) {
+        if let Some(state) = fact {
+            state.step(label, index, stmt);
+        }
+    }
+
+    fn edge(&self, _label: &Label, next: &Continuation, target: &Label, fact: &mut Self::Fact) {
+        if let Some(state) = fact {
+            *state = state.branch(self.0, next, target).0;
+        }
+    }
+}
+
+/// Find all places where references or locks might be leaked or released twice.
+pub fn ref_errors(cfg: &Cfg) -> Vec<RefErr> {
+    let analysis = Refs(cfg);
+    let results = solve(cfg, &analysis);
+    let mut result = Vec::new();
+    for label in results.labels() {
+        let Some(mut state) = results.entry(label).cloned().flatten() else {
+            continue;
+        };
+        let block = &cfg.blocks[label];
+        let mut report = |site: Site, errs: Vec<RefErrKind>| {
+            result.extend(errs.into_iter().map(|kind| RefErr {
+                label: label.clone(),
+                site: site.clone(),
+                kind,
+            }));
+        };
+        for (index, stmt) in block.body.iter().enumerate() {
+            let errs = state.step(label, index, stmt);
+            report(Site::Stmt(index, stmt.clone()), errs);
+        }
+        let site = Site::Cont(block.next.clone());
+        match &block.next {
+            Continuation::Exit => report(site, state.exit()),
+            next => {
+                for target in next.targets() {
+                    report(site.clone(), state.branch(cfg, next, target).1);
+                }
+            }
+        }
+    }
+    result.sort_by(|a, b| a.label.cmp(&b.label));
+    result
+}
+
+#[cfg(test)]
+mod tests;
@@ -0,0 +1,115 @@
+use super::*;
+
+fn errors(src: &str) -> Vec<RefErrKind> {
+    let cfg = Cfg::parse(src);
+    ref_errors(&cfg).into_iter().map(|e| e.kind).collect()
+}
+
+/// The reference acquired by the call at an index of the first block.
+fn call(index: usize) -> Origin {
+    Origin::Call("@0".to_owned(), index)
+}
+
+#[test]
+fn released() {
+    // Nothing is held on the null edge of the check.
+    let src = "\
+call 84
+jeq r0 0 out
+mov r1 r0
+call 86
+out:
+mov r0 0
+exit
+";
+    assert!(errors(src).is_empty());
+}
+
+#[test]
+fn unreleased() {
+    let src = "call 84\nmov r0 0\nexit\n";
+    assert_eq!(errors(src), vec![RefErrKind::Unreleased(call(0))]);
+}
+
+#[test]
+fn released_twice() {
+    let src = "\
+call 131
+jeq r0 0 out
+mov r6 r0
+mov r1 r6
+call 132
+mov r1 r6
+call 133
+out:
+mov r0 0
+exit
+";
+    assert_eq!(
+        errors(src),
+        vec![RefErrKind::DoubleRelease(Reg::R1, call(0))]
+    );
+    assert_eq!(
+        errors("mov r1 0\ncall 86\nexit\n"),
+        vec![RefErrKind::InvalidRelease(Reg::R1)]
+    );
+}
+
+#[test]
+fn locks() {
+    assert!(errors("call 93\ncall 94\nmov r0 0\nexit\n").is_empty());
+    assert_eq!(
+        errors("call 93\nmov r0 0\nexit\n"),
+        vec![RefErrKind::LockHeld]
+    );
+    assert_eq!(
+        errors("call 93\ncall 93\ncall 94\nmov r0 0\nexit\n"),
+        vec![RefErrKind::NestedLock]
+    );
+    assert_eq!(
+        errors("call 94\nmov r0 0\nexit\n"),
+        vec![RefErrKind::DoubleUnlock]
+    );
+}
+
+#[test]
+fn carried() {
+    // A loop may hold a reference across iterations if its header says so.
+    let src = "\
+call 84
+jeq r0 0 out
+mov r6 r0
+mov r7 0
+loop:
+;# req held(r6)
+add r7 1
+jlt r7 10 loop
+mov r1 r6
+call 86
+out:
+mov r0 0
+exit
+";
+    assert!(errors(src).is_empty());
+    let missing = src.replace("mov r6 r0\n", "mov r6 0\n");
+    assert!(errors(&missing).contains(&RefErrKind::NotHeld(Reg::R6)));
+}
+
+#[test]
+fn tail_calls() {
+    // A successful tail call leaves the program with the reference still held.
+    let src = "\
+call 84
+mov r6 r0
+ldmapfd r2 0
+mov r3 0
+call 12
+jeq r6 0 out
+mov r1 r6
+call 86
+out:
+mov r0 0
+exit
+";
+    assert_eq!(errors(src), vec![RefErrKind::Unreleased(call(0))]);
+}
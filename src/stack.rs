@@ -0,0 +1,204 @@
+//! Analysis of stack initialization.
+//! The kernel rejects reads of stack bytes that haven't been written beforehand,
+//! so every load from the frame must only touch bytes that are initialized on all paths.
+
+use std::{
+    collections::HashMap,
+    fmt::{self, Display, Formatter},
+};
+
+use crate::{
+    cfg::*,
+    dataflow::{solve, Analysis, Direction, Lattice},
+    helpers::{helper_by_id, Written},
+    types::{RegType, RegTypes, Region, TypeInfo},
+    vc::STACK_SIZE,
+};
+
+/// A read of stack memory that might not have been initialized.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UninitRead {
+    pub label: Label,
+    pub index: usize,
+    pub stmt: Stmt,
+    /// Offsets from `r10` of the bytes that might be uninitialized.
+    pub offsets: Vec<Offset>,
+    /// Whether the offset of the read isn't known,
+    /// so that it might touch any of the uninitialized bytes of the frame.
+    pub variable: bool,
+}
+
+impl Display for UninitRead {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        let UninitRead {
+            label,
+            index,
+            stmt,
+            offsets,
+            variable,
+        } = self;
+        if *variable {
+            return f.write_fmt(format_args!(
+                "Read of stack at a variable offset, which might be uninitialized, by {stmt:?} ({label}:{index})"
+            ));
+        }
+        let offsets = offsets
+            .iter()
+            .map(|o| format!("{o}"))
+            .collect::<Vec<_>>()
+            .join(", ");
+        f.write_fmt(format_args!(
+            "Read of uninitialized stack at r10 offsets [{offsets}] by {stmt:?} ({label}:{index})"
+        ))
+    }
+}
+
+/// Whether each byte of the frame is initialized, indexed by offset from `r10 - STACK_SIZE`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct State {
+    init: [bool; STACK_SIZE as usize],
+}
+
+/// Uninitialized bytes that a statement reads, and whether their offset is known.
+type Uninit = (Vec<Offset>, bool);
+
+impl State {
+    fn entry() -> Self {
+        Self {
+            init: [false; STACK_SIZE as usize],
+        }
+    }
+
+    fn initialize(&mut self, start: Offset, end: Offset) {
+        for o in start..end {
+            if let Some(byte) = frame_index(o) {
+                self.init[byte] = true;
+            }
+        }
+    }
+
+    /// Apply the statement at `index` of a block to the state, given the register types before it.
+    /// Returns the uninitialized bytes that it might read.
+    fn step(&mut self, block: &Block, index: usize, types: &RegTypes) -> Option<Uninit> {
+        match &block.body[index] {
+            Stmt::Store(size, mem_ref, _) => {
+                if let Some(start) = types.stack_offset(mem_ref) {
+                    self.initialize(start, start + size.bytes());
+                }
+            }
+            Stmt::Load(size, _, mem_ref) => {
+                if let Some(start) = types.stack_offset(mem_ref) {
+                    let offsets: Vec<Offset> = (start..start + size.bytes())
+                        .filter(|o| frame_index(*o).is_some_and(|byte| !self.init[byte]))
+                        .collect();
+                    return (!offsets.is_empty()).then_some((offsets, false));
+                }
+                // The read might be anywhere in the frame.
+                if types.get(mem_ref.0) == RegType::Ptr(Region::Stack, None) {
+                    let offsets: Vec<Offset> = (-STACK_SIZE..0)
+                        .filter(|o| !self.init[(o + STACK_SIZE) as usize])
+                        .collect();
+                    return (!offsets.is_empty()).then_some((offsets, true));
+                }
+            }
+            Stmt::Call(id) => {
+                // Helpers may fill in stack buffers of constant size.
+                if let Some(Written { ptr, size }) = helper_by_id(*id).writes {
+                    let start = match types.get(ptr) {
+                        RegType::Ptr(Region::Stack, o) => o,
+                        _ => None,
+                    };
+                    if let (Some(o), Some(n)) = (start, block.constant(size, index)) {
+                        if n > 0 {
+                            self.initialize(o, o.saturating_add(n));
+                        }
+                    }
+                }
+            }
+            // Subprograms have frames of their own, so they leave this one untouched.
+            _ => (),
+        }
+        None
+    }
+}
+
+impl Lattice for State {
+    fn join(&mut self, other: &Self) -> bool {
+        let prev = self.clone();
+        for (a, b) in self.init.iter_mut().zip(other.init.iter()) {
+            *a &= b;
+        }
+        *self != prev
+    }
+}
+
+fn frame_index(offset: Offset) -> Option<usize> {
+    if (-STACK_SIZE..0).contains(&offset) {
+        Some((offset + STACK_SIZE) as usize)
+    } else {
+        None
+    }
+}
+
+struct StackInit<'a> {
+    cfg: &'a Cfg,
+    /// Types before each statement of the reachable blocks.
+    types: HashMap<Label, Vec<RegTypes>>,
+}
+
+impl Analysis for StackInit<'_> {
+    type Fact = Option<State>;
+    const DIRECTION: Direction = Direction::Forward;
+
+    fn bottom(&self, _cfg: &Cfg) -> Self::Fact {
+        None
+    }
+
+    fn boundary(&self, _cfg: &Cfg) -> Self::Fact {
+        Some(State::entry())
+    }
+
+    fn transfer(&self, label: &Label, index: usize, _stmt: &Stmt, fact: &mut Self::Fact) {
+        if let (Some(state), Some(types)) = (fact, self.types.get(label)) {
+            state.step(&self.cfg.blocks[label], index, &types[index]);
+        }
+    }
+}
+
+/// Find all loads from the stack frame that might read uninitialized bytes,
+/// following stack pointers through the registers and spill slots that the types track.
+/// Out-of-frame accesses are left to the verification conditions.
+pub fn uninit_reads(cfg: &Cfg, types: &TypeInfo) -> Vec<UninitRead> {
+    let types = types
+        .reachable()
+        .map(|l| (l.clone(), types.stmt_types(l, &cfg.blocks[l])))
+        .collect();
+    let analysis = StackInit { cfg, types };
+    let results = solve(cfg, &analysis);
+    let mut result = Vec::new();
+    for label in results.labels() {
+        let (Some(mut state), Some(types)) = (
+            results.entry(label).cloned().flatten(),
+            analysis.types.get(label),
+        ) else {
+            continue;
+        };
+        let block = &cfg.blocks[label];
+        for (index, stmt) in block.body.iter().enumerate() {
+            if let Some((offsets, variable)) = state.step(block, index, &types[index]) {
+                result.push(UninitRead {
+                    label: label.clone(),
+                    index,
+                    stmt: stmt.clone(),
+                    offsets,
+                    variable,
+                });
+            }
+        }
+    }
+    result.sort_by(|a, b| (&a.label, a.index).cmp(&(&b.label, b.index)));
+    result
+}
+
+#[cfg(test)]
+mod tests;
@@ -0,0 +1,79 @@
+use std::collections::HashSet;
+
+use super::*;
+use crate::prog::ProgType;
+
+/// The offsets of the uninitialized reads of a program, and whether they are variable.
+fn reads(src: &str) -> Vec<(usize, Vec<Offset>, bool)> {
+    let cfg = Cfg::parse(src);
+    let prog = ProgType::Function;
+    let init = HashSet::from([Reg::R1, Reg::R10]);
+    let types = TypeInfo::infer(&cfg, RegTypes::entry(&cfg, &init, prog), prog);
+    uninit_reads(&cfg, &types)
+        .into_iter()
+        .map(|r| (r.index, r.offsets, r.variable))
+        .collect()
+}
+
+#[test]
+fn initialized() {
+    let src = "stxdw [r10 - 8] r1\nldxdw r0 [r10 - 8]\nexit\n";
+    assert!(reads(src).is_empty());
+}
+
+#[test]
+fn partially_initialized() {
+    let src = "stw [r10 - 8] 0\nldxdw r0 [r10 - 8]\nexit\n";
+    assert_eq!(reads(src), vec![(1, vec![-4, -3, -2, -1], false)]);
+}
+
+#[test]
+fn copied_pointer() {
+    let src = "mov r2 r10\nadd r2 -8\nstxdw [r2] r1\nldxdw r0 [r10 - 8]\nldxb r0 [r2 - 1]\nexit\n";
+    assert_eq!(reads(src), vec![(4, vec![-9], false)]);
+}
+
+#[test]
+fn spilled_pointer() {
+    // The pointer reloaded from the spill slot still points to the top of the frame.
+    let src = "\
+mov r1 r10
+stxdw [r10 - 8] r1
+ldxdw r2 [r10 - 8]
+ldxdw r0 [r2 - 16]
+exit
+";
+    assert_eq!(reads(src), vec![(3, (-16..-8).collect(), false)]);
+}
+
+#[test]
+fn variable_offset() {
+    let src = "stxdw [r10 - 8] r1\nmov r2 r10\nadd r2 r1\nldxb r0 [r2]\nexit\n";
+    let reads = reads(src);
+    assert_eq!(reads.len(), 1);
+    let (index, offsets, variable) = &reads[0];
+    assert_eq!((*index, offsets.len(), *variable), (3, 504, true));
+}
+
+#[test]
+fn one_path() {
+    let src = "\
+jeq r1 0 skip
+stxdw [r10 - 8] r1
+skip:
+ldxdw r0 [r10 - 8]
+exit
+";
+    assert_eq!(reads(src), vec![(0, (-8..0).collect(), false)]);
+}
+
+#[test]
+fn helper_writes() {
+    // probe_read fills in the buffer in r1 with as many bytes as r2 holds.
+    let constant =
+        "mov r1 r10\nadd r1 -16\nmov r2 16\nmov r3 0\ncall 4\nldxdw r0 [r10 - 16]\nexit\n";
+    assert!(reads(constant).is_empty());
+    let variable =
+        "mov r2 r1\nmov r1 r10\nadd r1 -16\nmov r3 0\ncall 4\nldxdw r0 [r10 - 16]\nexit\n";
+    assert_eq!(reads(variable), vec![(5, (-16..-8).collect(), false)]);
+}
@@ -0,0 +1,227 @@
+//! Tristate numbers, which track the bits of a value that are known, as the kernel verifier does.
+//! Each bit is either known to be the one in `value`, or unknown if it is set in `mask`.
+
+use std::{
+    fmt::{self, Display, Formatter},
+    ops::{Add, Mul, Sub},
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Tnum {
+    pub value: u64,
+    pub mask: u64,
+}
+
+impl Tnum {
+    pub const UNKNOWN: Tnum = Tnum {
+        value: 0,
+        mask: u64::MAX,
+    };
+
+    pub fn constant(value: u64) -> Self {
+        Tnum { value, mask: 0 }
+    }
+
+    /// The values between `min` and `max`, which share the bits above the highest one that differs.
+    pub fn range(min: u64, max: u64) -> Self {
+        let bits = 64 - (min ^ max).leading_zeros();
+        if bits > 63 {
+            return Tnum::UNKNOWN;
+        }
+        let delta = (1u64 << bits) - 1;
+        Tnum {
+            value: min & !delta,
+            mask: delta,
+        }
+    }
+
+    pub fn is_const(&self) -> bool {
+        self.mask == 0
+    }
+
+    /// The smallest and largest values.
+    pub fn min(&self) -> u64 {
+        self.value
+    }
+
+    pub fn max(&self) -> u64 {
+        self.value | self.mask
+    }
+
+    /// Whether every value of `other` is a value of this one.
+    pub fn contains(&self, other: &Tnum) -> bool {
+        other.mask & !self.mask == 0 && other.value & !self.mask == self.value
+    }
+
+    pub fn lshift(self, shift: u32) -> Self {
+        Tnum {
+            value: self.value.wrapping_shl(shift),
+            mask: self.mask.wrapping_shl(shift),
+        }
+    }
+
+    pub fn rshift(self, shift: u32) -> Self {
+        Tnum {
+            value: self.value.wrapping_shr(shift),
+            mask: self.mask.wrapping_shr(shift),
+        }
+    }
+
+    /// Shift right, copying the sign bit of a word of `bits` bits.
+    pub fn arshift(self, shift: u32, bits: u32) -> Self {
+        match bits {
+            32 => Tnum {
+                value: ((self.value as i32).wrapping_shr(shift) as u32) as u64,
+                mask: ((self.mask as i32).wrapping_shr(shift) as u32) as u64,
+            },
+            _ => Tnum {
+                value: (self.value as i64).wrapping_shr(shift) as u64,
+                mask: (self.mask as i64).wrapping_shr(shift) as u64,
+            },
+        }
+    }
+
+    pub fn and(self, other: Tnum) -> Self {
+        let alpha = self.value | self.mask;
+        let beta = other.value | other.mask;
+        let value = self.value & other.value;
+        Tnum {
+            value,
+            mask: alpha & beta & !value,
+        }
+    }
+
+    pub fn or(self, other: Tnum) -> Self {
+        let value = self.value | other.value;
+        let mu = self.mask | other.mask;
+        Tnum {
+            value,
+            mask: mu & !value,
+        }
+    }
+
+    pub fn xor(self, other: Tnum) -> Self {
+        let value = self.value ^ other.value;
+        let mu = self.mask | other.mask;
+        Tnum {
+            value: value & !mu,
+            mask: mu,
+        }
+    }
+
+    /// The values that both have, assuming that they have some.
+    pub fn intersect(self, other: Tnum) -> Self {
+        let value = self.value | other.value;
+        let mu = self.mask & other.mask;
+        Tnum {
+            value: value & !mu,
+            mask: mu,
+        }
+    }
+
+    /// The values that either has.
+    pub fn union(self, other: Tnum) -> Self {
+        let mu = self.mask | other.mask | (self.value ^ other.value);
+        Tnum {
+            value: self.value & !mu,
+            mask: mu,
+        }
+    }
+
+    /// Truncate to the lower `bytes` bytes.
+    pub fn cast(self, bytes: u32) -> Self {
+        let keep = u64::MAX.checked_shr(64 - 8 * bytes).unwrap_or(0);
+        Tnum {
+            value: self.value & keep,
+            mask: self.mask & keep,
+        }
+    }
+
+    /// The lower 32 bits.
+    pub fn subreg(self) -> Self {
+        self.cast(4)
+    }
+
+    /// The upper 32 bits, with the lower ones cleared.
+    pub fn clear_subreg(self) -> Self {
+        self.rshift(32).lshift(32)
+    }
+
+    /// Replace the lower 32 bits with those of `subreg`.
+    pub fn with_subreg(self, subreg: Tnum) -> Self {
+        self.clear_subreg().or(subreg.subreg())
+    }
+}
+
+impl Add for Tnum {
+    type Output = Tnum;
+
+    fn add(self, other: Tnum) -> Self {
+        let sm = self.mask.wrapping_add(other.mask);
+        let sv = self.value.wrapping_add(other.value);
+        let sigma = sm.wrapping_add(sv);
+        let chi = sigma ^ sv;
+        let mu = chi | self.mask | other.mask;
+        Tnum {
+            value: sv & !mu,
+            mask: mu,
+        }
+    }
+}
+
+impl Sub for Tnum {
+    type Output = Tnum;
+
+    fn sub(self, other: Tnum) -> Self {
+        let dv = self.value.wrapping_sub(other.value);
+        let alpha = dv.wrapping_add(self.mask);
+        let beta = dv.wrapping_sub(other.mask);
+        let chi = alpha ^ beta;
+        let mu = chi | self.mask | other.mask;
+        Tnum {
+            value: dv & !mu,
+            mask: mu,
+        }
+    }
+}
+
+impl Mul for Tnum {
+    type Output = Tnum;
+
+    /// Multiply by adding the shifted copies of `other` for the bits of this one.
+    fn mul(self, other: Tnum) -> Self {
+        let (mut a, mut b) = (self, other);
+        let acc_value = a.value.wrapping_mul(b.value);
+        let mut acc_mask = Tnum::constant(0);
+        while a.value != 0 || a.mask != 0 {
+            if a.value & 1 != 0 {
+                acc_mask = acc_mask
+                    + Tnum {
+                        value: 0,
+                        mask: b.mask,
+                    };
+            } else if a.mask & 1 != 0 {
+                acc_mask = acc_mask
+                    + Tnum {
+                        value: 0,
+                        mask: b.value | b.mask,
+                    };
+            }
+            a = a.rshift(1);
+            b = b.lshift(1);
+        }
+        Tnum::constant(acc_value) + acc_mask
+    }
+}
+
+impl Display for Tnum {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self.is_const() {
+            true => f.write_fmt(format_args!("{:#x}", self.value)),
+            false => f.write_fmt(format_args!("({:#x}; {:#x})", self.value, self.mask)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests;
@@ -0,0 +1,118 @@
+use super::*;
+
+/// The bits that vary between the tnums below, including the top one so that carries overflow.
+const BITS: [u32; 4] = [0, 1, 2, 63];
+
+/// Every tnum where each bit of `BITS` is zero, one or unknown, and every other bit is zero.
+fn tnums() -> Vec<Tnum> {
+    let mut tnums = vec![Tnum::constant(0)];
+    for bit in BITS {
+        tnums = tnums
+            .into_iter()
+            .flat_map(|t| {
+                let one = Tnum::constant(1 << bit);
+                let unknown = Tnum {
+                    value: 0,
+                    mask: 1 << bit,
+                };
+                [t, t.or(one), t.or(unknown)]
+            })
+            .collect();
+    }
+    tnums
+}
+
+/// The values of a tnum, by enumerating the subsets of its mask.
+fn values(t: Tnum) -> Vec<u64> {
+    let mut values = vec![t.value];
+    let mut bits = t.mask;
+    while bits != 0 {
+        values.push(t.value | bits);
+        bits = (bits - 1) & t.mask;
+    }
+    values
+}
+
+/// Check that a binary operation on tnums contains every result of the one on values.
+fn check(name: &str, op: impl Fn(Tnum, Tnum) -> Tnum, concrete: impl Fn(u64, u64) -> u64) {
+    let tnums = tnums();
+    for &a in &tnums {
+        for &b in &tnums {
+            let r = op(a, b);
+            for x in values(a) {
+                for y in values(b) {
+                    let z = concrete(x, y);
+                    assert!(
+                        r.contains(&Tnum::constant(z)),
+                        "{a} {name} {b} = {r} doesn't contain {x:#x} {name} {y:#x} = {z:#x}"
+                    );
+                }
+            }
+            if let (true, true) = (a.is_const(), b.is_const()) {
+                assert_eq!(r, Tnum::constant(concrete(a.value, b.value)));
+            }
+        }
+    }
+}
+
+#[test]
+fn values_of_tnums() {
+    assert_eq!(tnums().len(), 81);
+    assert_eq!(values(Tnum::constant(4)), vec![4]);
+    let mut v = values(Tnum { value: 8, mask: 5 });
+    v.sort();
+    assert_eq!(v, vec![8, 9, 12, 13]);
+}
+
+#[test]
+fn add() {
+    check("+", |a, b| a + b, u64::wrapping_add);
+}
+
+#[test]
+fn sub() {
+    check("-", |a, b| a - b, u64::wrapping_sub);
+}
+
+#[test]
+fn mul() {
+    check("*", |a, b| a * b, u64::wrapping_mul);
+}
+
+#[test]
+fn bitwise() {
+    check("&", Tnum::and, |x, y| x & y);
+    check("|", Tnum::or, |x, y| x | y);
+    check("^", Tnum::xor, |x, y| x ^ y);
+}
+
+#[test]
+fn shifts() {
+    for shift in [0, 1, 3, 63] {
+        for a in tnums() {
+            for x in values(a) {
+                let lsh = a.lshift(shift);
+                assert!(lsh.contains(&Tnum::constant(x << shift)), "{a} << {shift}");
+                let rsh = a.rshift(shift);
+                assert!(rsh.contains(&Tnum::constant(x >> shift)), "{a} >> {shift}");
+                let arsh = a.arshift(shift, 64);
+                let z = ((x as i64) >> shift) as u64;
+                assert!(arsh.contains(&Tnum::constant(z)), "{a} s>> {shift}");
+            }
+        }
+    }
+}
+
+#[test]
+fn range() {
+    assert_eq!(Tnum::range(4, 7), Tnum { value: 4, mask: 3 });
+    assert_eq!(Tnum::range(5, 5), Tnum::constant(5));
+    assert_eq!(Tnum::range(0, u64::MAX), Tnum::UNKNOWN);
+    for (min, max) in [(3, 9), (8, 8), (1, 1 << 40)] {
+        let t = Tnum::range(min, max);
+        assert!(
+            t.min() <= min && t.max() >= max,
+            "range({min}, {max}) = {t}"
+        );
+    }
+}
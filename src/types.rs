@@ -0,0 +1,719 @@
+//! Pointer and scalar type tracking for registers.
+//! Each register carries a ghost region tag that is propagated through moves and arithmetic,
+//! so that pointer arithmetic and dereferences can be checked the way the kernel does.
+
+use std::{
+    collections::{HashMap, HashSet},
+    fmt::{self, Display, Formatter},
+};
+
+use crate::{
+    cfg::*,
+    dataflow::{solve, Analysis, Direction, Lattice},
+    formula::FormulaBuilder,
+    helpers::{helper_by_id, Ret},
+    prog::{FieldKind, ProgType},
+};
+
+/// Memory regions that a pointer may refer to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Region {
+    /// The program context passed in `r1`.
+    Ctx,
+    /// The stack frame below `r10`.
+    Stack,
+    /// A value of the map with the given file descriptor.
+    MapValue(Imm),
+    /// A buffer given by an `is_buffer` precondition.
+    Buffer,
+    /// The packet data, from its start.
+    Packet,
+    /// The end of the packet data, which can only be compared against.
+    PacketEnd,
+    /// A socket returned by a lookup helper.
+    Sock,
+}
+
+impl Region {
+    /// Whether pointers into the regions can be compared to each other.
+    fn comparable(self, other: Region) -> bool {
+        use Region::*;
+        self == other || matches!((self, other), (Packet, PacketEnd) | (PacketEnd, Packet))
+    }
+
+    /// Whether the kernel allows adding scalars to pointers into the region.
+    fn allows_arith(self) -> bool {
+        !matches!(self, Region::PacketEnd | Region::Sock)
+    }
+}
+
+/// The call that returned a nullable pointer, which all copies of the pointer share,
+/// so that checking one of them against null resolves the others.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct NullId {
+    block: usize,
+    index: usize,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RegType {
+    NotInit,
+    Scalar,
+    /// Pointer into a region, with a known offset from the start of it if constant.
+    Ptr(Region, Option<Offset>),
+    /// Pointer to the start of a region or null, until a null check resolves it.
+    /// Copies that can't be told apart from those of other calls have no ID.
+    PtrOrNull(Region, Option<NullId>),
+    /// File descriptor of a map.
+    MapFd(Imm),
+    /// Pointer to a subprogram.
+    Func,
+    /// Former pointer into a region that has since been invalidated.
+    Stale(Region),
+    /// Different types on different paths.
+    Unknown,
+}
+
+impl RegType {
+    fn join(self, other: RegType) -> RegType {
+        match (self, other) {
+            (a, b) if a == b => a,
+            (RegType::NotInit, _) | (_, RegType::NotInit) => RegType::NotInit,
+            (RegType::Ptr(r1, _), RegType::Ptr(r2, _)) if r1 == r2 => RegType::Ptr(r1, None),
+            (RegType::PtrOrNull(r1, _), RegType::Ptr(r2, _) | RegType::PtrOrNull(r2, _))
+            | (RegType::Ptr(r2, _), RegType::PtrOrNull(r1, _))
+                if r1 == r2 =>
+            {
+                RegType::PtrOrNull(r1, None)
+            }
+            _ => RegType::Unknown,
+        }
+    }
+
+    pub fn is_ptr(&self) -> bool {
+        matches!(
+            self,
+            RegType::Ptr(_, _) | RegType::PtrOrNull(_, _) | RegType::MapFd(_) | RegType::Func
+        )
+    }
+
+    /// Whether the register might hold a pointer on some path.
+    pub fn may_be_ptr(&self) -> bool {
+        self.is_ptr() || matches!(self, RegType::Unknown | RegType::Stale(_))
+    }
+
+    fn forget_null_id(&mut self, id: NullId) {
+        if let RegType::PtrOrNull(region, Some(i)) = *self {
+            if i == id {
+                *self = RegType::PtrOrNull(region, None);
+            }
+        }
+    }
+
+    /// Invalidate pointers into the packet.
+    fn invalidate_pkt(&mut self) {
+        if let RegType::Ptr(r @ (Region::Packet | Region::PacketEnd), _) = *self {
+            *self = RegType::Stale(r);
+        }
+    }
+}
+
+impl Display for Region {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            Region::Ctx => f.write_str("ctx"),
+            Region::Stack => f.write_str("stack"),
+            Region::MapValue(fd) => f.write_fmt(format_args!("map {fd} value")),
+            Region::Buffer => f.write_str("buffer"),
+            Region::Packet => f.write_str("packet"),
+            Region::PacketEnd => f.write_str("packet end"),
+            Region::Sock => f.write_str("socket"),
+        }
+    }
+}
+
+impl Display for RegType {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            RegType::NotInit => f.write_str("uninitialized"),
+            RegType::Scalar => f.write_str("scalar"),
+            RegType::Ptr(region, Some(o)) => f.write_fmt(format_args!("{region} pointer{o:+}")),
+            RegType::Ptr(region, None) => f.write_fmt(format_args!("{region} pointer")),
+            RegType::PtrOrNull(region, _) => f.write_fmt(format_args!("{region} pointer or null")),
+            RegType::MapFd(fd) => f.write_fmt(format_args!("map {fd} fd")),
+            RegType::Func => f.write_str("function"),
+            RegType::Stale(region) => f.write_fmt(format_args!("stale {region} pointer")),
+            RegType::Unknown => f.write_str("unknown"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TypeErrKind {
+    /// Dereference of a register that isn't a memory pointer.
+    InvalidDeref(Reg, RegType),
+    /// Arithmetic that the kernel doesn't allow on a pointer.
+    PointerArith(Reg, RegType),
+    /// A pointer that might become visible to an unprivileged user.
+    PointerLeak(Reg, RegType),
+    /// Comparison between a pointer and a scalar in an unprivileged program.
+    PointerComparison(Reg, RegType),
+    /// Comparison between pointers into regions that can't be compared.
+    IncomparablePointers(Reg, RegType, Reg, RegType),
+    /// Access to the context outside of the fields that the program type allows.
+    CtxAccess {
+        offset: Option<Offset>,
+        size: WordSize,
+        write: bool,
+    },
+}
+
+/// An operation that isn't allowed for the types of its operands.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TypeErr {
+    pub label: Label,
+    pub site: Site,
+    pub kind: TypeErrKind,
+}
+
+impl Display for TypeErr {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        let TypeErr { label, site, kind } = self;
+        match kind {
+            TypeErrKind::InvalidDeref(reg, t) => {
+                f.write_fmt(format_args!("Dereference of r{} with type {t}", reg.get()))?
+            }
+            TypeErrKind::PointerArith(reg, t) => f.write_fmt(format_args!(
+                "Prohibited arithmetic on r{} with type {t}",
+                reg.get()
+            ))?,
+            TypeErrKind::PointerLeak(reg, t) => {
+                f.write_fmt(format_args!("Leak of r{} with type {t}", reg.get()))?
+            }
+            TypeErrKind::PointerComparison(reg, t) => f.write_fmt(format_args!(
+                "Comparison of r{} with type {t} against a scalar",
+                reg.get()
+            ))?,
+            TypeErrKind::IncomparablePointers(lhs, l, rhs, r) => f.write_fmt(format_args!(
+                "Comparison of r{} with type {l} against r{} with type {r}",
+                lhs.get(),
+                rhs.get()
+            ))?,
+            TypeErrKind::CtxAccess {
+                offset,
+                size,
+                write,
+            } => {
+                let access = if *write { "write" } else { "read" };
+                let bytes = size.bytes();
+                match offset {
+                    Some(o) => f.write_fmt(format_args!(
+                        "Invalid {bytes}-byte ctx {access} at offset {o}"
+                    ))?,
+                    None => f.write_fmt(format_args!(
+                        "Invalid {bytes}-byte ctx {access} at variable offset"
+                    ))?,
+                }
+            }
+        }
+        f.write_fmt(format_args!(" by {}", site.describe(label)))
+    }
+}
+
+/// Types of all registers and spilled stack slots at a program point.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RegTypes {
+    regs: [RegType; 11],
+    /// Types of 8-byte values spilled to the stack, by offset from `r10`.
+    spills: HashMap<Offset, RegType>,
+}
+
+impl RegTypes {
+    /// Types at the start of the program.
+    /// Initialized registers are scalars, except `r10`, the context pointer `r1` of program types
+    /// and registers that the preconditions declare as buffers.
+    pub fn entry(cfg: &Cfg, init: &HashSet<Reg>, prog: ProgType) -> Self {
+        let mut regs = [RegType::NotInit; 11];
+        for r in init.iter() {
+            regs[r.get() as usize] = RegType::Scalar;
+        }
+        for r in buffer_regs(&cfg.requires) {
+            regs[r.get() as usize] = RegType::Ptr(Region::Buffer, Some(0));
+        }
+        if prog.ctx().is_some() {
+            regs[Reg::R1.get() as usize] = RegType::Ptr(Region::Ctx, Some(0));
+        }
+        regs[Reg::R10.get() as usize] = RegType::Ptr(Region::Stack, Some(0));
+        Self {
+            regs,
+            spills: HashMap::new(),
+        }
+    }
+
+    pub fn get(&self, reg: Reg) -> RegType {
+        self.regs[reg.get() as usize]
+    }
+
+    fn set(&mut self, reg: Reg, t: RegType) {
+        self.regs[reg.get() as usize] = t;
+    }
+
+    pub fn src(&self, src: &RegImm) -> RegType {
+        match src {
+            RegImm::Reg(r) => self.get(*r),
+            RegImm::Imm(_) => RegType::Scalar,
+        }
+    }
+
+    /// Offset from `r10` of an access, if it is known to target the stack.
+    pub fn stack_offset(&self, MemRef(reg, offset): &MemRef) -> Option<Offset> {
+        match self.get(*reg) {
+            RegType::Ptr(Region::Stack, o) => o.map(|o| o + offset),
+            _ => None,
+        }
+    }
+
+    /// Apply a statement, returning an error if it isn't allowed for the operand types.
+    /// A nullable pointer that the statement returns gets the given ID.
+    fn step(&mut self, stmt: &Stmt, prog: ProgType, null_id: NullId) -> Option<TypeErrKind> {
+        match stmt {
+            Stmt::Unary(_, _, dst) => {
+                let t = self.get(*dst);
+                self.set(*dst, RegType::Scalar);
+                if t.is_ptr() {
+                    return Some(TypeErrKind::PointerArith(*dst, t));
+                }
+            }
+            Stmt::Binary(size, op, dst, src) => return self.binary(*size, *op, *dst, src),
+            Stmt::Store(size, mem_ref, src) => {
+                let err = self
+                    .check_deref(mem_ref)
+                    .or_else(|| self.check_ctx(prog, *size, mem_ref, true));
+                if self.get(mem_ref.0) == RegType::Ptr(Region::Stack, None) {
+                    self.spills.clear();
+                } else if let Some(start) = self.stack_offset(mem_ref) {
+                    let end = start + size.bytes();
+                    self.spills.retain(|o, _| *o + 8 <= start || end <= *o);
+                    let t = self.src(src);
+                    if *size == WordSize::B64 && t.is_ptr() {
+                        self.spills.insert(start, t);
+                    }
+                }
+                return err;
+            }
+            Stmt::Load(size, dst, mem_ref) => {
+                let err = self
+                    .check_deref(mem_ref)
+                    .or_else(|| self.check_ctx(prog, *size, mem_ref, false));
+                let spilled = match (size, self.stack_offset(mem_ref)) {
+                    (WordSize::B64, Some(o)) => self.spills.get(&o).copied(),
+                    _ => None,
+                };
+                let loaded = spilled.or_else(|| self.ctx_field_type(prog, *size, mem_ref));
+                self.set(*dst, loaded.unwrap_or(RegType::Scalar));
+                return err;
+            }
+            Stmt::LoadImm(dst, _) => self.set(*dst, RegType::Scalar),
+            Stmt::LoadMapFd(dst, fd) => self.set(*dst, RegType::MapFd(*fd)),
+            Stmt::LoadFunc(dst, _) => self.set(*dst, RegType::Func),
+            Stmt::Call(id) => {
+                let helper = helper_by_id(*id);
+                if helper.changes_pkt {
+                    self.regs.iter_mut().for_each(RegType::invalidate_pkt);
+                    self.spills.values_mut().for_each(RegType::invalidate_pkt);
+                }
+                // Copies of the result of a previous run of the call are a different pointer.
+                self.regs.iter_mut().for_each(|t| t.forget_null_id(null_id));
+                self.spills
+                    .values_mut()
+                    .for_each(|t| t.forget_null_id(null_id));
+                let ret = match (helper.ret, self.get(Reg::R1)) {
+                    (Ret::MapValue, RegType::MapFd(fd)) => {
+                        RegType::PtrOrNull(Region::MapValue(fd), Some(null_id))
+                    }
+                    (Ret::MemOrNull, _) => RegType::PtrOrNull(Region::Buffer, Some(null_id)),
+                    (Ret::SockOrNull, _) => RegType::PtrOrNull(Region::Sock, Some(null_id)),
+                    _ => RegType::Scalar,
+                };
+                self.set(Reg::R0, ret);
+                for r in [Reg::R1, Reg::R2, Reg::R3, Reg::R4, Reg::R5] {
+                    self.set(r, RegType::NotInit);
+                }
+            }
+            Stmt::CallLocal(_) => {
+                // Subprograms are assumed to change the packet, as they may call helpers that do.
+                self.regs.iter_mut().for_each(RegType::invalidate_pkt);
+                self.spills.values_mut().for_each(RegType::invalidate_pkt);
+                self.set(Reg::R0, RegType::Scalar);
+                for r in [Reg::R1, Reg::R2, Reg::R3, Reg::R4, Reg::R5] {
+                    self.set(r, RegType::NotInit);
+                }
+            }
+            Stmt::Assert(_) => (),
+        }
+        None
+    }
+
+    /// Pointers may only be compared to null or to pointers into the same region.
+    fn check_comparison(&self, lhs: Reg, rhs: &RegImm) -> Option<TypeErrKind> {
+        let l = self.get(lhs);
+        match (l, rhs) {
+            (_, RegImm::Imm(0)) => None,
+            (l, RegImm::Imm(_)) if l.may_be_ptr() => Some(TypeErrKind::PointerComparison(lhs, l)),
+            (_, RegImm::Imm(_)) => None,
+            (l, RegImm::Reg(rhs)) => match (l, self.get(*rhs)) {
+                (RegType::Ptr(r1, _), RegType::Ptr(r2, _)) if r1.comparable(r2) => None,
+                (l, r) if l.is_ptr() && r.is_ptr() => {
+                    Some(TypeErrKind::IncomparablePointers(lhs, l, *rhs, r))
+                }
+                (l, _) if l.may_be_ptr() => Some(TypeErrKind::PointerComparison(lhs, l)),
+                (_, r) if r.may_be_ptr() => Some(TypeErrKind::PointerComparison(*rhs, r)),
+                _ => None,
+            },
+        }
+    }
+
+    /// Type of a full load of a context field that holds a pointer.
+    fn ctx_field_type(
+        &self,
+        prog: ProgType,
+        size: WordSize,
+        MemRef(reg, offset): &MemRef,
+    ) -> Option<RegType> {
+        let (ctx, base) = match (prog.ctx(), self.get(*reg)) {
+            (Some(ctx), RegType::Ptr(Region::Ctx, Some(base))) => (ctx, base),
+            _ => return None,
+        };
+        let field = ctx.field(base + offset, size)?;
+        if field.offset != base + offset || field.size != size.bytes() {
+            return None;
+        }
+        match field.kind {
+            FieldKind::Scalar => None,
+            FieldKind::PacketData => Some(RegType::Ptr(Region::Packet, Some(0))),
+            FieldKind::PacketEnd => Some(RegType::Ptr(Region::PacketEnd, Some(0))),
+        }
+    }
+
+    fn check_ctx(
+        &self,
+        prog: ProgType,
+        size: WordSize,
+        MemRef(reg, offset): &MemRef,
+        write: bool,
+    ) -> Option<TypeErrKind> {
+        let (ctx, base) = match (prog.ctx(), self.get(*reg)) {
+            (Some(ctx), RegType::Ptr(Region::Ctx, base)) => (ctx, base),
+            _ => return None,
+        };
+        let offset = base.map(|b| b + offset);
+        match offset {
+            Some(o) if ctx.allows(o, size, write) => None,
+            _ => Some(TypeErrKind::CtxAccess {
+                offset,
+                size,
+                write,
+            }),
+        }
+    }
+
+    fn check_deref(&self, MemRef(reg, _): &MemRef) -> Option<TypeErrKind> {
+        match self.get(*reg) {
+            RegType::Ptr(Region::PacketEnd, _) => {
+                Some(TypeErrKind::InvalidDeref(*reg, self.get(*reg)))
+            }
+            // Null checks of nullable pointers are left to the verification conditions.
+            RegType::Ptr(_, _) | RegType::PtrOrNull(_, _) | RegType::NotInit => None,
+            t => Some(TypeErrKind::InvalidDeref(*reg, t)),
+        }
+    }
+
+    /// Types on the edge from a block to one of its targets.
+    /// Comparing a nullable pointer against 0 resolves it on both edges,
+    /// along with all copies of it.
+    fn branch(&self, next: &Continuation, target: &Label) -> RegTypes {
+        let mut types = self.clone();
+        if let Continuation::Jcc(cc @ (Cc::Eq | Cc::Ne), reg, RegImm::Imm(0), target_t, target_f) =
+            next
+        {
+            if let (t @ RegType::PtrOrNull(region, id), true) =
+                (self.get(*reg), target_t != target_f)
+            {
+                let null = (*cc == Cc::Eq) == (target == target_t);
+                let resolved = if null {
+                    RegType::Scalar
+                } else {
+                    RegType::Ptr(region, Some(0))
+                };
+                types.set(*reg, resolved);
+                if id.is_some() {
+                    let copies = types.regs.iter_mut().chain(types.spills.values_mut());
+                    copies.filter(|c| **c == t).for_each(|c| *c = resolved);
+                }
+            }
+        }
+        types
+    }
+
+    fn binary(
+        &mut self,
+        size: WordSize,
+        op: BinAlu,
+        dst: Reg,
+        src: &RegImm,
+    ) -> Option<TypeErrKind> {
+        let d = self.get(dst);
+        let s = self.src(src);
+        let src_reg = match src {
+            RegImm::Reg(r) => *r,
+            RegImm::Imm(_) => dst,
+        };
+        let imm = match src {
+            RegImm::Imm(i) => Some(*i),
+            RegImm::Reg(_) => None,
+        };
+
+        // Pointers only survive 64-bit moves, additions and subtractions.
+        let (result, err) = match (size, op, d, s) {
+            (WordSize::B64, BinAlu::Mov, _, s) => (s, None),
+            (_, _, RegType::Ptr(Region::PacketEnd, _), _) => {
+                (RegType::Scalar, Some(TypeErrKind::PointerArith(dst, d)))
+            }
+            (_, _, _, RegType::Ptr(Region::PacketEnd, _)) => {
+                (RegType::Scalar, Some(TypeErrKind::PointerArith(src_reg, s)))
+            }
+            (_, BinAlu::Mov, _, s) if s.is_ptr() => {
+                (RegType::Scalar, Some(TypeErrKind::PointerArith(src_reg, s)))
+            }
+            (_, BinAlu::Mov, _, RegType::NotInit) => (RegType::NotInit, None),
+            (_, BinAlu::Mov, _, _) => (RegType::Scalar, None),
+            (_, _, RegType::NotInit, _) | (_, _, _, RegType::NotInit) => (RegType::NotInit, None),
+            (WordSize::B64, BinAlu::Add, RegType::Ptr(r, o), RegType::Scalar)
+                if r.allows_arith() =>
+            {
+                (RegType::Ptr(r, o.zip(imm).map(|(o, i)| o + i)), None)
+            }
+            (WordSize::B64, BinAlu::Add, RegType::Scalar, RegType::Ptr(r, _))
+                if r.allows_arith() =>
+            {
+                (RegType::Ptr(r, None), None)
+            }
+            (WordSize::B64, BinAlu::Sub, RegType::Ptr(r, o), RegType::Scalar)
+                if r.allows_arith() =>
+            {
+                (RegType::Ptr(r, o.zip(imm).map(|(o, i)| o - i)), None)
+            }
+            (WordSize::B64, BinAlu::Sub, RegType::Ptr(r1, _), RegType::Ptr(r2, _))
+                if r1 == r2 && r1.allows_arith() =>
+            {
+                (RegType::Scalar, None)
+            }
+            (WordSize::B64, BinAlu::Add | BinAlu::Sub, RegType::Stale(r), RegType::Scalar) => {
+                (RegType::Stale(r), None)
+            }
+            (_, _, d, _) if d.is_ptr() => {
+                (RegType::Scalar, Some(TypeErrKind::PointerArith(dst, d)))
+            }
+            (_, _, _, s) if s.is_ptr() => {
+                (RegType::Scalar, Some(TypeErrKind::PointerArith(src_reg, s)))
+            }
+            (_, _, RegType::Unknown, _) | (_, _, _, RegType::Unknown) => (RegType::Unknown, None),
+            _ => (RegType::Scalar, None),
+        };
+        self.set(dst, result);
+        err
+    }
+}
+
+impl Lattice for RegTypes {
+    fn join(&mut self, other: &Self) -> bool {
+        let prev = self.clone();
+        for (a, b) in self.regs.iter_mut().zip(other.regs.iter()) {
+            *a = a.join(*b);
+        }
+        self.spills.retain(|o, t| match other.spills.get(o) {
+            Some(t2) => {
+                *t = t.join(*t2);
+                true
+            }
+            None => false,
+        });
+        *self != prev
+    }
+}
+
+/// Registers that a formula declares as buffers.
+fn buffer_regs(f: &Formula) -> Vec<Reg> {
+    match f {
+        Formula::IsBuffer(ptr, _) => FormulaBuilder::new().reg_of(ptr).into_iter().collect(),
+        Formula::Not(inner) | Formula::Quant(_, _, inner) => buffer_regs(inner),
+        Formula::Bin(_, fs) => {
+            let mut regs = buffer_regs(&fs.0);
+            regs.extend(buffer_regs(&fs.1));
+            regs
+        }
+        Formula::Val(_) | Formula::Rel(_, _, _) | Formula::Held(_) => vec![],
+    }
+}
+
+struct Types<'a> {
+    entry: RegTypes,
+    prog: ProgType,
+    blocks: &'a HashMap<Label, usize>,
+}
+
+impl Analysis for Types<'_> {
+    type Fact = Option<RegTypes>;
+    const DIRECTION: Direction = Direction::Forward;
+
+    fn bottom(&self, _cfg: &Cfg) -> Self::Fact {
+        None
+    }
+
+    fn boundary(&self, _cfg: &Cfg) -> Self::Fact {
+        Some(self.entry.clone())
+    }
+
+    fn transfer(&self, label: &Label, index: usize, stmt: &Stmt, fact: &mut Self::Fact) {
+        if let Some(types) = fact {
+            let id = NullId {
+                block: self.blocks[label],
+                index,
+            };
+            types.step(stmt, self.prog, id);
+        }
+    }
+
+    fn edge(&self, _label: &Label, next: &Continuation, target: &Label, fact: &mut Self::Fact) {
+        if let Some(types) = fact {
+            *types = types.branch(next, target);
+        }
+    }
+}
+
+/// Register types at the entry of every reachable block.
+pub struct TypeInfo {
+    prog: ProgType,
+    entries: HashMap<Label, RegTypes>,
+    /// Numbers of the blocks, which make up the IDs of nullable pointers.
+    blocks: HashMap<Label, usize>,
+}
+
+impl TypeInfo {
+    pub fn infer(cfg: &Cfg, entry: RegTypes, prog: ProgType) -> Self {
+        let mut labels: Vec<&Label> = cfg.blocks.keys().collect();
+        labels.sort();
+        let blocks: HashMap<Label, usize> = labels
+            .into_iter()
+            .enumerate()
+            .map(|(i, l)| (l.clone(), i))
+            .collect();
+        let analysis = Types {
+            entry,
+            prog,
+            blocks: &blocks,
+        };
+        let results = solve(cfg, &analysis);
+        let entries = results
+            .labels()
+            .filter_map(|l| Some((l.clone(), results.entry(l)?.clone()?)))
+            .collect();
+        Self {
+            prog,
+            entries,
+            blocks,
+        }
+    }
+
+    fn null_id(&self, label: &Label, index: usize) -> NullId {
+        NullId {
+            block: self.blocks[label],
+            index,
+        }
+    }
+
+    /// Labels of the blocks that are reachable from the start.
+    pub fn reachable(&self) -> impl Iterator<Item = &Label> {
+        self.entries.keys()
+    }
+
+    /// Types before each statement of a block, followed by the types at its end.
+    pub fn stmt_types(&self, label: &Label, block: &Block) -> Vec<RegTypes> {
+        let mut types = self.entries[label].clone();
+        let mut result = vec![types.clone()];
+        for (index, stmt) in block.body.iter().enumerate() {
+            types.step(stmt, self.prog, self.null_id(label, index));
+            result.push(types.clone());
+        }
+        result
+    }
+
+    /// Find all operations that aren't allowed for the types of their operands.
+    pub fn errors(&self, cfg: &Cfg) -> Vec<TypeErr> {
+        let mut result = Vec::new();
+        for (label, entry) in self.entries.iter() {
+            let mut types = entry.clone();
+            for (index, stmt) in cfg.blocks[label].body.iter().enumerate() {
+                if let Some(kind) = types.step(stmt, self.prog, self.null_id(label, index)) {
+                    result.push(TypeErr {
+                        label: label.clone(),
+                        site: Site::Stmt(index, stmt.clone()),
+                        kind,
+                    });
+                }
+            }
+        }
+        result.sort_by(|a, b| a.label.cmp(&b.label));
+        result
+    }
+
+    /// Find all places where an unprivileged program might leak a kernel address:
+    /// storing pointers anywhere but the stack, returning them, or comparing them to scalars.
+    pub fn leaks(&self, cfg: &Cfg) -> Vec<TypeErr> {
+        let mut result = Vec::new();
+        for (label, entry) in self.entries.iter() {
+            let block = &cfg.blocks[label];
+            let mut types = entry.clone();
+            let mut report = |site: Site, kind: Option<TypeErrKind>| {
+                if let Some(kind) = kind {
+                    result.push(TypeErr {
+                        label: label.clone(),
+                        site,
+                        kind,
+                    });
+                }
+            };
+            for (index, stmt) in block.body.iter().enumerate() {
+                if let Stmt::Store(_, MemRef(dst, _), RegImm::Reg(src)) = stmt {
+                    // Only spills to the stack stay out of reach of the user.
+                    let t = types.get(*src);
+                    if !matches!(types.get(*dst), RegType::Ptr(Region::Stack, _)) && t.may_be_ptr()
+                    {
+                        let kind = TypeErrKind::PointerLeak(*src, t);
+                        report(Site::Stmt(index, stmt.clone()), Some(kind));
+                    }
+                }
+                types.step(stmt, self.prog, self.null_id(label, index));
+            }
+            let site = Site::Cont(block.next.clone());
+            match &block.next {
+                Continuation::Exit => {
+                    let t = types.get(Reg::R0);
+                    let kind = t
+                        .may_be_ptr()
+                        .then_some(TypeErrKind::PointerLeak(Reg::R0, t));
+                    report(site, kind);
+                }
+                Continuation::Jcc(_, lhs, rhs, _, _) => {
+                    report(site, types.check_comparison(*lhs, rhs));
+                }
+                Continuation::Jmp(_) => (),
+            }
+        }
+        result.sort_by(|a, b| a.label.cmp(&b.label));
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests;
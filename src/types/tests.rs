@@ -0,0 +1,293 @@
+use super::*;
+
+fn infer(cfg: &Cfg, prog: ProgType) -> TypeInfo {
+    let init = HashSet::from([Reg::R1, Reg::R10]);
+    TypeInfo::infer(cfg, RegTypes::entry(cfg, &init, prog), prog)
+}
+
+/// Types at the end of the block with the given label.
+fn types_at(src: &str, label: &str, prog: ProgType) -> RegTypes {
+    let cfg = Cfg::parse(src);
+    let label = label.to_owned();
+    let types = infer(&cfg, prog).stmt_types(&label, &cfg.blocks[&label]);
+    types.last().unwrap().clone()
+}
+
+/// The kinds of type errors of a program.
+fn errors(src: &str, prog: ProgType) -> Vec<TypeErrKind> {
+    let cfg = Cfg::parse(src);
+    let errs = infer(&cfg, prog).errors(&cfg);
+    errs.into_iter().map(|e| e.kind).collect()
+}
+
+#[test]
+fn stack_offsets() {
+    let src = "mov r2 r10\nadd r2 -8\nsub r2 8\nmov r3 r2\nadd r3 r1\nexit\n";
+    let types = types_at(src, "@0", ProgType::Function);
+    assert_eq!(types.get(Reg::R2), RegType::Ptr(Region::Stack, Some(-16)));
+    assert_eq!(types.get(Reg::R3), RegType::Ptr(Region::Stack, None));
+}
+
+#[test]
+fn spills() {
+    let src = "stxdw [r10 - 8] r1\nldxdw r2 [r10 - 8]\nstw [r10 - 4] 0\nldxdw r3 [r10 - 8]\nexit\n";
+    let types = types_at(src, "@0", ProgType::Xdp);
+    assert_eq!(types.get(Reg::R2), RegType::Ptr(Region::Ctx, Some(0)));
+    // Overwriting part of the slot turns it into a scalar.
+    assert_eq!(types.get(Reg::R3), RegType::Scalar);
+}
+
+#[test]
+fn joins() {
+    let src = "\
+mov r2 r10
+mov r3 r10
+jeq r1 0 end
+add r2 -8
+mov r3 0
+end:
+exit
+";
+    let types = types_at(src, "end", ProgType::Function);
+    assert_eq!(types.get(Reg::R2), RegType::Ptr(Region::Stack, None));
+    assert_eq!(types.get(Reg::R3), RegType::Unknown);
+}
+
+#[test]
+fn scalar_deref() {
+    assert_eq!(
+        errors("mov r2 5\nldxb r0 [r2]\nexit\n", ProgType::Function),
+        vec![TypeErrKind::InvalidDeref(Reg::R2, RegType::Scalar)]
+    );
+}
+
+#[test]
+fn pointer_arith() {
+    let stack = RegType::Ptr(Region::Stack, Some(0));
+    assert_eq!(
+        errors("mov r2 r10\nmul r2 2\nmov r0 0\nexit\n", ProgType::Function),
+        vec![TypeErrKind::PointerArith(Reg::R2, stack)]
+    );
+    assert_eq!(
+        errors(
+            "mov r2 r10\nmov32 r3 r2\nmov r0 0\nexit\n",
+            ProgType::Function
+        ),
+        vec![TypeErrKind::PointerArith(Reg::R2, stack)]
+    );
+}
+
+#[test]
+fn socket_arith() {
+    // Sockets only allow accesses at constant offsets, so no arithmetic at all.
+    let src = "\
+call 84
+jeq r0 0 end
+add r0 8
+end:
+exit
+";
+    assert_eq!(
+        errors(src, ProgType::Function),
+        vec![TypeErrKind::PointerArith(
+            Reg::R0,
+            RegType::Ptr(Region::Sock, Some(0))
+        )]
+    );
+}
+
+/// The kinds of leaks of an unprivileged program.
+fn leaks(src: &str, prog: ProgType) -> Vec<TypeErrKind> {
+    let cfg = Cfg::parse(src);
+    let leaks = infer(&cfg, prog).leaks(&cfg);
+    leaks.into_iter().map(|e| e.kind).collect()
+}
+
+#[test]
+fn stored_pointers() {
+    let stack = RegType::Ptr(Region::Stack, Some(0));
+    let spill = "mov r2 r10\nstxdw [r10 - 8] r2\nmov r0 0\nexit\n";
+    assert!(leaks(spill, ProgType::Xdp).is_empty());
+    // Maps and the context are visible to the user.
+    let map = "\
+ldmapfd r1 0
+call 1
+jeq r0 0 end
+mov r2 r10
+stxdw [r0] r2
+end:
+mov r0 0
+exit
+";
+    assert_eq!(
+        leaks(map, ProgType::Xdp),
+        vec![TypeErrKind::PointerLeak(Reg::R2, stack)]
+    );
+    let ctx = "mov r2 r10\nstxdw [r1] r2\nmov r0 0\nexit\n";
+    assert_eq!(
+        leaks(ctx, ProgType::Xdp),
+        vec![TypeErrKind::PointerLeak(Reg::R2, stack)]
+    );
+    // So might any memory that isn't known to be the stack.
+    let unknown = "\
+mov r3 r10
+jeq r1 0 store
+mov r3 r1
+store:
+mov r2 r10
+stxdw [r3 - 8] r2
+mov r0 0
+exit
+";
+    assert_eq!(
+        leaks(unknown, ProgType::Xdp),
+        vec![TypeErrKind::PointerLeak(Reg::R2, stack)]
+    );
+}
+
+#[test]
+fn returned_pointers() {
+    assert_eq!(
+        leaks("mov r0 r10\nexit\n", ProgType::Function),
+        vec![TypeErrKind::PointerLeak(
+            Reg::R0,
+            RegType::Ptr(Region::Stack, Some(0))
+        )]
+    );
+}
+
+#[test]
+fn compared_pointers() {
+    let stack = RegType::Ptr(Region::Stack, Some(0));
+    let scalar = "mov r0 0\njgt r10 5 end\nend:\nexit\n";
+    assert_eq!(
+        leaks(scalar, ProgType::Function),
+        vec![TypeErrKind::PointerComparison(Reg::R10, stack)]
+    );
+    let null = "mov r0 0\njeq r10 0 end\nend:\nexit\n";
+    assert!(leaks(null, ProgType::Function).is_empty());
+    // Pointers into different regions are reported as such, rather than as scalars.
+    let ctx = RegType::Ptr(Region::Ctx, Some(0));
+    let regions = "mov r0 0\njgt r10 r1 end\nend:\nexit\n";
+    assert_eq!(
+        leaks(regions, ProgType::Xdp),
+        vec![TypeErrKind::IncomparablePointers(
+            Reg::R10,
+            stack,
+            Reg::R1,
+            ctx
+        )]
+    );
+}
+
+#[test]
+fn packet_pointers() {
+    let src = "ldxw r2 [r1]\nldxw r3 [r1 + 4]\nldxb r4 [r1]\nmov r5 r2\nadd r5 14\nexit\n";
+    let types = types_at(src, "@0", ProgType::Xdp);
+    assert_eq!(types.get(Reg::R2), RegType::Ptr(Region::Packet, Some(0)));
+    assert_eq!(types.get(Reg::R3), RegType::Ptr(Region::PacketEnd, Some(0)));
+    // Only full loads of the fields are pointers.
+    assert_eq!(types.get(Reg::R4), RegType::Scalar);
+    assert_eq!(types.get(Reg::R5), RegType::Ptr(Region::Packet, Some(14)));
+}
+
+#[test]
+fn packet_end() {
+    let end = RegType::Ptr(Region::PacketEnd, Some(0));
+    assert_eq!(
+        errors(
+            "ldxw r3 [r1 + 4]\nadd r3 1\nmov r0 0\nexit\n",
+            ProgType::Xdp
+        ),
+        vec![TypeErrKind::PointerArith(Reg::R3, end)]
+    );
+    assert_eq!(
+        errors("ldxw r3 [r1 + 4]\nldxb r0 [r3]\nexit\n", ProgType::Xdp),
+        vec![TypeErrKind::InvalidDeref(Reg::R3, end)]
+    );
+}
+
+#[test]
+fn invalidated_packet() {
+    // xdp_adjust_head may move the packet data.
+    let src = "\
+ldxw r6 [r1]
+stxdw [r10 - 8] r6
+mov r2 0
+call 44
+ldxdw r7 [r10 - 8]
+exit
+";
+    let types = types_at(src, "@0", ProgType::Xdp);
+    assert_eq!(types.get(Reg::R6), RegType::Stale(Region::Packet));
+    assert_eq!(types.get(Reg::R7), RegType::Stale(Region::Packet));
+    let deref = "ldxw r6 [r1]\ncall 44\nldxb r0 [r6]\nexit\n";
+    assert_eq!(
+        errors(deref, ProgType::Xdp),
+        vec![TypeErrKind::InvalidDeref(
+            Reg::R6,
+            RegType::Stale(Region::Packet)
+        )]
+    );
+    // Other helpers leave the packet alone.
+    let time = "ldxw r6 [r1]\ncall 5\nldxb r0 [r6]\nexit\n";
+    assert!(errors(time, ProgType::Xdp).is_empty());
+}
+
+#[test]
+fn subprograms_invalidate_packet() {
+    let src = "\
+ldxw r6 [r1]
+call f
+exit
+
+;# function f
+mov r0 0
+exit
+";
+    let types = types_at(src, "@0", ProgType::Xdp);
+    assert_eq!(types.get(Reg::R6), RegType::Stale(Region::Packet));
+}
+
+#[test]
+fn null_checks() {
+    // Checking the result resolves its copies in registers and on the stack.
+    let src = "\
+ldmapfd r1 0
+call 1
+mov r6 r0
+stxdw [r10 - 8] r0
+jne r0 0 load
+exit
+load:
+ldxdw r7 [r10 - 8]
+exit
+";
+    let value = RegType::Ptr(Region::MapValue(0), Some(0));
+    let types = types_at(src, "load", ProgType::Function);
+    assert_eq!(types.get(Reg::R0), value);
+    assert_eq!(types.get(Reg::R6), value);
+    assert_eq!(types.get(Reg::R7), value);
+    let types = types_at(src, "@0", ProgType::Function);
+    assert!(matches!(types.get(Reg::R6), RegType::PtrOrNull(_, Some(_))));
+}
+
+#[test]
+fn null_checks_of_other_calls() {
+    let src = "\
+ldmapfd r1 0
+call 1
+mov r6 r0
+ldmapfd r1 0
+call 1
+jne r0 0 load
+exit
+load:
+exit
+";
+    let types = types_at(src, "load", ProgType::Function);
+    assert!(matches!(
+        types.get(Reg::R6),
+        RegType::PtrOrNull(Region::MapValue(0), Some(_))
+    ));
+}
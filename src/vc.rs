@@ -1,17 +1,36 @@
 //! Verification condition generation.
 
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 
-use crate::{cfg::*, formula::*};
+use crate::{
+    bounds::Facts,
+    cfg::*,
+    formula::*,
+    helpers::{helper_by_id, Callback, CbArg, Ret, SOCK_SIZE},
+    loops::cut_points,
+    types::*,
+};
+
+/// Size of the stack frame that `r10` points to the top of.
+pub const STACK_SIZE: i64 = 512;
+
+/// Ghost variable holding the start of the packet data.
+pub const PKT_DATA: &str = "pkt";
+/// Ghost variable holding the end of the packet data.
+pub const PKT_END: &str = "pkt_end";
 
 #[derive(Debug, PartialEq, Eq)]
 enum BlockStatus {
     Pending,
-    Cyclic,
     PreCond(Formula),
 }
 
-pub fn vc(module: Cfg, f: &mut FormulaBuilder) -> Vec<(String, Formula)> {
+pub fn vc(
+    module: Cfg,
+    types: &TypeInfo,
+    facts: &Facts,
+    f: &mut FormulaBuilder,
+) -> Vec<(String, Formula)> {
     // Stores results.
     let mut verif_conds: Vec<(String, Formula)> = Vec::new();
 
@@ -19,6 +38,20 @@ pub fn vc(module: Cfg, f: &mut FormulaBuilder) -> Vec<(String, Formula)> {
     // Also used to track which blocks have already been visited.
     let mut pre_conds: HashMap<Label, BlockStatus> = HashMap::new();
 
+    // Blocks that cut every cycle, along with the regions whose cycles they cut.
+    let cuts: HashMap<Label, HashSet<Label>> = cut_points(&module)
+        .into_iter()
+        .map(|c| (c.label, c.region))
+        .collect();
+
+    // Ghost variables holding the variant of each loop header at the start of an iteration.
+    let ghosts: HashMap<Label, Ident> = module
+        .blocks
+        .iter()
+        .filter(|(_, b)| b.variant.is_some())
+        .map(|(l, _)| (l.clone(), f.var(String::from("variant")).1))
+        .collect();
+
     // Perform a reverse breadth-first traversal of CFG.
     let mut stack = vec![module.start.clone()];
     while let Some(label) = stack.pop() {
@@ -34,20 +67,27 @@ pub fn vc(module: Cfg, f: &mut FormulaBuilder) -> Vec<(String, Formula)> {
 
         // This func attemps to retrieve the precond of another block.
         let mut get_post_cond = |target: &Label| {
-            let b = pre_conds.get_mut(target);
-            match b {
+            // Cut points use their requirement, which is `true` if they don't have one.
+            // Jumps within the region that they cut close a cycle,
+            // so the variant of the target must also have decreased.
+            if let Some(region) = cuts.get(target) {
+                if !pre_conds.contains_key(target) {
+                    stack.push(target.clone());
+                }
+                let target_block = &module.blocks[target];
+                let require = target_block.require.clone().unwrap_or(f.top());
+                return match &target_block.variant {
+                    Some(v) if region.contains(&label) => {
+                        let ghost = f.var_ident(ghosts[target].clone());
+                        Some(f.and(require, f.rel(Cc::Lt, v.clone(), ghost)))
+                    }
+                    _ => Some(require),
+                };
+            }
+            match pre_conds.get(target) {
                 // If already processed, return the result.
                 Some(BlockStatus::PreCond(c)) => Some(c.clone()),
-                // If pending, use the requirement if it exists and fail if it doesn't.
-                Some(BlockStatus::Pending) | Some(BlockStatus::Cyclic) => {
-                    // Mark block as cyclic.
-                    *b.unwrap() = BlockStatus::Cyclic;
-                    if let Some(c) = &module.blocks[target].require {
-                        Some(c.clone())
-                    } else {
-                        Some(f.top())
-                    }
-                }
+                Some(BlockStatus::Pending) => panic!("cycle through {target} isn't cut"),
                 // If block isn't marked as anything, push the current block and it to the stack.
                 None => {
                     stack.push(label.clone());
@@ -92,18 +132,33 @@ pub fn vc(module: Cfg, f: &mut FormulaBuilder) -> Vec<(String, Formula)> {
         };
 
         // Perform WP-calculus on postcond with block body.
-        let wp_result = wp(f, &block.body, post_cond);
+        let stmt_types = types.stmt_types(&label, block);
+        let mut wp_result = wp(f, &module, block, &stmt_types, facts, &label, post_cond);
+
+        // The bounds known at the start of the block may be assumed.
+        if let Some(assumption) = facts.assumptions.get(&label) {
+            wp_result = f.implies(assumption.clone(), wp_result);
+        }
+
+        // The variant must be bounded below when entering the block,
+        // and its value is remembered for the back edges to it.
+        // With a bound on the iterations, it must be bounded above by it as well.
+        if let Some(v) = &block.variant {
+            let decreases = assign(f, &ghosts[&label], v.clone(), wp_result);
+            wp_result = f.and(f.rel(Cc::Ge, v.clone(), f.val(0)), decreases);
+            if let Some(n) = block.bound {
+                wp_result = f.and(f.rel(Cc::Le, v.clone(), f.val(n)), wp_result);
+            }
+        }
 
         // Cache or use result of WP.
         let top = f.top();
         let require = block.require.as_ref();
-        let require = require.or(if pre_conds[&label] == BlockStatus::Cyclic {
-            Some(&top)
-        } else {
-            None
-        });
+        let require = require.or(cuts.contains_key(&label).then_some(&top));
 
         if let Some(require) = require {
+            // The frame may be assumed at cut points, since `r10` is read-only.
+            wp_result = f.implies(frame(f), wp_result);
             // If the block has a requirement,
             // add a VC requiring that the requirement implies the WP result.
             verif_conds.push((label.clone(), f.implies(require.clone(), wp_result)));
@@ -118,15 +173,27 @@ pub fn vc(module: Cfg, f: &mut FormulaBuilder) -> Vec<(String, Formula)> {
     verif_conds.push((
         "entry".to_owned(),
         match &pre_conds[&module.start] {
-            BlockStatus::PreCond(c) => f.implies(module.requires, c.clone()),
+            BlockStatus::PreCond(c) => f.implies(f.and(frame(f), module.requires), c.clone()),
             _ => panic!("starting block is never processed"),
         },
     ));
     verif_conds
 }
 
-fn wp(f: &mut FormulaBuilder, instrs: &[Stmt], mut cond: Formula) -> Formula {
-    for instr in instrs.iter().rev() {
+fn wp(
+    f: &mut FormulaBuilder,
+    module: &Cfg,
+    block: &Block,
+    types: &[RegTypes],
+    facts: &Facts,
+    label: &Label,
+    mut cond: Formula,
+) -> Formula {
+    // Accesses known to stay within their region, and divisions known not to be by zero,
+    // need no check.
+    let is_safe = |index: usize| facts.safe.contains(&(label.clone(), index));
+    for ((index, instr), types) in block.body.iter().enumerate().zip(types.windows(2)).rev() {
+        let (types, types_after) = (&types[0], &types[1]);
         match instr {
             Stmt::Unary(WordSize::B64, op, reg) => {
                 let (t, t_id) = f.reg(*reg);
@@ -143,33 +210,224 @@ fn wp(f: &mut FormulaBuilder, instrs: &[Stmt], mut cond: Formula) -> Formula {
                 cond = assign(f, &d_id, e, cond);
 
                 // Add extra conditions for division/modulo by zero.
-                if op == &BinAlu::Div || op == &BinAlu::Mod {
+                if (op == &BinAlu::Div || op == &BinAlu::Mod) && !is_safe(index) {
                     cond = f.asym_and(f.rel(Cc::Ne, s, f.val(0)), cond);
                 }
             }
+            Stmt::Store(_, _, _) if is_safe(index) => (),
             Stmt::Store(size, mem_ref, _) => {
-                let valid_addr = valid_addr(f, *size, mem_ref);
+                let valid_addr = valid_addr(f, *size, mem_ref, types.get(mem_ref.0));
                 cond = f.and(valid_addr, cond);
             }
             Stmt::Load(size, dst, mem_ref) => {
-                let valid_addr = valid_addr(f, *size, mem_ref);
-                let (_, v_id) = f.var(String::from("v"));
-                let (_, t_id) = f.reg(*dst);
-                let replace_reg = match f.replace(&t_id, &v_id, &cond) {
-                    Some(x) => f.forall(v_id.clone(), x),
-                    None => cond,
+                let valid_addr = match is_safe(index) {
+                    true => f.top(),
+                    false => valid_addr(f, *size, mem_ref, types.get(mem_ref.0)),
+                };
+                let (_, d_id) = f.reg(*dst);
+                // Packet pointers with a known offset have a known value.
+                let loaded = match types_after.get(*dst) {
+                    RegType::Ptr(Region::Packet, Some(o)) => {
+                        Some(f.binop(BinAlu::Add, f.var_ident(PKT_DATA.to_owned()), f.val(o)))
+                    }
+                    RegType::Ptr(Region::PacketEnd, Some(0)) => {
+                        Some(f.var_ident(PKT_END.to_owned()))
+                    }
+                    _ => None,
                 };
-                cond = f.and(valid_addr, replace_reg);
+                let assigned = match loaded {
+                    Some(e) => assign(f, &d_id, e, cond),
+                    None => havoc(f, *dst, cond),
+                };
+                cond = f.and(valid_addr, assigned);
             }
             Stmt::Assert(a) => {
                 cond = f.asym_and(a.clone(), cond);
             }
+            Stmt::LoadImm(dst, imm) => {
+                let (_, d_id) = f.reg(*dst);
+                cond = assign(f, &d_id, f.val(*imm), cond);
+            }
+            Stmt::LoadMapFd(dst, _) | Stmt::LoadFunc(dst, _) => {
+                cond = havoc(f, *dst, cond);
+            }
+            Stmt::Call(id) => {
+                let helper = helper_by_id(*id);
+                // Helpers clobber the argument registers.
+                for reg in [Reg::R1, Reg::R2, Reg::R3, Reg::R4, Reg::R5] {
+                    cond = havoc(f, reg, cond);
+                }
+                // Packet-changing helpers start a new generation of packet pointers.
+                // Stale pointers are rejected by `valid_addr` through their type.
+                if helper.changes_pkt {
+                    cond = havoc_ghost(f, PKT_DATA, cond);
+                    cond = havoc_ghost(f, PKT_END, cond);
+                }
+                // Nullable pointers are either null or point to a buffer of a known size.
+                // Any other return value is arbitrary.
+                let size = match (helper.ret, types.get(Reg::R1)) {
+                    (Ret::MapValue, RegType::MapFd(fd)) => {
+                        module.map(fd).map(|m| f.val(m.value_size))
+                    }
+                    (Ret::MemOrNull, _) => Some(f.reg(Reg::R2).0),
+                    (Ret::SockOrNull, _) => Some(f.val(SOCK_SIZE)),
+                    _ => None,
+                };
+                cond = match size {
+                    Some(size) => nullable(f, Reg::R0, size, cond),
+                    None => havoc(f, Reg::R0, cond),
+                };
+                // A successful tail call ends the path, passing the context to the callee.
+                // Only a failed one falls through.
+                if helper.exits {
+                    cond = f.and(module.tail_requires.clone(), cond);
+                }
+                // Callbacks are only known by their contracts.
+                // Creating the CFG rejects calls without a known one, which can't be verified.
+                if let Some(callback) = helper.callback {
+                    let Some(name) = block.callback(index) else {
+                        return f.bot();
+                    };
+                    let map = match types.get(Reg::R1) {
+                        RegType::MapFd(fd)
+                        | RegType::Ptr(Region::MapValue(fd), _)
+                        | RegType::PtrOrNull(Region::MapValue(fd), _) => module.map(fd),
+                        _ => None,
+                    };
+                    // A stack pointer to pass along covers the rest of the frame.
+                    let ctx_size = match types.get(Reg::R3) {
+                        RegType::Ptr(Region::Stack, Some(o)) if o < 0 => Some(-o),
+                        _ => None,
+                    };
+                    let contract = &module.contracts[name];
+                    cond = call_callback(f, &callback, map, ctx_size, contract, cond);
+                }
+            }
+            Stmt::CallLocal(name) => {
+                let Contract { requires, ensures } = &module.contracts[name];
+                for reg in [Reg::R1, Reg::R2, Reg::R3, Reg::R4, Reg::R5] {
+                    cond = havoc(f, reg, cond);
+                }
+                cond = havoc_ghost(f, PKT_DATA, cond);
+                cond = havoc_ghost(f, PKT_END, cond);
+                cond = call_local(f, requires, ensures, cond);
+            }
             instr => panic!("not implemented: {instr:?}"),
         }
     }
     cond
 }
 
+/// Generate the condition for `cond` to hold for any value of `reg`.
+fn havoc(f: &mut FormulaBuilder, reg: Reg, cond: Formula) -> Formula {
+    let (_, t_id) = f.reg(reg);
+    havoc_ghost(f, &t_id, cond)
+}
+
+/// Generate the condition for `cond` to hold for any value of a variable.
+fn havoc_ghost(f: &mut FormulaBuilder, target: &str, cond: Formula) -> Formula {
+    let (_, v_id) = f.var(String::from("v"));
+    match f.replace(&target.to_owned(), &v_id, &cond) {
+        Some(x) => f.forall(v_id, x),
+        None => cond,
+    }
+}
+
+/// Generate the condition for a subprogram call with a contract to be valid and for `cond` to
+/// hold afterwards. The callee has its own registers, so only its result in `r0` is visible to
+/// the caller. Registers `r6`-`r10` of the caller are preserved.
+fn call_local(
+    f: &mut FormulaBuilder,
+    requires: &Formula,
+    ensures: &Formula,
+    cond: Formula,
+) -> Formula {
+    let (_, r0_id) = f.reg(Reg::R0);
+    let (_, v_id) = f.var(String::from("v"));
+    let cond = f.replace(&r0_id, &v_id, &cond).unwrap_or(cond);
+
+    // The postcondition holds for some values of the other registers of the callee.
+    let post = f.replace(&r0_id, &v_id, ensures).unwrap_or(ensures.clone());
+    let (post, callee_regs) = rename_regs(f, post);
+    let result = forall_all(f, callee_regs, f.implies(post, cond));
+    f.asym_and(requires.clone(), f.forall(v_id, result))
+}
+
+/// Generate the condition for a helper to call a callback with a contract and for `cond` to
+/// hold afterwards. The precondition of the callback must follow from the arguments that the
+/// helper passes to it, and its postcondition holds for some values of its registers.
+fn call_callback(
+    f: &mut FormulaBuilder,
+    callback: &Callback,
+    map: Option<&MapDef>,
+    ctx_size: Option<i64>,
+    Contract { requires, ensures }: &Contract,
+    cond: Formula,
+) -> Formula {
+    let (pre, params) = rename_regs(f, requires.clone());
+    let mut args = f.top();
+    for (reg, w_id) in params.iter() {
+        let w = f.var_ident(w_id.clone());
+        let arg = (reg.get() as usize)
+            .checked_sub(1)
+            .and_then(|k| callback.args.get(k));
+        let arg = match (arg, map) {
+            (Some(CbArg::Ctx), _) => {
+                let is_ctx = f.eq(w, f.reg(Reg::R3).0);
+                match ctx_size {
+                    Some(size) => f.and(is_ctx, f.is_buffer(w_id.clone(), f.val(size))),
+                    None => is_ctx,
+                }
+            }
+            (Some(CbArg::MapValue), Some(map)) => f.is_buffer(w_id.clone(), f.val(map.value_size)),
+            _ => f.top(),
+        };
+        args = f.and(args, arg);
+    }
+    let obligation = forall_all(f, params, f.implies(args, pre));
+
+    let (post, callee_regs) = rename_regs(f, ensures.clone());
+    let assumption = forall_all(f, callee_regs, f.implies(post, cond));
+    f.asym_and(obligation, assumption)
+}
+
+/// Replace the registers that occur in a formula with fresh variables.
+fn rename_regs(f: &mut FormulaBuilder, mut formula: Formula) -> (Formula, Vec<(Reg, Ident)>) {
+    let mut renamed = Vec::new();
+    for reg in (0..=10).filter_map(Reg::new) {
+        let (_, t_id) = f.reg(reg);
+        let (_, w_id) = f.var(String::from("w"));
+        if let Some(x) = f.replace(&t_id, &w_id, &formula) {
+            formula = x;
+            renamed.push((reg, w_id));
+        }
+    }
+    (formula, renamed)
+}
+
+/// Universally quantify a formula over renamed registers.
+fn forall_all(f: &FormulaBuilder, vars: Vec<(Reg, Ident)>, mut formula: Formula) -> Formula {
+    for (_, id) in vars.into_iter().rev() {
+        formula = f.forall(id, formula);
+    }
+    formula
+}
+
+/// Generate the condition for `cond` to hold if `reg` is either null
+/// or points to a buffer of `size` bytes.
+fn nullable(f: &mut FormulaBuilder, reg: Reg, size: Expr, cond: Formula) -> Formula {
+    let (v, v_id) = f.var(String::from("v"));
+    let (_, t_id) = f.reg(reg);
+    match f.replace(&t_id, &v_id, &cond) {
+        Some(x) => {
+            let is_null = f.eq(v, f.val(0));
+            let assumption = f.or(is_null, f.is_buffer(v_id.clone(), size));
+            f.forall(v_id, f.implies(assumption, x))
+        }
+        None => cond,
+    }
+}
+
 fn assign(f: &mut FormulaBuilder, target: &Ident, e: Expr, cond: Formula) -> Formula {
     let (v, v_id) = f.var(String::from("v"));
     match f.replace(target, &v_id, &cond) {
@@ -178,16 +436,35 @@ fn assign(f: &mut FormulaBuilder, target: &Ident, e: Expr, cond: Formula) -> For
     }
 }
 
-fn valid_addr(f: &mut FormulaBuilder, size: WordSize, MemRef(reg, offset): &MemRef) -> Formula {
+fn valid_addr(
+    f: &mut FormulaBuilder,
+    size: WordSize,
+    MemRef(reg, offset): &MemRef,
+    base: RegType,
+) -> Formula {
+    let addr = f.binop(BinAlu::Add, f.reg(*reg).0, f.val(*offset));
+    let bytes = size.bytes();
+    match base {
+        // Stack pointers can only ever target the frame.
+        RegType::Ptr(Region::Stack, _) => in_frame(f, addr, bytes),
+        // Context accesses are checked against the layout of the program type.
+        RegType::Ptr(Region::Ctx, _) => f.top(),
+        RegType::Ptr(Region::Packet, _) => in_packet(f, addr, bytes),
+        RegType::Ptr(_, _) => in_buffer(f, addr, bytes),
+        // Dereferencing a nullable pointer requires it to have been checked.
+        RegType::PtrOrNull(_, _) => {
+            let in_buffer = in_buffer(f, addr, bytes);
+            f.asym_and(f.rel(Cc::Ne, f.reg(*reg).0, f.val(0)), in_buffer)
+        }
+        // Anything else cannot be dereferenced at all.
+        _ => f.bot(),
+    }
+}
+
+/// Generate the condition that `[addr, addr + bytes)` lies within some buffer.
+fn in_buffer(f: &mut FormulaBuilder, addr: Expr, bytes: i64) -> Formula {
     let (ptr, ptr_id) = f.var("p".to_owned());
     let (sz, sz_id) = f.var("s".to_owned());
-    let addr = f.binop(BinAlu::Add, f.reg(*reg).0, f.val(*offset));
-    let bytes = match size {
-        WordSize::B8 => 1,
-        WordSize::B16 => 2,
-        WordSize::B32 => 4,
-        WordSize::B64 => 8,
-    };
     let upper_bound = f.binop(
         BinAlu::Sub,
         f.binop(BinAlu::Add, ptr.clone(), sz.clone()),
@@ -210,3 +487,36 @@ fn valid_addr(f: &mut FormulaBuilder, size: WordSize, MemRef(reg, offset): &MemR
         ),
     )
 }
+
+/// Generate the condition that `[addr, addr + bytes)` lies within the packet data.
+fn in_packet(f: &mut FormulaBuilder, addr: Expr, bytes: i64) -> Formula {
+    let data = f.var_ident(PKT_DATA.to_owned());
+    let data_end = f.var_ident(PKT_END.to_owned());
+    let end = f.binop(BinAlu::Add, addr.clone(), f.val(bytes));
+    f.and(f.rel(Cc::Le, data, addr), f.rel(Cc::Le, end, data_end))
+}
+
+/// Generate the condition that the stack frame lies entirely above address 0
+/// and that `r10` is 8-byte aligned, which holds throughout the program.
+pub fn frame(f: &FormulaBuilder) -> Formula {
+    let r10 = f.reg(Reg::R10).0;
+    f.and(
+        f.rel(Cc::Ge, r10.clone(), f.val(STACK_SIZE)),
+        f.eq(f.binop(BinAlu::Mod, r10, f.val(8)), f.val(0)),
+    )
+}
+
+/// Generate the condition that `[addr, addr + bytes)` lies within the stack frame,
+/// which is the [STACK_SIZE] bytes directly below `r10`.
+fn in_frame(f: &mut FormulaBuilder, addr: Expr, bytes: i64) -> Formula {
+    let frame_ptr = f.reg(Reg::R10).0;
+    let lower_bound = f.binop(BinAlu::Sub, frame_ptr.clone(), f.val(STACK_SIZE));
+    let end = f.binop(BinAlu::Add, addr.clone(), f.val(bytes));
+    f.and(
+        f.rel(Cc::Le, lower_bound, addr),
+        f.rel(Cc::Le, end, frame_ptr),
+    )
+}
+
+#[cfg(test)]
+mod tests;
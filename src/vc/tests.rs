@@ -0,0 +1,129 @@
+use std::collections::HashSet;
+
+use super::*;
+use crate::{ast::FBinOp, prog::ProgType, types::RegTypes};
+
+fn goals(src: &str, f: &mut FormulaBuilder) -> Vec<(String, Formula)> {
+    let cfg = Cfg::parse(src);
+    let prog = ProgType::Function;
+    let init = HashSet::from([Reg::R1, Reg::R10]);
+    let types = TypeInfo::infer(&cfg, RegTypes::entry(&cfg, &init, prog), prog);
+    vc(cfg, &types, &Facts::default(), f)
+}
+
+/// The assumption of a goal of the form `a -> b`.
+fn assumption(goal: &Formula) -> &Formula {
+    match goal {
+        Formula::Bin(FBinOp::Implies, fs) => &fs.0,
+        goal => panic!("not an implication: {goal:?}"),
+    }
+}
+
+#[test]
+fn frame_at_entry() {
+    let mut f = FormulaBuilder::new();
+    let goals = goals(
+        ";# requires r1 >= 1\nstxdw [r10 - 8] r1\nmov r0 0\nexit\n",
+        &mut f,
+    );
+    let (name, entry) = goals.last().unwrap();
+    assert_eq!(name, "entry");
+    let requires = f.and_all([f.rel(Cc::Ge, f.reg(Reg::R1).0, f.val(1))]);
+    assert_eq!(assumption(entry), &f.and(frame(&f), requires));
+}
+
+#[test]
+fn frame_at_cut_points() {
+    let src = "\
+mov r1 0
+loop:
+;# req r1 <= 10
+add r1 1
+jlt r1 10 loop
+mov r0 0
+exit
+";
+    let mut f = FormulaBuilder::new();
+    let goals = goals(src, &mut f);
+    let (_, cut) = goals.iter().find(|(name, _)| name == "loop").unwrap();
+    let require = f.rel(Cc::Le, f.reg(Reg::R1).0, f.val(10));
+    assert_eq!(assumption(cut), &require);
+    let Formula::Bin(FBinOp::Implies, fs) = cut else {
+        unreachable!()
+    };
+    assert_eq!(assumption(&fs.1), &frame(&f));
+}
+
+#[test]
+fn tail_calls() {
+    // The callee's precondition must hold before the call, after which the path goes on.
+    let mut f = FormulaBuilder::new();
+    let goals = goals(";# tail_requires r1 = 5\ncall 12\nmov r0 0\nexit\n", &mut f);
+    let (_, entry) = goals.last().unwrap();
+    let Formula::Bin(FBinOp::Implies, fs) = entry else {
+        panic!("not an implication: {entry:?}")
+    };
+    let tail_requires = f.and_all([f.eq(f.reg(Reg::R1).0, f.val(5))]);
+    assert!(matches!(&fs.1, Formula::Bin(FBinOp::And, c) if c.0 == tail_requires));
+}
+
+/// Whether a formula contains a relation with the given comparison.
+fn has_rel(formula: &Formula, cc: Cc) -> bool {
+    match formula {
+        Formula::Rel(c, _, _) => *c == cc,
+        Formula::Not(g) | Formula::Quant(_, _, g) => has_rel(g, cc),
+        Formula::Bin(_, gs) => has_rel(&gs.0, cc) || has_rel(&gs.1, cc),
+        _ => false,
+    }
+}
+
+const COUNTDOWN: &str = "\
+mov r1 10
+loop:
+;# req r1 >= 1
+;# variant r1
+sub r1 1
+jgt r1 0 loop
+mov r0 0
+exit
+";
+
+#[test]
+fn variants() {
+    let mut f = FormulaBuilder::new();
+    let goals = goals(COUNTDOWN, &mut f);
+    let (_, cut) = goals.iter().find(|(name, _)| name == "loop").unwrap();
+    let Formula::Bin(FBinOp::Implies, fs) = cut else {
+        unreachable!()
+    };
+    // The variant is bounded below at the header, and decreases on the back edge.
+    let Formula::Bin(FBinOp::Implies, fs) = &fs.1 else {
+        panic!("frame isn't assumed: {:?}", fs.1)
+    };
+    let Formula::Bin(FBinOp::And, obligations) = &fs.1 else {
+        panic!("no variant obligations: {:?}", fs.1)
+    };
+    assert_eq!(obligations.0, f.rel(Cc::Ge, f.reg(Reg::R1).0, f.val(0)));
+    assert!(has_rel(&obligations.1, Cc::Lt));
+    // Entering the loop doesn't need a decrease.
+    let (_, entry) = goals.last().unwrap();
+    assert!(!has_rel(entry, Cc::Lt));
+}
+
+#[test]
+fn variant_bounds() {
+    let src = COUNTDOWN.replace(";# variant r1\n", ";# variant r1\n;# bound 10\n");
+    let mut f = FormulaBuilder::new();
+    let goals = goals(&src, &mut f);
+    let (_, cut) = goals.iter().find(|(name, _)| name == "loop").unwrap();
+    let Formula::Bin(FBinOp::Implies, fs) = cut else {
+        unreachable!()
+    };
+    let Formula::Bin(FBinOp::Implies, fs) = &fs.1 else {
+        unreachable!()
+    };
+    let Formula::Bin(FBinOp::And, obligations) = &fs.1 else {
+        panic!("no variant obligations: {:?}", fs.1)
+    };
+    assert_eq!(obligations.0, f.rel(Cc::Le, f.reg(Reg::R1).0, f.val(10)));
+}
@@ -1,6 +1,9 @@
 //! WhyML generation from formulas.
 
-use crate::ast::*;
+use crate::{
+    ast::*,
+    vc::{PKT_DATA, PKT_END},
+};
 
 pub struct Conditions(pub Vec<(String, Formula)>);
 
@@ -14,7 +17,7 @@ impl std::fmt::Display for Conditions {
         )?;
         for (name, goal) in self.0.iter() {
             f.write_fmt(format_args!(
-                "goal {name}: forall r0 r1 r2 r3 r4 r5 r6 r7 r8 r9 r10 : uint64 . {goal}\n\n"
+                "goal {name}: forall r0 r1 r2 r3 r4 r5 r6 r7 r8 r9 r10 {PKT_DATA} {PKT_END} : uint64 . {goal}\n\n"
             ))?;
         }
         Ok(())
@@ -101,6 +104,8 @@ impl std::fmt::Display for Formula {
                 f.write_fmt(format_args!("({e1} {rel_str} {e2})"))
             }
             Formula::IsBuffer(ptr, sz) => f.write_fmt(format_args!("is_buffer {ptr} {sz}")),
+            // Held references are checked statically.
+            Formula::Held(_) => f.write_str("true"),
         }
     }
 }
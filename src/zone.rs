@@ -0,0 +1,490 @@
+//! A relational abstract domain of zones, bounding the differences between pairs of values.
+//! Values are the registers and the ghost values of the verification conditions,
+//! along with the context pointer that the program starts with.
+//! Differences are of mathematical integers, so they are only kept through operations
+//! that are known not to wrap around, and the bounds of single values use [Intervals].
+
+use std::collections::BTreeSet;
+
+use crate::{
+    ast::FBinOp,
+    cfg::*,
+    dataflow::{Analysis, Direction, Lattice},
+    formula::FormulaBuilder,
+    helpers::helper_by_id,
+    init::{stmt_defs, stmt_kills},
+    interval::{constants, negated, unsigned, Interval, Intervals},
+    prog::{FieldKind, ProgType},
+    vc::{PKT_DATA, PKT_END},
+};
+
+/// A value that the zones relate.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Var {
+    /// The constant zero, so that differences with it bound single values.
+    Zero,
+    Reg(Reg),
+    PktData,
+    PktEnd,
+    /// The context pointer that the program starts with in `r1`.
+    Ctx,
+}
+
+const VARS: usize = 15;
+
+/// The largest value of a register.
+const MAX: i128 = u64::MAX as i128;
+
+impl Var {
+    fn index(self) -> usize {
+        match self {
+            Var::Zero => 0,
+            Var::Reg(r) => r.get() as usize + 1,
+            Var::PktData => 12,
+            Var::PktEnd => 13,
+            Var::Ctx => 14,
+        }
+    }
+
+    fn all() -> impl Iterator<Item = Var> {
+        [Var::Zero]
+            .into_iter()
+            .chain((0..11).filter_map(Reg::new).map(Var::Reg))
+            .chain([Var::PktData, Var::PktEnd, Var::Ctx])
+    }
+
+    /// The expression of the value in formulas, if it has one.
+    fn expr(self, f: &FormulaBuilder) -> Option<Expr> {
+        match self {
+            Var::Zero => Some(f.val(0)),
+            Var::Reg(r) => Some(f.reg(r).0),
+            Var::PktData => Some(f.var_ident(PKT_DATA.to_owned())),
+            Var::PktEnd => Some(f.var_ident(PKT_END.to_owned())),
+            Var::Ctx => None,
+        }
+    }
+}
+
+/// The bound on `a - b` that holds for any values of `a` and `b`.
+fn implicit(a: usize, b: usize) -> i128 {
+    match a == b || a == 0 {
+        true => 0,
+        false => MAX,
+    }
+}
+
+/// Bounds on the differences between all pairs of values, as a difference-bound matrix.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Dbm(Vec<i128>);
+
+impl Dbm {
+    fn top() -> Self {
+        Dbm((0..VARS * VARS)
+            .map(|k| implicit(k / VARS, k % VARS))
+            .collect())
+    }
+
+    /// The upper bound on `a - b`.
+    pub fn diff(&self, a: Var, b: Var) -> i128 {
+        self.0[a.index() * VARS + b.index()]
+    }
+
+    fn at(&mut self, a: usize, b: usize) -> &mut i128 {
+        &mut self.0[a * VARS + b]
+    }
+
+    /// Add the constraint `a - b <= c`.
+    fn constrain(&mut self, a: Var, b: Var, c: i128) {
+        let bound = self.at(a.index(), b.index());
+        *bound = (*bound).min(c);
+    }
+
+    /// Add the constraint `a - b = c`.
+    fn equate(&mut self, a: Var, b: Var, c: i128) {
+        self.constrain(a, b, c);
+        self.constrain(b, a, -c);
+    }
+
+    /// Tighten every bound to the tightest one implied by the others,
+    /// returning whether any values satisfy them.
+    fn close(&mut self) -> bool {
+        for k in 0..VARS {
+            for i in 0..VARS {
+                let ik = self.0[i * VARS + k];
+                for j in 0..VARS {
+                    let through = ik + self.0[k * VARS + j];
+                    let bound = self.at(i, j);
+                    *bound = (*bound).min(through);
+                }
+            }
+        }
+        (0..VARS).all(|i| self.0[i * VARS + i] >= 0)
+    }
+
+    /// Drop all constraints on a value.
+    fn forget(&mut self, var: Var) {
+        let v = var.index();
+        for j in 0..VARS {
+            *self.at(v, j) = implicit(v, j);
+            *self.at(j, v) = implicit(j, v);
+        }
+    }
+
+    /// Add a value in the range `[lo, hi]` to a value, keeping its differences with the others.
+    fn shift(&mut self, var: Var, lo: i128, hi: i128) {
+        let v = var.index();
+        for j in (0..VARS).filter(|j| *j != v) {
+            *self.at(v, j) = (self.0[v * VARS + j] + hi).min(implicit(v, j));
+            *self.at(j, v) = (self.0[j * VARS + v] - lo).min(implicit(j, v));
+        }
+    }
+
+    fn interval(&self, var: Var) -> Interval {
+        Interval {
+            lo: (-self.diff(Var::Zero, var)).clamp(0, MAX) as u64,
+            hi: self.diff(var, Var::Zero).clamp(0, MAX) as u64,
+        }
+    }
+
+    fn bound(&mut self, var: Var, interval: Interval) {
+        self.constrain(var, Var::Zero, interval.hi as i128);
+        self.constrain(Var::Zero, var, -(interval.lo as i128));
+    }
+
+    fn intervals(&self) -> Intervals {
+        let mut regs = [Interval::TOP; 11];
+        for (r, interval) in regs.iter_mut().enumerate() {
+            *interval = self.interval(Var::Reg(Reg::new(r as u8).unwrap()));
+        }
+        Intervals(Some(regs))
+    }
+}
+
+/// The constraints on the values at a program point, or `None` if it is unreachable.
+/// The matrix is kept closed, except right after widening.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Zones(pub Option<Dbm>);
+
+impl Lattice for Zones {
+    fn join(&mut self, other: &Self) -> bool {
+        let Some(b) = &other.0 else {
+            return false;
+        };
+        let Some(a) = &mut self.0 else {
+            self.0 = other.0.clone();
+            return true;
+        };
+        let mut changed = false;
+        for (a, b) in a.0.iter_mut().zip(b.0.iter()) {
+            if b > a {
+                *a = *b;
+                changed = true;
+            }
+        }
+        changed
+    }
+}
+
+/// A value that an operand of an expression in a formula stands for, plus an offset.
+fn linear(e: &Expr, f: &FormulaBuilder) -> Option<(Var, i128)> {
+    match e {
+        Expr::Val(i) if *i >= 0 => Some((Var::Zero, *i as i128)),
+        Expr::Var(x) if x == PKT_DATA => Some((Var::PktData, 0)),
+        Expr::Var(x) if x == PKT_END => Some((Var::PktEnd, 0)),
+        Expr::Var(x) => Some((Var::Reg(f.reg_of(x)?), 0)),
+        Expr::Binary(op @ (BinAlu::Add | BinAlu::Sub), es) => {
+            let (var, offset) = linear(&es.0, f)?;
+            let Expr::Val(i) = es.1 else {
+                return None;
+            };
+            match op {
+                BinAlu::Add => Some((var, offset + i as i128)),
+                _ => Some((var, offset - i as i128)),
+            }
+        }
+        _ => None,
+    }
+}
+
+impl Zones {
+    fn update(&mut self, op: impl FnOnce(&mut Dbm)) {
+        if let Some(dbm) = &mut self.0 {
+            op(dbm);
+            if !dbm.close() {
+                self.0 = None;
+            }
+        }
+    }
+
+    fn step(&mut self, stmt: &Stmt, prog: ProgType) {
+        self.update(|dbm| {
+            let before = dbm.intervals();
+            let mut after = before.clone();
+            after.step(stmt);
+            match stmt {
+                Stmt::Binary(WordSize::B64, BinAlu::Mov, dst, RegImm::Reg(src)) if dst != src => {
+                    dbm.forget(Var::Reg(*dst));
+                    dbm.equate(Var::Reg(*dst), Var::Reg(*src), 0);
+                }
+                Stmt::Binary(WordSize::B64, op @ (BinAlu::Add | BinAlu::Sub), dst, src)
+                    if *src != RegImm::Reg(*dst) =>
+                {
+                    let a = before.get(*dst).unwrap();
+                    let b = before.src(src).unwrap();
+                    let (lo, hi) = match op {
+                        BinAlu::Add => (b.lo as i128, b.hi as i128),
+                        _ => (-(b.hi as i128), -(b.lo as i128)),
+                    };
+                    match a.lo as i128 + lo >= 0 && a.hi as i128 + hi <= MAX {
+                        true => dbm.shift(Var::Reg(*dst), lo, hi),
+                        false => dbm.forget(Var::Reg(*dst)),
+                    }
+                }
+                // Loading the packet pointers from the context gives the ghost values.
+                Stmt::Load(size, dst, MemRef(base, offset))
+                    if dbm.diff(Var::Reg(*base), Var::Ctx) == 0
+                        && dbm.diff(Var::Ctx, Var::Reg(*base)) == 0 =>
+                {
+                    let ghost = match prog.ctx().and_then(|c| c.field(*offset, *size)) {
+                        Some(field) if field.kind == FieldKind::PacketData => Some(Var::PktData),
+                        Some(field) if field.kind == FieldKind::PacketEnd => Some(Var::PktEnd),
+                        _ => None,
+                    };
+                    dbm.forget(Var::Reg(*dst));
+                    if let Some(ghost) = ghost {
+                        dbm.equate(Var::Reg(*dst), ghost, 0);
+                        after.set(*dst, Interval::TOP);
+                    }
+                }
+                Stmt::Call(id) => {
+                    for r in 0..=5 {
+                        dbm.forget(Var::Reg(Reg::new(r).unwrap()));
+                    }
+                    if helper_by_id(*id).changes_pkt {
+                        dbm.forget(Var::PktData);
+                        dbm.forget(Var::PktEnd);
+                    }
+                }
+                Stmt::CallLocal(_) => {
+                    for r in 0..=5 {
+                        dbm.forget(Var::Reg(Reg::new(r).unwrap()));
+                    }
+                    dbm.forget(Var::PktData);
+                    dbm.forget(Var::PktEnd);
+                }
+                stmt => {
+                    for r in stmt_defs(stmt).into_iter().chain(stmt_kills(stmt)) {
+                        dbm.forget(Var::Reg(r));
+                    }
+                }
+            }
+            for r in (0..11).filter_map(Reg::new) {
+                dbm.bound(Var::Reg(r), after.get(r).unwrap());
+            }
+        });
+    }
+
+    /// Add the constraint `a + a_offset cc b + b_offset`.
+    fn compare(&mut self, cc: Cc, (a, a_offset): (Var, i128), (b, b_offset): (Var, i128)) {
+        let c = b_offset - a_offset;
+        self.update(|dbm| match cc {
+            Cc::Eq => dbm.equate(a, b, c),
+            Cc::Lt => dbm.constrain(a, b, c - 1),
+            Cc::Le => dbm.constrain(a, b, c),
+            Cc::Gt => dbm.constrain(b, a, -c - 1),
+            Cc::Ge => dbm.constrain(b, a, -c),
+            _ => (),
+        });
+    }
+
+    /// Restrict the values to those satisfying a comparison of registers.
+    fn assume(&mut self, cc: Cc, lhs: Reg, rhs: &RegImm) {
+        let Some(dbm) = &self.0 else {
+            return;
+        };
+        let mut intervals = dbm.intervals();
+        let (a, b) = (intervals.get(lhs).unwrap(), intervals.src(rhs).unwrap());
+        if let Some(cc) = unsigned(cc, a, b) {
+            let rhs = match rhs {
+                RegImm::Reg(r) => (Var::Reg(*r), 0),
+                RegImm::Imm(i) => (Var::Zero, *i as u64 as i128),
+            };
+            self.compare(cc, (Var::Reg(lhs), 0), rhs);
+        }
+        // Intervals also handle the comparisons that zones can't express.
+        intervals.assume(cc, lhs, rhs);
+        let Intervals(Some(regs)) = intervals else {
+            self.0 = None;
+            return;
+        };
+        self.update(|dbm| {
+            for (r, interval) in regs.iter().enumerate() {
+                dbm.bound(Var::Reg(Reg::new(r as u8).unwrap()), *interval);
+            }
+        });
+    }
+
+    /// Assume the comparisons between values plus constants within a formula,
+    /// reading the arithmetic in it as not wrapping around.
+    fn assume_formula(&mut self, formula: &Formula, f: &FormulaBuilder) {
+        match formula {
+            Formula::Bin(FBinOp::And | FBinOp::AndAsym, fs) => {
+                self.assume_formula(&fs.0, f);
+                self.assume_formula(&fs.1, f);
+            }
+            Formula::Rel(cc, a, b) => {
+                if let (Some(a), Some(b)) = (linear(a, f), linear(b, f)) {
+                    self.compare(*cc, a, b);
+                }
+            }
+            _ => (),
+        }
+    }
+
+    /// Express the constraints as formulas, leaving out the ones that others imply.
+    /// Values that are equal up to a constant are expressed in terms of the first of them,
+    /// and the other constraints only relate these.
+    pub fn formulas(&self, f: &FormulaBuilder) -> Vec<Formula> {
+        let Some(dbm) = &self.0 else {
+            return vec![f.bot()];
+        };
+        let vars: Vec<Var> = Var::all().filter(|v| v.expr(f).is_some()).collect();
+        let fits = |c: i128| c.unsigned_abs() <= i64::MAX as u128;
+        let mut result = Vec::new();
+        let mut reps: Vec<Var> = Vec::new();
+        for &var in vars.iter() {
+            let rep = reps
+                .iter()
+                .find(|r| dbm.diff(var, **r) == -dbm.diff(**r, var))
+                .copied();
+            let Some(rep) = rep else {
+                reps.push(var);
+                continue;
+            };
+            let c = dbm.diff(var, rep);
+            let e = var.expr(f).unwrap();
+            let rhs = match (rep, c) {
+                (_, c) if !fits(c) => continue,
+                (Var::Zero, c) => f.val(c as Imm),
+                (rep, 0) => rep.expr(f).unwrap(),
+                (rep, c) if c > 0 => f.binop(BinAlu::Add, rep.expr(f).unwrap(), f.val(c as Imm)),
+                (rep, c) => f.binop(BinAlu::Sub, rep.expr(f).unwrap(), f.val(-c as Imm)),
+            };
+            result.push(f.eq(e, rhs));
+        }
+
+        for &a in reps.iter() {
+            for &b in reps.iter().filter(|b| **b != a) {
+                let c = dbm.diff(a, b);
+                let trivial = c >= implicit(a.index(), b.index());
+                let redundant = reps
+                    .iter()
+                    .filter(|k| **k != a && **k != b)
+                    .any(|k| dbm.diff(a, *k) + dbm.diff(*k, b) <= c);
+                if trivial || redundant {
+                    continue;
+                }
+                let (ea, eb) = (a.expr(f).unwrap(), b.expr(f).unwrap());
+                let formula = match (a, b, c) {
+                    (Var::Zero, _, c) if fits(c) => f.rel(Cc::Ge, eb, f.val(-c as Imm)),
+                    (_, Var::Zero, c) if fits(c) => f.rel(Cc::Le, ea, f.val(c as Imm)),
+                    (_, _, 0) => f.rel(Cc::Le, ea, eb),
+                    (_, _, -1) => f.rel(Cc::Lt, ea, eb),
+                    (_, _, c) if c < 0 && fits(c) => {
+                        f.rel(Cc::Lt, ea, f.binop(BinAlu::Sub, eb, f.val(-c as Imm - 1)))
+                    }
+                    // Only add to the other value if that can't wrap around.
+                    (_, _, c) if fits(c) && dbm.diff(b, Var::Zero) + c <= MAX => {
+                        f.rel(Cc::Le, ea, f.binop(BinAlu::Add, eb, f.val(c as Imm)))
+                    }
+                    _ => continue,
+                };
+                result.push(formula);
+            }
+        }
+        result
+    }
+}
+
+/// Zones of the registers and ghost values, starting from the constraints that the program requires.
+/// Widening jumps to constants of the program, so that loops bounded by them stay precise.
+pub struct ZoneAnalysis<'a> {
+    prog: ProgType,
+    f: &'a FormulaBuilder,
+    thresholds: BTreeSet<i128>,
+}
+
+impl<'a> ZoneAnalysis<'a> {
+    pub fn new(cfg: &Cfg, prog: ProgType, f: &'a FormulaBuilder) -> Self {
+        let thresholds = constants(cfg)
+            .into_iter()
+            .flat_map(|c| [c as i128, c as u64 as i128])
+            .flat_map(|c| [c - 1, c, c + 1, -c - 1, -c, -c + 1])
+            .collect();
+        ZoneAnalysis {
+            prog,
+            f,
+            thresholds,
+        }
+    }
+}
+
+impl Analysis for ZoneAnalysis<'_> {
+    type Fact = Zones;
+    const DIRECTION: Direction = Direction::Forward;
+
+    fn bottom(&self, _cfg: &Cfg) -> Self::Fact {
+        Zones(None)
+    }
+
+    fn boundary(&self, cfg: &Cfg) -> Self::Fact {
+        let mut fact = Zones(Some(Dbm::top()));
+        if self.prog.ctx().is_some() {
+            fact.update(|dbm| dbm.equate(Var::Reg(Reg::R1), Var::Ctx, 0));
+        }
+        fact.assume_formula(&cfg.requires, self.f);
+        fact
+    }
+
+    fn transfer(&self, _label: &Label, _index: usize, stmt: &Stmt, fact: &mut Self::Fact) {
+        fact.step(stmt, self.prog);
+    }
+
+    fn edge(&self, _label: &Label, next: &Continuation, target: &Label, fact: &mut Self::Fact) {
+        if let Continuation::Jcc(cc, lhs, rhs, target_t, target_f) = next {
+            if target_t != target_f {
+                match target == target_t {
+                    true => fact.assume(*cc, *lhs, rhs),
+                    false => {
+                        if let Some(cc) = negated(*cc) {
+                            fact.assume(cc, *lhs, rhs);
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    fn widen(&self, fact: &mut Self::Fact, other: &Self::Fact) -> bool {
+        let (Some(a), Some(b)) = (&mut fact.0, &other.0) else {
+            return fact.join(other);
+        };
+        let mut changed = false;
+        for (k, (a, b)) in a.0.iter_mut().zip(b.0.iter()).enumerate() {
+            if b > a {
+                let max = implicit(k / VARS, k % VARS);
+                *a = self
+                    .thresholds
+                    .range(b..=&max)
+                    .next()
+                    .copied()
+                    .unwrap_or(max);
+                changed = true;
+            }
+        }
+        changed
+    }
+}
+
+#[cfg(test)]
+mod tests;
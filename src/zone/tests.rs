@@ -0,0 +1,79 @@
+use super::*;
+use crate::dataflow::solve;
+
+/// A loop moving `r1` and `r2` in lockstep.
+const LOCKSTEP: &str = "\
+a:
+    mov r1 0
+    mov r2 5
+l:
+    add r1 1
+    add r2 1
+    jlt r1 10 l
+e:
+    mov r0 r2
+    exit
+";
+
+/// Zones with only the constraint `r1 - r2 <= c`.
+fn r1_minus_r2(c: i128) -> Zones {
+    let mut dbm = Dbm::top();
+    dbm.constrain(Var::Reg(Reg::R1), Var::Reg(Reg::R2), c);
+    Zones(Some(dbm))
+}
+
+fn diff(zones: &Zones) -> i128 {
+    zones
+        .0
+        .as_ref()
+        .unwrap()
+        .diff(Var::Reg(Reg::R1), Var::Reg(Reg::R2))
+}
+
+#[test]
+fn widening_to_thresholds() {
+    let cfg = Cfg::parse(LOCKSTEP);
+    let f = FormulaBuilder::new();
+    let analysis = ZoneAnalysis::new(&cfg, ProgType::Function, &f);
+
+    // Growing bounds jump to the nearest constant of the program, its negation or their neighbours.
+    let mut fact = r1_minus_r2(-5);
+    assert!(analysis.widen(&mut fact, &r1_minus_r2(-3)));
+    assert_eq!(diff(&fact), -2);
+    assert!(!analysis.widen(&mut fact, &r1_minus_r2(-4)));
+    assert!(analysis.widen(&mut fact, &r1_minus_r2(7)));
+    assert_eq!(diff(&fact), 9);
+    // Past the largest constant, nothing bounds them.
+    assert!(analysis.widen(&mut fact, &r1_minus_r2(20)));
+    assert_eq!(diff(&fact), MAX);
+}
+
+#[test]
+fn widening_unreachable() {
+    let cfg = Cfg::parse(LOCKSTEP);
+    let f = FormulaBuilder::new();
+    let analysis = ZoneAnalysis::new(&cfg, ProgType::Function, &f);
+    let mut fact = Zones(None);
+    assert!(analysis.widen(&mut fact, &r1_minus_r2(3)));
+    assert_eq!(diff(&fact), 3);
+    assert!(!analysis.widen(&mut fact, &Zones(None)));
+}
+
+#[test]
+fn relational_invariant() {
+    let cfg = Cfg::parse(LOCKSTEP);
+    let f = FormulaBuilder::new();
+    let analysis = ZoneAnalysis::new(&cfg, ProgType::Function, &f);
+    let results = solve(&cfg, &analysis);
+
+    // The difference survives widening, although neither register is constant.
+    let head = results.entry(&label("l")).unwrap();
+    let dbm = head.0.as_ref().unwrap();
+    assert_eq!(dbm.diff(Var::Reg(Reg::R2), Var::Reg(Reg::R1)), 5);
+    assert_eq!(dbm.diff(Var::Reg(Reg::R1), Var::Reg(Reg::R2)), -5);
+    assert_eq!(dbm.diff(Var::Reg(Reg::R1), Var::Zero), 9);
+
+    let exit = results.entry(&label("e")).unwrap();
+    let dbm = exit.0.as_ref().unwrap();
+    assert_eq!(dbm.interval(Var::Reg(Reg::R2)), Interval { lo: 15, hi: 15 });
+}